@@ -0,0 +1,390 @@
+//! A minimal reader for DER-encoded ASN.1, which is all that's needed to pick apart Authenticode
+//! signatures and the certificates inside of them.
+
+use crate::error::{Error, Result};
+use std::fmt::Write as _;
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_PRINTABLE_STRING: u8 = 0x13;
+pub const TAG_T61_STRING: u8 = 0x14;
+pub const TAG_IA5_STRING: u8 = 0x16;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_UNIVERSAL_STRING: u8 = 0x1c;
+pub const TAG_BMP_STRING: u8 = 0x1e;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// The tag of a context-specific field, such as `[0]` or `[1] IMPLICIT`.
+#[inline]
+pub const fn context_tag(number: u8, is_constructed: bool) -> u8 {
+    0x80 | (if is_constructed { 0x20 } else { 0 }) | number
+}
+
+/// A single DER value.
+#[derive(Debug, Clone, Copy)]
+pub struct DerValue<'a> {
+    pub tag: u8,
+
+    /// The contents of the value, without the tag and length.
+    pub contents: &'a [u8],
+
+    /// The whole encoding of the value, including the tag and length.
+    pub raw: &'a [u8],
+}
+
+/// Reads DER values one after another out of a buffer.
+#[derive(Debug, Clone)]
+pub struct DerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The tag of the next value, if there is one.
+    #[inline]
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Reads the next value, whatever it is.
+    pub fn read(&mut self) -> Result<DerValue<'a>> {
+        let tag = *self
+            .data
+            .first()
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        if tag & 0x1f == 0x1f {
+            return Err(invalid("high tag numbers aren't supported"));
+        }
+        let first_length = *self.data.get(1).ok_or_else(|| invalid("missing length"))?;
+        let (length, header_size) = if first_length & 0x80 == 0 {
+            (first_length as usize, 2)
+        } else {
+            let length_size = (first_length & 0x7f) as usize;
+            if length_size == 0 {
+                return Err(invalid("indefinite lengths aren't allowed"));
+            } else if length_size > 4 {
+                return Err(invalid("length is too long"));
+            }
+            let bytes = self
+                .data
+                .get(2..2 + length_size)
+                .ok_or_else(|| invalid("truncated length"))?;
+            let length = bytes
+                .iter()
+                .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+            (length, 2 + length_size)
+        };
+        let end = header_size
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid("value runs past the end of the data"))?;
+        let value = DerValue {
+            tag,
+            contents: &self.data[header_size..end],
+            raw: &self.data[..end],
+        };
+        self.data = &self.data[end..];
+        Ok(value)
+    }
+
+    /// Reads the next value, failing if it doesn't have the expected tag.
+    pub fn read_tag(&mut self, tag: u8) -> Result<DerValue<'a>> {
+        let value = self.read()?;
+        if value.tag == tag {
+            Ok(value)
+        } else {
+            Err(invalid(format!(
+                "expected tag 0x{:02x}, but got 0x{:02x}",
+                tag, value.tag
+            )))
+        }
+    }
+
+    /// Reads the next value if it has the provided tag, which is how optional fields are handled.
+    pub fn read_optional(&mut self, tag: u8) -> Result<Option<DerValue<'a>>> {
+        if self.peek_tag() == Some(tag) {
+            Ok(Some(self.read()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads every remaining value.
+    pub fn read_all(&mut self) -> Result<Vec<DerValue<'a>>> {
+        let mut values = Vec::new();
+        while !self.is_empty() {
+            values.push(self.read()?);
+        }
+        Ok(values)
+    }
+}
+
+impl<'a> DerValue<'a> {
+    /// Parses a buffer that holds exactly one value.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let mut reader = DerReader::new(data);
+        let value = reader.read()?;
+        if reader.is_empty() {
+            Ok(value)
+        } else {
+            Err(invalid("trailing data after value"))
+        }
+    }
+
+    /// A reader over the values inside of this one, for SEQUENCEs, SETs and explicitly tagged
+    /// fields.
+    #[inline]
+    pub fn reader(&self) -> DerReader<'a> {
+        DerReader::new(self.contents)
+    }
+
+    /// Decodes an OBJECT IDENTIFIER into its dotted form, such as "1.2.840.113549.1.7.2".
+    pub fn as_oid(&self) -> Result<String> {
+        if self.tag != TAG_OID || self.contents.is_empty() {
+            return Err(invalid("expected an object identifier"));
+        }
+        let mut arcs = Vec::new();
+        let mut arc = 0u64;
+        for byte in self.contents {
+            if arc > u64::MAX >> 7 {
+                return Err(invalid("object identifier arc is too large"));
+            }
+            arc = (arc << 7) | (byte & 0x7f) as u64;
+            if byte & 0x80 == 0 {
+                arcs.push(arc);
+                arc = 0;
+            }
+        }
+        if self.contents.last().unwrap() & 0x80 != 0 {
+            return Err(invalid("truncated object identifier"));
+        }
+        let mut oid = match arcs[0] {
+            first if first < 40 => format!("0.{}", first),
+            first if first < 80 => format!("1.{}", first - 40),
+            first => format!("2.{}", first - 80),
+        };
+        for arc in &arcs[1..] {
+            write!(oid, ".{}", arc).unwrap();
+        }
+        Ok(oid)
+    }
+
+    /// Gets the big-endian bytes of an INTEGER, with any leading zero byte that's only there to
+    /// keep the value positive removed. This is how serial numbers are kept.
+    pub fn as_integer_bytes(&self) -> Result<&'a [u8]> {
+        if self.tag != TAG_INTEGER || self.contents.is_empty() {
+            return Err(invalid("expected an integer"));
+        }
+        match self.contents {
+            [0, rest @ ..] if !rest.is_empty() => Ok(rest),
+            contents => Ok(contents),
+        }
+    }
+
+    /// Decodes a small, non-negative INTEGER, such as a version number.
+    pub fn as_u64(&self) -> Result<u64> {
+        let bytes = self.as_integer_bytes()?;
+        if bytes.len() > 8 || (self.contents[0] & 0x80 != 0) {
+            return Err(invalid("integer doesn't fit in a u64"));
+        }
+        Ok(bytes
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64))
+    }
+
+    /// Decodes a BOOLEAN.
+    pub fn as_bool(&self) -> Result<bool> {
+        match (self.tag, self.contents) {
+            (TAG_BOOLEAN, [value]) => Ok(*value != 0),
+            _ => Err(invalid("expected a boolean")),
+        }
+    }
+
+    /// Gets the bits of a BIT STRING. Anything that's signed or used as a key is a whole number of
+    /// bytes, so the unused bits count is ignored.
+    pub fn as_bit_string(&self) -> Result<&'a [u8]> {
+        match (self.tag, self.contents) {
+            (TAG_BIT_STRING, [_unused_bits, bits @ ..]) => Ok(bits),
+            _ => Err(invalid("expected a bit string")),
+        }
+    }
+
+    /// Gets the contents of an OCTET STRING.
+    pub fn as_octet_string(&self) -> Result<&'a [u8]> {
+        if self.tag == TAG_OCTET_STRING {
+            Ok(self.contents)
+        } else {
+            Err(invalid("expected an octet string"))
+        }
+    }
+
+    /// Decodes any of the string types that show up in certificates.
+    pub fn as_string(&self) -> Result<String> {
+        match self.tag {
+            TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_IA5_STRING => {
+                Ok(String::from_utf8(self.contents.to_vec())?)
+            }
+            // T61 strings are treated as Latin-1, which is what they almost always are in
+            // practice.
+            TAG_T61_STRING => Ok(self.contents.iter().map(|byte| *byte as char).collect()),
+            TAG_BMP_STRING => decode_bmp_string(self.contents),
+            TAG_UNIVERSAL_STRING => self
+                .contents
+                .chunks(4)
+                .map(|chunk| match chunk {
+                    [a, b, c, d] => char::from_u32(u32::from_be_bytes([*a, *b, *c, *d]))
+                        .ok_or_else(|| invalid("invalid character in universal string")),
+                    _ => Err(invalid("truncated universal string")),
+                })
+                .collect(),
+            tag => Err(invalid(format!("tag 0x{:02x} isn't a string", tag))),
+        }
+    }
+
+    /// Decodes a UTCTime or GeneralizedTime into seconds since the Unix epoch. Only the forms that
+    /// DER allows are accepted: times have to be in UTC and end with "Z".
+    pub fn as_time(&self) -> Result<i64> {
+        // Checking for ASCII up front means the fields below can be sliced by byte offset.
+        if !self.contents.is_ascii() {
+            return Err(invalid("time isn't valid ASCII"));
+        }
+        let text = std::str::from_utf8(self.contents).unwrap();
+        let digits = text
+            .strip_suffix('Z')
+            .ok_or_else(|| invalid("time isn't in UTC"))?;
+        // Fractional seconds are allowed in GeneralizedTime, but they don't matter here.
+        let digits = digits.split('.').next().unwrap();
+        let (year, rest) = match self.tag {
+            TAG_UTC_TIME if digits.len() == 12 => {
+                let year = parse_digits(&digits[..2])?;
+                (
+                    if year >= 50 { 1900 + year } else { 2000 + year },
+                    &digits[2..],
+                )
+            }
+            TAG_GENERALIZED_TIME if digits.len() == 14 => {
+                (parse_digits(&digits[..4])?, &digits[4..])
+            }
+            _ => return Err(invalid("expected a time")),
+        };
+        let month = parse_digits(&rest[0..2])?;
+        let day = parse_digits(&rest[2..4])?;
+        let hour = parse_digits(&rest[4..6])?;
+        let minute = parse_digits(&rest[6..8])?;
+        let second = parse_digits(&rest[8..10])?;
+        // A second of 60 is allowed for leap seconds.
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(invalid(format!("invalid time {}", text)));
+        }
+        Ok(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+    }
+}
+
+/// Decodes a big-endian UTF-16 string, which is how BMPStrings are stored.
+pub(crate) fn decode_bmp_string(data: &[u8]) -> Result<String> {
+    if data.len() % 2 != 0 {
+        return Err(invalid("BMP string has an odd length"));
+    }
+    let units: Vec<u16> = data
+        .chunks(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|_| invalid("invalid UTF-16 in BMP string"))
+}
+
+fn parse_digits(digits: &str) -> Result<i64> {
+    if digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        Ok(digits.parse().unwrap())
+    } else {
+        Err(invalid(format!("{} isn't a number", digits)))
+    }
+}
+
+/// The number of days between the Unix epoch and the provided date in the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+pub(crate) fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::InvalidDer {
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(tag: u8, contents: &[u8]) -> Result<i64> {
+        DerValue {
+            tag,
+            contents,
+            raw: &[],
+        }
+        .as_time()
+    }
+
+    #[test]
+    fn decodes_times() {
+        assert_eq!(time(TAG_UTC_TIME, b"700101000000Z").unwrap(), 0);
+        assert_eq!(time(TAG_UTC_TIME, b"500101000000Z").unwrap(), -631152000);
+        assert_eq!(
+            time(TAG_GENERALIZED_TIME, b"20000301000000Z").unwrap(),
+            951868800
+        );
+        assert_eq!(
+            time(TAG_GENERALIZED_TIME, b"20000301000000.123Z").unwrap(),
+            951868800
+        );
+        // Leap seconds are accepted.
+        assert_eq!(time(TAG_UTC_TIME, b"491231235960Z").unwrap(), 2524608000);
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        assert!(time(TAG_UTC_TIME, b"700101000061Z").is_err());
+        assert!(time(TAG_UTC_TIME, b"701301000000Z").is_err());
+        assert!(time(TAG_UTC_TIME, b"700101240000Z").is_err());
+        assert!(time(TAG_UTC_TIME, b"7001010000+0Z").is_err());
+        assert!(time(TAG_UTC_TIME, b"700101000000+0100").is_err());
+        assert!(time(TAG_GENERALIZED_TIME, b"700101000000Z").is_err());
+        assert!(time(TAG_INTEGER, b"700101000000Z").is_err());
+        // Multi-byte characters used to make slicing the fields panic.
+        assert!(time(TAG_UTC_TIME, "70010100000\u{e9}Z".as_bytes()).is_err());
+        assert!(time(TAG_GENERALIZED_TIME, "2000030100\u{e9}\u{e9}Z".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_values() {
+        assert!(DerValue::parse(&[TAG_SEQUENCE, 0x84, 0xff, 0xff, 0xff, 0xff]).is_err());
+        assert!(DerValue::parse(&[TAG_SEQUENCE, 0x02, 0x05]).is_err());
+        assert!(DerValue::parse(&[TAG_SEQUENCE]).is_err());
+        assert!(DerValue::parse(&[TAG_NULL, 0x00, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! The object identifiers that show up in Authenticode signatures.
+
+pub const SHA1: &str = "1.3.14.3.2.26";
+pub const SHA256: &str = "2.16.840.1.101.3.4.2.1";
+pub const SHA384: &str = "2.16.840.1.101.3.4.2.2";
+pub const SHA512: &str = "2.16.840.1.101.3.4.2.3";
+
+pub const RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+pub const SHA1_WITH_RSA: &str = "1.2.840.113549.1.1.5";
+pub const SHA256_WITH_RSA: &str = "1.2.840.113549.1.1.11";
+pub const SHA384_WITH_RSA: &str = "1.2.840.113549.1.1.12";
+pub const SHA512_WITH_RSA: &str = "1.2.840.113549.1.1.13";
+pub const EC_PUBLIC_KEY: &str = "1.2.840.10045.2.1";
+pub const SECP256R1: &str = "1.2.840.10045.3.1.7";
+pub const SECP384R1: &str = "1.3.132.0.34";
+pub const ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+pub const ECDSA_WITH_SHA384: &str = "1.2.840.10045.4.3.3";
+pub const ECDSA_WITH_SHA512: &str = "1.2.840.10045.4.3.4";
+
+pub const PKCS7_DATA: &str = "1.2.840.113549.1.7.1";
+pub const PKCS7_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+
+pub const CONTENT_TYPE: &str = "1.2.840.113549.1.9.3";
+pub const MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+pub const SIGNING_TIME: &str = "1.2.840.113549.1.9.5";
+pub const COUNTER_SIGNATURE: &str = "1.2.840.113549.1.9.6";
+pub const TST_INFO: &str = "1.2.840.113549.1.9.16.1.4";
+
+pub const SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+pub const SPC_STATEMENT_TYPE: &str = "1.3.6.1.4.1.311.2.1.11";
+pub const SPC_SP_OPUS_INFO: &str = "1.3.6.1.4.1.311.2.1.12";
+pub const SPC_PE_IMAGE_DATA: &str = "1.3.6.1.4.1.311.2.1.15";
+pub const SPC_NESTED_SIGNATURE: &str = "1.3.6.1.4.1.311.2.4.1";
+pub const SPC_RFC3161_TIMESTAMP: &str = "1.3.6.1.4.1.311.3.3.1";
+
+pub const COMMON_NAME: &str = "2.5.4.3";
+pub const COUNTRY: &str = "2.5.4.6";
+pub const LOCALITY: &str = "2.5.4.7";
+pub const STATE: &str = "2.5.4.8";
+pub const ORGANIZATION: &str = "2.5.4.10";
+pub const ORGANIZATIONAL_UNIT: &str = "2.5.4.11";
+pub const EMAIL_ADDRESS: &str = "1.2.840.113549.1.9.1";
+
+pub const BASIC_CONSTRAINTS: &str = "2.5.29.19";
+pub const KEY_USAGE: &str = "2.5.29.15";
+pub const EXTENDED_KEY_USAGE: &str = "2.5.29.37";
+
+pub const CODE_SIGNING: &str = "1.3.6.1.5.5.7.3.3";
+pub const TIME_STAMPING: &str = "1.3.6.1.5.5.7.3.8";
+pub const SUBJECT_KEY_IDENTIFIER: &str = "2.5.29.14";
@@ -0,0 +1,532 @@
+//! The PKCS#7 SignedData structure that an Authenticode signature is stored in.
+
+use super::{
+    der::{
+        context_tag, decode_bmp_string, invalid, DerReader, DerValue, TAG_INTEGER,
+        TAG_OCTET_STRING, TAG_SEQUENCE, TAG_SET,
+    },
+    oid,
+    x509::{expect_sequence, parse_certificates, AlgorithmIdentifier, Certificate, Name},
+};
+use crate::error::Result;
+
+/// An attribute of a signer, with its values still encoded.
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub oid: String,
+    pub values: Vec<Vec<u8>>,
+}
+
+impl Attribute {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut attribute = expect_sequence(value)?.reader();
+        let oid = attribute.read()?.as_oid()?;
+        let values = attribute
+            .read_tag(TAG_SET)?
+            .reader()
+            .read_all()?
+            .into_iter()
+            .map(|value| value.raw.to_vec())
+            .collect();
+        Ok(Self { oid, values })
+    }
+}
+
+fn parse_attributes(value: &DerValue<'_>) -> Result<Vec<Attribute>> {
+    value
+        .reader()
+        .read_all()?
+        .iter()
+        .map(Attribute::parse)
+        .collect()
+}
+
+/// Finds the first value of the attribute with the provided OID.
+fn find_attribute<'a>(attributes: &'a [Attribute], attribute_oid: &str) -> Option<&'a [u8]> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.oid == attribute_oid)
+        .and_then(|attribute| attribute.values.first())
+        .map(|value| value.as_slice())
+}
+
+/// How a signer says which certificate is theirs.
+#[derive(Debug, Clone)]
+pub enum SignerIdentifier {
+    IssuerAndSerialNumber {
+        issuer: Name,
+        serial_number: Vec<u8>,
+    },
+    SubjectKeyIdentifier(Vec<u8>),
+}
+
+/// Information about a signer, including the signature itself.
+#[derive(Debug, Clone)]
+pub struct SignerInfo {
+    pub version: u64,
+    pub signer: SignerIdentifier,
+
+    /// The algorithm used to hash the content and the authenticated attributes.
+    pub digest_algorithm: AlgorithmIdentifier,
+
+    /// The attributes that are covered by the signature.
+    pub authenticated_attributes: Vec<Attribute>,
+
+    /// The authenticated attributes encoded as a SET, which is what's actually signed. `None` if
+    /// the signer didn't include any authenticated attributes.
+    pub authenticated_attributes_der: Option<Vec<u8>>,
+
+    /// The algorithm used to sign.
+    pub signature_algorithm: AlgorithmIdentifier,
+
+    /// The signature.
+    pub encrypted_digest: Vec<u8>,
+
+    /// The attributes that aren't covered by the signature, such as timestamps and nested
+    /// signatures.
+    pub unauthenticated_attributes: Vec<Attribute>,
+}
+
+impl SignerInfo {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut fields = expect_sequence(value)?.reader();
+        let version = fields.read_tag(TAG_INTEGER)?.as_u64()?;
+        let signer_id = fields.read()?;
+        let signer = match signer_id.tag {
+            TAG_SEQUENCE => {
+                let mut issuer_and_serial = signer_id.reader();
+                SignerIdentifier::IssuerAndSerialNumber {
+                    issuer: Name::parse(&issuer_and_serial.read()?)?,
+                    serial_number: issuer_and_serial
+                        .read_tag(TAG_INTEGER)?
+                        .as_integer_bytes()?
+                        .to_vec(),
+                }
+            }
+            tag if tag == context_tag(0, false) => {
+                SignerIdentifier::SubjectKeyIdentifier(signer_id.contents.to_vec())
+            }
+            tag => {
+                return Err(invalid(format!(
+                    "unknown signer identifier tag 0x{:02x}",
+                    tag
+                )))
+            }
+        };
+        let digest_algorithm = AlgorithmIdentifier::parse(&fields.read()?)?;
+        let (authenticated_attributes, authenticated_attributes_der) =
+            match fields.read_optional(context_tag(0, true))? {
+                Some(attributes) => {
+                    // The attributes are tagged [0] IMPLICIT, but they're signed as a SET.
+                    let mut der = attributes.raw.to_vec();
+                    der[0] = TAG_SET;
+                    (parse_attributes(&attributes)?, Some(der))
+                }
+                None => (Vec::new(), None),
+            };
+        let signature_algorithm = AlgorithmIdentifier::parse(&fields.read()?)?;
+        let encrypted_digest = fields.read_tag(TAG_OCTET_STRING)?.contents.to_vec();
+        let unauthenticated_attributes = match fields.read_optional(context_tag(1, true))? {
+            Some(attributes) => parse_attributes(&attributes)?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            version,
+            signer,
+            digest_algorithm,
+            authenticated_attributes,
+            authenticated_attributes_der,
+            signature_algorithm,
+            encrypted_digest,
+            unauthenticated_attributes,
+        })
+    }
+
+    /// Gets the first encoded value of the authenticated attribute with the provided OID.
+    #[inline]
+    pub fn authenticated_attribute(&self, attribute_oid: &str) -> Option<&[u8]> {
+        find_attribute(&self.authenticated_attributes, attribute_oid)
+    }
+
+    /// Gets every encoded value of the unauthenticated attributes with the provided OID.
+    pub fn unauthenticated_attribute_values<'a>(
+        &'a self,
+        attribute_oid: &'a str,
+    ) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.unauthenticated_attributes
+            .iter()
+            .filter(move |attribute| attribute.oid == attribute_oid)
+            .flat_map(|attribute| attribute.values.iter().map(|value| value.as_slice()))
+    }
+
+    /// The hash of the content, from the messageDigest attribute.
+    pub fn message_digest(&self) -> Result<Option<Vec<u8>>> {
+        self.authenticated_attribute(oid::MESSAGE_DIGEST)
+            .map(|value| Ok(DerValue::parse(value)?.as_octet_string()?.to_vec()))
+            .transpose()
+    }
+
+    /// The type of the content, from the contentType attribute.
+    pub fn content_type(&self) -> Result<Option<String>> {
+        self.authenticated_attribute(oid::CONTENT_TYPE)
+            .map(|value| DerValue::parse(value)?.as_oid())
+            .transpose()
+    }
+
+    /// The time the signer says they signed at, from the signingTime attribute. This is only
+    /// trustworthy if it comes from a timestamp authority.
+    pub fn signing_time(&self) -> Result<Option<i64>> {
+        self.authenticated_attribute(oid::SIGNING_TIME)
+            .map(|value| DerValue::parse(value)?.as_time())
+            .transpose()
+    }
+
+    /// The program name and URL the publisher supplied when signing.
+    pub fn opus_info(&self) -> Result<Option<SpcSpOpusInfo>> {
+        self.authenticated_attribute(oid::SPC_SP_OPUS_INFO)
+            .map(|value| SpcSpOpusInfo::parse(&DerValue::parse(value)?))
+            .transpose()
+    }
+}
+
+/// The program name and URL from the SpcSpOpusInfo authenticated attribute.
+#[derive(Debug, Clone, Default)]
+pub struct SpcSpOpusInfo {
+    pub program_name: Option<String>,
+
+    /// A URL with more information about the program.
+    pub more_info: Option<String>,
+}
+
+impl SpcSpOpusInfo {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut fields = expect_sequence(value)?.reader();
+        let program_name = match fields.read_optional(context_tag(0, true))? {
+            Some(name) => parse_spc_string(&name.reader().read()?)?,
+            None => None,
+        };
+        let more_info = match fields.read_optional(context_tag(1, true))? {
+            Some(link) => parse_spc_link(&link.reader().read()?)?,
+            None => None,
+        };
+        Ok(Self {
+            program_name,
+            more_info,
+        })
+    }
+}
+
+/// Decodes an SpcString, which is either a BMPString or an IA5String.
+fn parse_spc_string(value: &DerValue<'_>) -> Result<Option<String>> {
+    match value.tag {
+        tag if tag == context_tag(0, false) => Ok(Some(decode_bmp_string(value.contents)?)),
+        tag if tag == context_tag(1, false) => {
+            Ok(Some(String::from_utf8(value.contents.to_vec())?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Decodes an SpcLink, which is either a URL, a serialized object or a file name. Serialized
+/// objects don't have a useful text form, so they're ignored.
+fn parse_spc_link(value: &DerValue<'_>) -> Result<Option<String>> {
+    match value.tag {
+        tag if tag == context_tag(0, false) => {
+            Ok(Some(String::from_utf8(value.contents.to_vec())?))
+        }
+        tag if tag == context_tag(2, true) => parse_spc_string(&value.reader().read()?),
+        _ => Ok(None),
+    }
+}
+
+/// The content of an Authenticode signature, which holds the digest of the image.
+#[derive(Debug, Clone)]
+pub struct SpcIndirectDataContent {
+    /// The type of the signed data, which is SpcPeImageData for PE images.
+    pub data_type: String,
+
+    /// The encoded value of the signed data, if there is one.
+    pub data: Option<Vec<u8>>,
+
+    pub digest_algorithm: AlgorithmIdentifier,
+
+    /// The Authenticode digest of the image.
+    pub digest: Vec<u8>,
+
+    /// The bytes that the messageDigest attribute is the hash of. For Authenticode, that's the
+    /// contents of the SpcIndirectDataContent, without its tag and length.
+    pub signed_content: Vec<u8>,
+}
+
+impl SpcIndirectDataContent {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut fields = expect_sequence(value)?.reader();
+        let mut data = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let data_type = data.read()?.as_oid()?;
+        let data = if data.is_empty() {
+            None
+        } else {
+            Some(data.read()?.raw.to_vec())
+        };
+        let mut digest_info = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let digest_algorithm = AlgorithmIdentifier::parse(&digest_info.read()?)?;
+        let digest = digest_info.read()?.as_octet_string()?.to_vec();
+        Ok(Self {
+            data_type,
+            data,
+            digest_algorithm,
+            digest,
+            signed_content: value.contents.to_vec(),
+        })
+    }
+}
+
+/// A PKCS#7 SignedData, which is what an Authenticode signature is.
+#[derive(Debug, Clone)]
+pub struct SignedData {
+    pub version: u64,
+    pub digest_algorithms: Vec<AlgorithmIdentifier>,
+
+    /// The type of the signed content. For Authenticode, this is SpcIndirectDataContent.
+    pub content_type: String,
+
+    /// The encoded content.
+    pub content: Option<Vec<u8>>,
+
+    /// The decoded content, if this is an Authenticode signature.
+    pub indirect_data: Option<SpcIndirectDataContent>,
+
+    /// Every certificate that was included with the signature. These aren't in any particular
+    /// order.
+    pub certificates: Vec<Certificate>,
+
+    pub signer_infos: Vec<SignerInfo>,
+
+    /// Any additional signatures that are nested in the signers' unauthenticated attributes, such
+    /// as a SHA-256 signature alongside a SHA-1 one.
+    pub nested_signatures: Vec<SignedData>,
+}
+
+impl SignedData {
+    /// How deeply signatures can be nested in each other. Windows only looks at signatures nested
+    /// directly in the outer one.
+    pub const MAX_NESTING_DEPTH: usize = 1;
+
+    /// Parses a DER-encoded ContentInfo holding a SignedData, such as the contents of a
+    /// WIN_CERTIFICATE. Anything after the ContentInfo (such as alignment padding) is ignored.
+    pub fn from_der(data: &[u8]) -> Result<Self> {
+        Self::from_der_at_depth(data, 0)
+    }
+
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        Self::parse_at_depth(value, 0)
+    }
+
+    fn from_der_at_depth(data: &[u8], depth: usize) -> Result<Self> {
+        let content_info = DerReader::new(data).read()?;
+        let mut fields = expect_sequence(&content_info)?.reader();
+        let content_type = fields.read()?.as_oid()?;
+        if content_type != oid::PKCS7_SIGNED_DATA {
+            return Err(invalid(format!(
+                "expected SignedData, but got content type {}",
+                content_type
+            )));
+        }
+        let content = fields.read_tag(context_tag(0, true))?;
+        Self::parse_at_depth(&content.reader().read()?, depth)
+    }
+
+    fn parse_at_depth(value: &DerValue<'_>, depth: usize) -> Result<Self> {
+        let mut fields = expect_sequence(value)?.reader();
+        let version = fields.read_tag(TAG_INTEGER)?.as_u64()?;
+        let digest_algorithms = fields
+            .read_tag(TAG_SET)?
+            .reader()
+            .read_all()?
+            .iter()
+            .map(AlgorithmIdentifier::parse)
+            .collect::<Result<_>>()?;
+
+        let mut content_info = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let content_type = content_info.read()?.as_oid()?;
+        let content = match content_info.read_optional(context_tag(0, true))? {
+            Some(content) => Some(content.reader().read()?),
+            None => None,
+        };
+        let indirect_data = match content {
+            Some(content) if content_type == oid::SPC_INDIRECT_DATA => {
+                Some(SpcIndirectDataContent::parse(&content)?)
+            }
+            _ => None,
+        };
+
+        let certificates = match fields.read_optional(context_tag(0, true))? {
+            Some(certificates) => parse_certificates(&mut certificates.reader())?,
+            None => Vec::new(),
+        };
+        let _crls = fields.read_optional(context_tag(1, true))?;
+        let signer_infos: Vec<SignerInfo> = fields
+            .read_tag(TAG_SET)?
+            .reader()
+            .read_all()?
+            .iter()
+            .map(SignerInfo::parse)
+            .collect::<Result<_>>()?;
+
+        let mut nested_signatures = Vec::new();
+        for signer_info in &signer_infos {
+            for nested in signer_info.unauthenticated_attribute_values(oid::SPC_NESTED_SIGNATURE) {
+                if depth >= Self::MAX_NESTING_DEPTH {
+                    return Err(invalid(format!(
+                        "signatures are nested more than {} deep",
+                        Self::MAX_NESTING_DEPTH
+                    )));
+                }
+                nested_signatures.push(Self::from_der_at_depth(nested, depth + 1)?);
+            }
+        }
+
+        Ok(Self {
+            version,
+            digest_algorithms,
+            content_type,
+            content: content.map(|content| content.raw.to_vec()),
+            indirect_data,
+            certificates,
+            signer_infos,
+            nested_signatures,
+        })
+    }
+
+    /// Finds the certificate that belongs to the provided signer.
+    pub fn signer_certificate(&self, signer_info: &SignerInfo) -> Option<&Certificate> {
+        self.certificates
+            .iter()
+            .find(|certificate| match &signer_info.signer {
+                SignerIdentifier::IssuerAndSerialNumber {
+                    issuer,
+                    serial_number,
+                } => {
+                    certificate.issuer.raw == issuer.raw
+                        && certificate.serial_number == *serial_number
+                }
+                SignerIdentifier::SubjectKeyIdentifier(key_id) => certificate
+                    .extension(oid::SUBJECT_KEY_IDENTIFIER)
+                    .and_then(|extension| {
+                        Some(DerValue::parse(&extension.value).ok()?.contents == key_id.as_slice())
+                    })
+                    .unwrap_or(false),
+            })
+    }
+
+    /// This signature followed by every nested signature, depth first.
+    pub fn all_signatures(&self) -> Vec<&SignedData> {
+        let mut signatures = vec![self];
+        for nested in &self.nested_signatures {
+            signatures.extend(nested.all_signatures());
+        }
+        signatures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticode::der::{TAG_NULL, TAG_OID};
+
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        match contents.len() {
+            length if length < 0x80 => encoded.push(length as u8),
+            length if length <= 0xff => encoded.extend([0x81, length as u8]),
+            length => encoded.extend([0x82, (length >> 8) as u8, length as u8]),
+        }
+        encoded.extend_from_slice(contents);
+        encoded
+    }
+
+    fn der_oid(dotted: &str) -> Vec<u8> {
+        let arcs: Vec<u64> = dotted.split('.').map(|arc| arc.parse().unwrap()).collect();
+        let mut contents = Vec::new();
+        for arc in std::iter::once(arcs[0] * 40 + arcs[1]).chain(arcs[2..].iter().copied()) {
+            let mut bytes = vec![(arc & 0x7f) as u8];
+            let mut rest = arc >> 7;
+            while rest != 0 {
+                bytes.push((rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            contents.extend(bytes.iter().rev());
+        }
+        der(TAG_OID, &contents)
+    }
+
+    fn algorithm(dotted: &str) -> Vec<u8> {
+        der(
+            TAG_SEQUENCE,
+            &[der_oid(dotted), der(TAG_NULL, &[])].concat(),
+        )
+    }
+
+    /// A ContentInfo holding a SignedData with one signer, which has `nested` as a nested
+    /// signature if it's provided.
+    fn signed_data(nested: Option<Vec<u8>>) -> Vec<u8> {
+        let mut signer_info = [
+            der(TAG_INTEGER, &[1]),
+            der(context_tag(0, false), b"key"),
+            algorithm(oid::SHA256),
+            algorithm(oid::RSA_ENCRYPTION),
+            der(TAG_OCTET_STRING, b"signature"),
+        ]
+        .concat();
+        if let Some(nested) = nested {
+            let attribute = der(
+                TAG_SEQUENCE,
+                &[der_oid(oid::SPC_NESTED_SIGNATURE), der(TAG_SET, &nested)].concat(),
+            );
+            signer_info.extend(der(context_tag(1, true), &attribute));
+        }
+        let signed_data = der(
+            TAG_SEQUENCE,
+            &[
+                der(TAG_INTEGER, &[1]),
+                der(TAG_SET, &algorithm(oid::SHA256)),
+                der(TAG_SEQUENCE, &der_oid(oid::PKCS7_DATA)),
+                der(TAG_SET, &der(TAG_SEQUENCE, &signer_info)),
+            ]
+            .concat(),
+        );
+        der(
+            TAG_SEQUENCE,
+            &[
+                der_oid(oid::PKCS7_SIGNED_DATA),
+                der(context_tag(0, true), &signed_data),
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn parses_a_nested_signature() {
+        let signature = SignedData::from_der(&signed_data(Some(signed_data(None)))).unwrap();
+        assert_eq!(signature.content_type, oid::PKCS7_DATA);
+        assert_eq!(signature.signer_infos.len(), 1);
+        assert_eq!(signature.signer_infos[0].encrypted_digest, b"signature");
+        assert_eq!(signature.nested_signatures.len(), 1);
+        assert_eq!(signature.all_signatures().len(), 2);
+    }
+
+    #[test]
+    fn rejects_signatures_nested_too_deeply() {
+        let data = signed_data(Some(signed_data(Some(signed_data(None)))));
+        assert!(SignedData::from_der(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_other_content_types() {
+        let data = der(
+            TAG_SEQUENCE,
+            &[der_oid(oid::PKCS7_DATA), der(context_tag(0, true), &[])].concat(),
+        );
+        assert!(SignedData::from_der(&data).is_err());
+    }
+}
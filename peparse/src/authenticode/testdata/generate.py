@@ -0,0 +1,364 @@
+#!/usr/bin/env python3
+"""Generates the certificates and signatures that the Authenticode tests use.
+
+Everything is P-256 with deterministic ECDSA (RFC 6979) and fixed keys, serial numbers and
+validity periods, so running this again produces the same files. Needs the `cryptography`
+package.
+
+The signatures are over the tiny image that the tests in base.rs build, whose SHA-256
+Authenticode digest is IMAGE_DIGEST.
+"""
+
+import datetime
+import hashlib
+import os
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+IMAGE_DIGEST = bytes.fromhex("bc3f1515524e111a36a8f89331ca44d19e084f603566774b9b1af0e7e1b65501")
+
+SHA256 = "2.16.840.1.101.3.4.2.1"
+ECDSA_WITH_SHA256 = "1.2.840.10045.4.3.2"
+PKCS7_SIGNED_DATA = "1.2.840.113549.1.7.2"
+CONTENT_TYPE = "1.2.840.113549.1.9.3"
+MESSAGE_DIGEST = "1.2.840.113549.1.9.4"
+SIGNING_TIME = "1.2.840.113549.1.9.5"
+COUNTER_SIGNATURE = "1.2.840.113549.1.9.6"
+TST_INFO = "1.2.840.113549.1.9.16.1.4"
+PKCS7_DATA = "1.2.840.113549.1.7.1"
+SPC_INDIRECT_DATA = "1.3.6.1.4.1.311.2.1.4"
+SPC_PE_IMAGE_DATA = "1.3.6.1.4.1.311.2.1.15"
+SPC_RFC3161_TIMESTAMP = "1.3.6.1.4.1.311.3.3.1"
+TIMESTAMP_POLICY = "1.2.3.4.1"
+
+OUT = os.path.dirname(os.path.abspath(__file__))
+
+
+def der(tag, contents):
+    length = len(contents)
+    if length < 0x80:
+        encoded_length = bytes([length])
+    else:
+        length_bytes = length.to_bytes((length.bit_length() + 7) // 8, "big")
+        encoded_length = bytes([0x80 | len(length_bytes)]) + length_bytes
+    return bytes([tag]) + encoded_length + contents
+
+
+def sequence(*items):
+    return der(0x30, b"".join(items))
+
+
+def set_of(*items):
+    return der(0x31, b"".join(items))
+
+
+def integer(value):
+    return der(0x02, value.to_bytes(value.bit_length() // 8 + 1, "big"))
+
+
+def octet_string(value):
+    return der(0x04, value)
+
+
+def oid(dotted):
+    arcs = [int(arc) for arc in dotted.split(".")]
+    contents = b""
+    for arc in [arcs[0] * 40 + arcs[1]] + arcs[2:]:
+        encoded = [arc & 0x7F]
+        arc >>= 7
+        while arc:
+            encoded.append(0x80 | (arc & 0x7F))
+            arc >>= 7
+        contents += bytes(reversed(encoded))
+    return der(0x06, contents)
+
+
+def algorithm(dotted, null=True):
+    return sequence(oid(dotted), der(0x05, b"") if null else b"")
+
+
+def utc_time(time):
+    return der(0x17, time.strftime("%y%m%d%H%M%SZ").encode())
+
+
+def generalized_time(time):
+    return der(0x18, time.strftime("%Y%m%d%H%M%SZ").encode())
+
+
+def explicit(number, contents):
+    return der(0xA0 | number, contents)
+
+
+def time(year, month=1, day=1):
+    return datetime.datetime(year, month, day, tzinfo=datetime.timezone.utc)
+
+
+def key(number):
+    return ec.derive_private_key(number, ec.SECP256R1())
+
+
+def certificate(
+    name,
+    serial,
+    private_key,
+    issuer=None,
+    issuer_key=None,
+    is_ca=False,
+    cert_sign=None,
+    usages=None,
+    not_before=time(2020),
+    not_after=time(2040),
+):
+    subject = x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, name)])
+    if cert_sign is None:
+        cert_sign = is_ca
+    builder = (
+        x509.CertificateBuilder()
+        .subject_name(subject)
+        .issuer_name(issuer.subject if issuer else subject)
+        .public_key(private_key.public_key())
+        .serial_number(serial)
+        .not_valid_before(not_before)
+        .not_valid_after(not_after)
+        .add_extension(x509.BasicConstraints(ca=is_ca, path_length=None), critical=True)
+        .add_extension(
+            x509.KeyUsage(
+                digital_signature=not is_ca,
+                content_commitment=False,
+                key_encipherment=False,
+                data_encipherment=False,
+                key_agreement=False,
+                key_cert_sign=cert_sign,
+                crl_sign=is_ca,
+                encipher_only=False,
+                decipher_only=False,
+            ),
+            critical=True,
+        )
+    )
+    if usages:
+        builder = builder.add_extension(x509.ExtendedKeyUsage(usages), critical=False)
+    return builder.sign(issuer_key or private_key, hashes.SHA256(), ecdsa_deterministic=True)
+
+
+def certificate_der(certificate):
+    return certificate.public_bytes(serialization.Encoding.DER)
+
+
+def sign(private_key, data):
+    return private_key.sign(data, ec.ECDSA(hashes.SHA256(), deterministic_signing=True))
+
+
+def signer_info(certificate, private_key, attributes, timestamp=None):
+    """A SignerInfo that signs the provided authenticated attributes. `timestamp` is called with
+    the signature, and returns the unauthenticated attribute that timestamps it."""
+    attributes = b"".join(
+        sequence(oid(attribute), set_of(value)) for attribute, value in attributes
+    )
+    issuer_and_serial = sequence(
+        certificate.issuer.public_bytes(), integer(certificate.serial_number)
+    )
+    signature = sign(private_key, set_of(attributes))
+    return sequence(
+        integer(1),
+        issuer_and_serial,
+        algorithm(SHA256),
+        der(0xA0, attributes),
+        algorithm(ECDSA_WITH_SHA256, null=False),
+        octet_string(signature),
+        der(0xA1, timestamp(signature)) if timestamp else b"",
+    )
+
+
+def signed_data(content_type, content, certificates, signer):
+    return sequence(
+        oid(PKCS7_SIGNED_DATA),
+        explicit(
+            0,
+            sequence(
+                integer(1),
+                set_of(algorithm(SHA256)),
+                sequence(oid(content_type), explicit(0, content)),
+                der(0xA0, b"".join(certificate_der(c) for c in certificates)),
+                set_of(signer),
+            ),
+        ),
+    )
+
+
+def authenticode_signature(certificate, private_key, certificates, signing_time, timestamp=None):
+    # SpcPeImageData with no flags and an empty file link, which is what signtool writes.
+    pe_image_data = sequence(der(0x03, b"\x00"), explicit(0, der(0xA2, der(0x80, b""))))
+    indirect_data = sequence(
+        sequence(oid(SPC_PE_IMAGE_DATA), pe_image_data),
+        sequence(algorithm(SHA256), octet_string(IMAGE_DIGEST)),
+    )
+    # The messageDigest is the hash of the SpcIndirectDataContent without its tag and length.
+    signed_content = indirect_data[2:]
+    signer = signer_info(
+        certificate,
+        private_key,
+        [
+            (CONTENT_TYPE, oid(SPC_INDIRECT_DATA)),
+            (SIGNING_TIME, utc_time(signing_time)),
+            (MESSAGE_DIGEST, octet_string(hashlib.sha256(signed_content).digest())),
+        ],
+        timestamp,
+    )
+    return signed_data(SPC_INDIRECT_DATA, indirect_data, certificates, signer)
+
+
+def countersignature(certificate, private_key, timestamp_time):
+    """Timestamps a signature with a PKCS#9 countersignature, which signs the signature itself."""
+
+    def timestamp(signature):
+        countersigner = signer_info(
+            certificate,
+            private_key,
+            [
+                (CONTENT_TYPE, oid(PKCS7_DATA)),
+                (SIGNING_TIME, utc_time(timestamp_time)),
+                (MESSAGE_DIGEST, octet_string(hashlib.sha256(signature).digest())),
+            ],
+        )
+        return sequence(oid(COUNTER_SIGNATURE), set_of(countersigner))
+
+    return timestamp
+
+
+def rfc3161_timestamp(certificate, private_key, timestamp_time):
+    """Timestamps a signature with an RFC 3161 token, which signs a TSTInfo holding the hash of
+    the signature."""
+
+    def timestamp(signature):
+        tst_info = sequence(
+            integer(1),
+            oid(TIMESTAMP_POLICY),
+            sequence(algorithm(SHA256), octet_string(hashlib.sha256(signature).digest())),
+            integer(42),
+            generalized_time(timestamp_time),
+            integer(0x0102030405060708),
+        )
+        token_signer = signer_info(
+            certificate,
+            private_key,
+            [
+                (CONTENT_TYPE, oid(TST_INFO)),
+                (MESSAGE_DIGEST, octet_string(hashlib.sha256(tst_info).digest())),
+            ],
+        )
+        token = signed_data(TST_INFO, octet_string(tst_info), [certificate], token_signer)
+        return sequence(oid(SPC_RFC3161_TIMESTAMP), set_of(token))
+
+    return timestamp
+
+
+def write(name, data):
+    if isinstance(data, x509.Certificate):
+        data = certificate_der(data)
+    with open(os.path.join(OUT, name), "wb") as f:
+        f.write(data)
+
+
+def main():
+    root_key, intermediate_key, leaf_key = key(1001), key(1002), key(1003)
+    root = certificate("peparse Test Root", 1, root_key, is_ca=True)
+    intermediate = certificate(
+        "peparse Test Code Signing CA", 2, intermediate_key, root, root_key, is_ca=True
+    )
+    # The leaf expired long ago, so signatures only verify at a time inside of its validity.
+    leaf = certificate(
+        "peparse Test Publisher",
+        3,
+        leaf_key,
+        intermediate,
+        intermediate_key,
+        usages=[ExtendedKeyUsageOID.CODE_SIGNING],
+        not_before=time(2021),
+        not_after=time(2022),
+    )
+    write("root.der", root)
+    write("intermediate.der", intermediate)
+    write("leaf.der", leaf)
+
+    not_ca = certificate("peparse Test Not A CA", 4, key(1004), root, root_key, cert_sign=True)
+    write("not_ca.der", not_ca)
+    write(
+        "not_ca_leaf.der",
+        certificate("peparse Test Not A CA Leaf", 5, key(1005), not_ca, key(1004)),
+    )
+    no_cert_sign = certificate(
+        "peparse Test No Cert Sign CA", 6, key(1006), root, root_key, is_ca=True, cert_sign=False
+    )
+    write("no_cert_sign_ca.der", no_cert_sign)
+    write(
+        "no_cert_sign_leaf.der",
+        certificate("peparse Test No Cert Sign Leaf", 7, key(1007), no_cert_sign, key(1006)),
+    )
+
+    write(
+        "signed.der",
+        authenticode_signature(leaf, leaf_key, [leaf, intermediate], time(2021, 6, 1)),
+    )
+
+    # The timestamp authority's certificates share a key, and only differ in their extended key
+    # usages.
+    tsa_key = key(1008)
+    tsa = certificate(
+        "peparse Test Timestamping Authority",
+        8,
+        tsa_key,
+        root,
+        root_key,
+        usages=[ExtendedKeyUsageOID.TIME_STAMPING],
+    )
+    tsa_without_eku = certificate(
+        "peparse Test Timestamping Authority",
+        9,
+        tsa_key,
+        root,
+        root_key,
+        usages=[ExtendedKeyUsageOID.CODE_SIGNING],
+    )
+    write("tsa.der", tsa)
+    write("tsa_without_eku.der", tsa_without_eku)
+
+    # The timestamps are a month after the signing time the signer claims.
+    timestamp_time = time(2021, 7, 1)
+    write(
+        "countersigned.der",
+        authenticode_signature(
+            leaf,
+            leaf_key,
+            [leaf, intermediate, tsa],
+            time(2021, 6, 1),
+            countersignature(tsa, tsa_key, timestamp_time),
+        ),
+    )
+    write(
+        "countersigned_without_eku.der",
+        authenticode_signature(
+            leaf,
+            leaf_key,
+            [leaf, intermediate, tsa_without_eku],
+            time(2021, 6, 1),
+            countersignature(tsa_without_eku, tsa_key, timestamp_time),
+        ),
+    )
+    write(
+        "rfc3161.der",
+        authenticode_signature(
+            leaf,
+            leaf_key,
+            [leaf, intermediate],
+            time(2021, 6, 1),
+            rfc3161_timestamp(tsa, tsa_key, timestamp_time),
+        ),
+    )
+
+
+if __name__ == "__main__":
+    main()
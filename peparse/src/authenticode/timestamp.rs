@@ -0,0 +1,455 @@
+//! Timestamps, which prove that a signature existed at a certain time. They come in two forms: the
+//! legacy Authenticode countersignature, and the RFC 3161 timestamp token that newer timestamp
+//! authorities issue.
+
+use super::{
+    der::{
+        context_tag, invalid, DerValue, TAG_BOOLEAN, TAG_GENERALIZED_TIME, TAG_INTEGER,
+        TAG_SEQUENCE,
+    },
+    oid,
+    pkcs7::{SignedData, SignerInfo},
+    verify::{build_chain, verify_signer_info, TrustStore, VerificationFailure},
+    x509::{expect_sequence, AlgorithmIdentifier, Certificate},
+};
+use crate::error::{Error, Result};
+
+/// Which form a timestamp is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampKind {
+    /// A PKCS#9 countersignature (1.2.840.113549.1.9.6).
+    Countersignature,
+
+    /// An RFC 3161 timestamp token (1.3.6.1.4.1.311.3.3.1).
+    Rfc3161,
+}
+
+/// The TSTInfo from an RFC 3161 timestamp token.
+#[derive(Debug, Clone)]
+pub struct TstInfo {
+    pub version: u64,
+
+    /// The policy the timestamp authority issued the timestamp under.
+    pub policy: String,
+
+    pub message_imprint_algorithm: AlgorithmIdentifier,
+    pub message_imprint: Vec<u8>,
+    pub serial_number: Vec<u8>,
+
+    /// When the timestamp was issued, in seconds since the Unix epoch.
+    pub gen_time: i64,
+
+    pub is_ordered: bool,
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl TstInfo {
+    pub fn from_der(data: &[u8]) -> Result<Self> {
+        let value = DerValue::parse(data)?;
+        let mut fields = expect_sequence(&value)?.reader();
+        let version = fields.read_tag(TAG_INTEGER)?.as_u64()?;
+        let policy = fields.read()?.as_oid()?;
+        let mut imprint = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let message_imprint_algorithm = AlgorithmIdentifier::parse(&imprint.read()?)?;
+        let message_imprint = imprint.read()?.as_octet_string()?.to_vec();
+        let serial_number = fields.read_tag(TAG_INTEGER)?.as_integer_bytes()?.to_vec();
+        let gen_time = fields.read_tag(TAG_GENERALIZED_TIME)?.as_time()?;
+        let _accuracy = fields.read_optional(TAG_SEQUENCE)?;
+        let is_ordered = match fields.read_optional(TAG_BOOLEAN)? {
+            Some(is_ordered) => is_ordered.as_bool()?,
+            None => false,
+        };
+        let nonce = match fields.read_optional(TAG_INTEGER)? {
+            Some(nonce) => Some(nonce.as_integer_bytes()?.to_vec()),
+            None => None,
+        };
+        let _tsa = fields.read_optional(context_tag(0, true))?;
+        Ok(Self {
+            version,
+            policy,
+            message_imprint_algorithm,
+            message_imprint,
+            serial_number,
+            gen_time,
+            is_ordered,
+            nonce,
+        })
+    }
+}
+
+/// A timestamp over a signer's signature.
+#[derive(Debug, Clone)]
+pub struct Timestamp {
+    pub kind: TimestampKind,
+
+    /// When the signature was timestamped, in seconds since the Unix epoch.
+    pub signing_time: i64,
+
+    /// The algorithm used to hash the signer's encrypted digest.
+    pub imprint_algorithm: AlgorithmIdentifier,
+
+    /// The hash of the signer's encrypted digest, which is what ties the timestamp to the
+    /// signature.
+    pub imprint: Vec<u8>,
+
+    /// The timestamp authority's certificate, if it was included.
+    pub tsa_certificate: Option<Certificate>,
+
+    /// The certificates available for building the timestamp authority's chain.
+    pub certificates: Vec<Certificate>,
+
+    /// The timestamp authority's signer info.
+    pub signer_info: SignerInfo,
+
+    /// The TSTInfo, for RFC 3161 timestamps.
+    pub tst_info: Option<TstInfo>,
+
+    /// What the timestamp authority signed: the TSTInfo for RFC 3161 timestamps, or the
+    /// countersigned encrypted digest for countersignatures.
+    signed_content: Vec<u8>,
+}
+
+impl Timestamp {
+    /// Decodes every timestamp in a signer's unauthenticated attributes. `signature` is the
+    /// SignedData the signer belongs to, which is where a countersigner's certificate is kept.
+    /// Each timestamp is decoded on its own, so one that's malformed doesn't hide the others.
+    pub fn from_signer_info(signature: &SignedData, signer_info: &SignerInfo) -> Vec<Result<Self>> {
+        let countersignatures = signer_info
+            .unauthenticated_attribute_values(oid::COUNTER_SIGNATURE)
+            .map(|value| {
+                Self::from_countersignature(
+                    signature,
+                    signer_info,
+                    SignerInfo::parse(&DerValue::parse(value)?)?,
+                )
+            });
+        let tokens = signer_info
+            .unauthenticated_attribute_values(oid::SPC_RFC3161_TIMESTAMP)
+            .map(|value| Self::from_rfc3161(SignedData::from_der(value)?));
+        countersignatures.chain(tokens).collect()
+    }
+
+    fn from_countersignature(
+        signature: &SignedData,
+        countersigned: &SignerInfo,
+        signer_info: SignerInfo,
+    ) -> Result<Self> {
+        let signing_time = signer_info
+            .signing_time()?
+            .ok_or_else(|| invalid("countersignature has no signing time"))?;
+        let imprint = signer_info
+            .message_digest()?
+            .ok_or_else(|| invalid("countersignature has no message digest"))?;
+        Ok(Self {
+            kind: TimestampKind::Countersignature,
+            signing_time,
+            imprint_algorithm: signer_info.digest_algorithm.clone(),
+            imprint,
+            tsa_certificate: signature.signer_certificate(&signer_info).cloned(),
+            certificates: signature.certificates.clone(),
+            signer_info,
+            tst_info: None,
+            signed_content: countersigned.encrypted_digest.clone(),
+        })
+    }
+
+    fn from_rfc3161(token: SignedData) -> Result<Self> {
+        if token.content_type != oid::TST_INFO {
+            return Err(invalid(format!(
+                "expected a TSTInfo, but got content type {}",
+                token.content_type
+            )));
+        }
+        // The TSTInfo is wrapped in an OCTET STRING, and it's the TSTInfo itself that's signed.
+        let content = token
+            .content
+            .as_deref()
+            .ok_or_else(|| invalid("timestamp token has no content"))?;
+        let tst_info_der = DerValue::parse(content)?.as_octet_string()?.to_vec();
+        let tst_info = TstInfo::from_der(&tst_info_der)?;
+        let signer_info = match token.signer_infos.as_slice() {
+            [signer_info] => signer_info.clone(),
+            signer_infos => {
+                return Err(invalid(format!(
+                    "timestamp token has {} signers",
+                    signer_infos.len()
+                )))
+            }
+        };
+        Ok(Self {
+            kind: TimestampKind::Rfc3161,
+            signing_time: tst_info.gen_time,
+            imprint_algorithm: tst_info.message_imprint_algorithm.clone(),
+            imprint: tst_info.message_imprint.clone(),
+            tsa_certificate: token.signer_certificate(&signer_info).cloned(),
+            certificates: token.certificates,
+            signer_info,
+            tst_info: Some(tst_info),
+            signed_content: tst_info_der,
+        })
+    }
+
+    /// If the imprint is the hash of the provided signer's encrypted digest, which means the
+    /// timestamp is for that signature.
+    pub fn imprint_matches(&self, signer_info: &SignerInfo) -> Result<bool> {
+        let algorithm = self.imprint_algorithm.hash_algorithm().ok_or_else(|| {
+            invalid(format!(
+                "unsupported imprint algorithm {}",
+                self.imprint_algorithm.oid
+            ))
+        })?;
+        Ok(algorithm.digest(&signer_info.encrypted_digest) == self.imprint)
+    }
+
+    /// Checks the timestamp against the signer it's for: the imprint has to match the signer's
+    /// encrypted digest, the timestamp authority's signature has to be valid, and its certificate
+    /// has to be allowed to be used for timestamping, chain to the trust store and be valid at the
+    /// time of the timestamp.
+    pub fn verify(&self, signer_info: &SignerInfo, trust_store: &TrustStore) -> TimestampReport {
+        let mut report = TimestampReport {
+            timestamp: Some(self.clone()),
+            chain: Vec::new(),
+            failures: Vec::new(),
+        };
+        match self.imprint_matches(signer_info) {
+            Ok(true) => {}
+            Ok(false) => report
+                .failures
+                .push(VerificationFailure::TimestampImprintMismatch),
+            Err(_) => report
+                .failures
+                .push(VerificationFailure::UnsupportedDigestAlgorithm(
+                    self.imprint_algorithm.oid.clone(),
+                )),
+        }
+        let tsa_certificate = match &self.tsa_certificate {
+            Some(certificate) => certificate,
+            None => {
+                report
+                    .failures
+                    .push(VerificationFailure::SignerCertificateNotFound);
+                return report;
+            }
+        };
+        if let Err(failure) =
+            verify_signer_info(&self.signer_info, tsa_certificate, &self.signed_content)
+        {
+            report.failures.push(failure);
+        }
+        // Unlike code signing certificates, a timestamp authority's certificate has to have the
+        // extended key usage extension (RFC 3161, section 2.3).
+        let can_timestamp = tsa_certificate
+            .extended_key_usages()
+            .map(|usages| usages.iter().any(|usage| usage == oid::TIME_STAMPING))
+            .unwrap_or(false);
+        if !can_timestamp {
+            report
+                .failures
+                .push(VerificationFailure::MissingExtendedKeyUsage {
+                    subject: tsa_certificate.subject.to_string(),
+                    usage: oid::TIME_STAMPING.into(),
+                });
+        }
+        let (chain, failures) = build_chain(
+            tsa_certificate,
+            &self.certificates,
+            trust_store,
+            self.signing_time,
+            None,
+        );
+        report.chain = chain;
+        report.failures.extend(failures);
+        report
+    }
+}
+
+/// The result of verifying a timestamp.
+#[derive(Debug, Clone)]
+pub struct TimestampReport {
+    /// The timestamp, unless it couldn't be decoded.
+    pub timestamp: Option<Timestamp>,
+
+    /// The timestamp authority's certificate, followed by each issuer up to the root, as far as
+    /// the chain could be built.
+    pub chain: Vec<Certificate>,
+
+    pub failures: Vec<VerificationFailure>,
+}
+
+impl TimestampReport {
+    /// A report for a timestamp that couldn't be decoded.
+    pub fn malformed(error: Error) -> Self {
+        Self {
+            timestamp: None,
+            chain: Vec::new(),
+            failures: vec![VerificationFailure::MalformedTimestamp(error.to_string())],
+        }
+    }
+
+    /// When the timestamp says the signature was made, if it could be decoded.
+    #[inline]
+    pub fn signing_time(&self) -> Option<i64> {
+        self.timestamp
+            .as_ref()
+            .map(|timestamp| timestamp.signing_time)
+    }
+
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authenticode::{pkcs7::Attribute, HashAlgorithm};
+
+    // The signatures and certificates in testdata are generated by testdata/generate.py.
+
+    /// 2021-07-01, when the test signatures were timestamped.
+    const TIMESTAMP_TIME: i64 = 1_625_097_600;
+
+    fn trust_store() -> TrustStore {
+        TrustStore {
+            roots: vec![Certificate::from_der(include_bytes!("testdata/root.der")).unwrap()],
+        }
+    }
+
+    /// Decodes the timestamps of the signer of a signature.
+    fn timestamps(der: &[u8]) -> (SignerInfo, Vec<Result<Timestamp>>) {
+        let signature = SignedData::from_der(der).unwrap();
+        let signer_info = signature.signer_infos[0].clone();
+        let timestamps = Timestamp::from_signer_info(&signature, &signer_info);
+        (signer_info, timestamps)
+    }
+
+    fn timestamp(der: &[u8]) -> (SignerInfo, Timestamp) {
+        let (signer_info, mut timestamps) = timestamps(der);
+        assert_eq!(timestamps.len(), 1);
+        (signer_info, timestamps.remove(0).unwrap())
+    }
+
+    #[test]
+    fn tst_info() {
+        let (signer_info, timestamp) = timestamp(include_bytes!("testdata/rfc3161.der"));
+        let tst_info = TstInfo::from_der(&timestamp.signed_content).unwrap();
+        assert_eq!(tst_info.version, 1);
+        assert_eq!(tst_info.policy, "1.2.3.4.1");
+        assert_eq!(tst_info.message_imprint_algorithm.oid, oid::SHA256);
+        assert_eq!(
+            tst_info.message_imprint,
+            HashAlgorithm::Sha256.digest(&signer_info.encrypted_digest)
+        );
+        assert_eq!(tst_info.serial_number, vec![42]);
+        assert_eq!(tst_info.gen_time, TIMESTAMP_TIME);
+        assert!(!tst_info.is_ordered);
+        assert_eq!(
+            tst_info.nonce,
+            Some(vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08])
+        );
+
+        let truncated = &timestamp.signed_content[..timestamp.signed_content.len() - 1];
+        assert!(TstInfo::from_der(truncated).is_err());
+    }
+
+    #[test]
+    fn rfc3161_timestamp() {
+        let (signer_info, timestamp) = timestamp(include_bytes!("testdata/rfc3161.der"));
+        assert_eq!(timestamp.kind, TimestampKind::Rfc3161);
+        assert_eq!(timestamp.signing_time, TIMESTAMP_TIME);
+        assert!(timestamp.tst_info.is_some());
+        assert_eq!(
+            timestamp
+                .tsa_certificate
+                .as_ref()
+                .and_then(|certificate| certificate.subject.common_name()),
+            Some("peparse Test Timestamping Authority")
+        );
+        assert!(timestamp.imprint_matches(&signer_info).unwrap());
+
+        let report = timestamp.verify(&signer_info, &trust_store());
+        assert_eq!(report.failures, vec![]);
+        assert_eq!(report.chain.len(), 2);
+        assert_eq!(report.signing_time(), Some(TIMESTAMP_TIME));
+    }
+
+    #[test]
+    fn countersignature() {
+        let (signer_info, timestamp) = timestamp(include_bytes!("testdata/countersigned.der"));
+        assert_eq!(timestamp.kind, TimestampKind::Countersignature);
+        assert_eq!(timestamp.signing_time, TIMESTAMP_TIME);
+        assert!(timestamp.tst_info.is_none());
+        assert!(timestamp.imprint_matches(&signer_info).unwrap());
+
+        let report = timestamp.verify(&signer_info, &trust_store());
+        assert_eq!(report.failures, vec![]);
+        assert_eq!(report.chain.len(), 2);
+        assert_eq!(report.signing_time(), Some(TIMESTAMP_TIME));
+    }
+
+    #[test]
+    fn timestamp_for_another_signature() {
+        for der in [
+            &include_bytes!("testdata/rfc3161.der")[..],
+            &include_bytes!("testdata/countersigned.der")[..],
+        ] {
+            let (mut signer_info, timestamp) = timestamp(der);
+            signer_info.encrypted_digest[0] ^= 1;
+            assert!(!timestamp.imprint_matches(&signer_info).unwrap());
+            assert_eq!(
+                timestamp.verify(&signer_info, &trust_store()).failures,
+                vec![VerificationFailure::TimestampImprintMismatch]
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_authority_without_the_time_stamping_usage() {
+        let (signer_info, timestamp) =
+            timestamp(include_bytes!("testdata/countersigned_without_eku.der"));
+        let report = timestamp.verify(&signer_info, &trust_store());
+        assert_eq!(
+            report.failures,
+            vec![VerificationFailure::MissingExtendedKeyUsage {
+                subject: "CN=peparse Test Timestamping Authority".into(),
+                usage: oid::TIME_STAMPING.into(),
+            }]
+        );
+        assert!(!report.is_valid());
+        // The time is still available, but it shouldn't be trusted.
+        assert_eq!(report.signing_time(), Some(TIMESTAMP_TIME));
+    }
+
+    #[test]
+    fn untrusted_timestamp_authority() {
+        let (signer_info, timestamp) = timestamp(include_bytes!("testdata/countersigned.der"));
+        let report = timestamp.verify(&signer_info, &TrustStore::new());
+        assert_eq!(
+            report.failures,
+            vec![VerificationFailure::IssuerNotFound {
+                subject: "CN=peparse Test Timestamping Authority".into(),
+                issuer: "CN=peparse Test Root".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed_timestamps_are_decoded_separately() {
+        let signature = SignedData::from_der(include_bytes!("testdata/rfc3161.der")).unwrap();
+        let mut signer_info = signature.signer_infos[0].clone();
+        signer_info.unauthenticated_attributes.push(Attribute {
+            oid: oid::COUNTER_SIGNATURE.into(),
+            values: vec![vec![TAG_SEQUENCE, 0]],
+        });
+        let timestamps = Timestamp::from_signer_info(&signature, &signer_info);
+        assert_eq!(timestamps.len(), 2);
+        assert!(timestamps[0].is_err());
+        assert_eq!(timestamps[1].as_ref().unwrap().kind, TimestampKind::Rfc3161);
+
+        let report =
+            TimestampReport::malformed(timestamps.into_iter().next().unwrap().unwrap_err());
+        assert!(!report.is_valid());
+        assert_eq!(report.signing_time(), None);
+    }
+}
@@ -0,0 +1,964 @@
+//! Offline verification of Authenticode signatures. Nothing here touches the network: revocation
+//! isn't checked, and certificates are only checked against the roots in a [`TrustStore`].
+
+use super::{
+    der::DerValue,
+    oid,
+    pkcs7::{SignedData, SignerInfo},
+    timestamp::{Timestamp, TimestampReport},
+    x509::Certificate,
+    HashAlgorithm,
+};
+use crate::{
+    error::{Error, Result},
+    PeFile,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use p256::ecdsa::signature::hazmat::PrehashVerifier as _;
+use rsa::{pkcs1::DecodeRsaPublicKey as _, Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use std::{
+    fmt, fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The most certificates a chain can have, so that cycles between certificates can't cause
+/// problems.
+const MAX_CHAIN_LENGTH: usize = 16;
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// The root certificates that signatures are allowed to chain to.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    pub roots: Vec<Certificate>,
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn add(&mut self, certificate: Certificate) {
+        self.roots.push(certificate);
+    }
+
+    /// Adds every certificate in a PEM or DER file. PEM files can hold any number of
+    /// certificates.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let data = fs::read(path)?;
+        match std::str::from_utf8(&data) {
+            Ok(text) if text.contains(PEM_BEGIN) => {
+                for der in decode_pem_certificates(text)? {
+                    self.add(Certificate::from_der(&der)?);
+                }
+            }
+            _ => self.add(Certificate::from_der(&data)?),
+        }
+        Ok(())
+    }
+
+    /// Loads every .pem, .crt, .cer and .der file in a directory. Other files are ignored.
+    pub fn from_directory<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut store = Self::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            let is_certificate = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| {
+                    matches!(
+                        extension.to_ascii_lowercase().as_str(),
+                        "pem" | "crt" | "cer" | "der"
+                    )
+                })
+                .unwrap_or(false);
+            if is_certificate && path.is_file() {
+                store.add_file(&path)?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Finds a root that's the same certificate as the provided one.
+    fn find_exact(&self, certificate: &Certificate) -> Option<&Certificate> {
+        self.roots.iter().find(|root| root.raw == certificate.raw)
+    }
+}
+
+fn decode_pem_certificates(text: &str) -> Result<Vec<Vec<u8>>> {
+    let mut certificates = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let body = &rest[start + PEM_BEGIN.len()..];
+        let end = body.find(PEM_END).ok_or_else(|| Error::Other {
+            message: "PEM certificate is missing its end marker.".into(),
+        })?;
+        let encoded: String = body[..end]
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        certificates.push(BASE64.decode(encoded).map_err(|error| Error::Other {
+            message: format!("Invalid base64 in PEM certificate: {}", error),
+        })?);
+        rest = &body[end + PEM_END.len()..];
+    }
+    Ok(certificates)
+}
+
+/// A single reason a signature didn't verify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationFailure {
+    /// The image doesn't have any Authenticode signatures.
+    NotSigned,
+
+    /// The signature couldn't be decoded.
+    Malformed(String),
+
+    /// The signature doesn't have exactly one signer.
+    SignerCount(usize),
+
+    /// The signed content isn't an SpcIndirectDataContent.
+    MissingIndirectData,
+
+    UnsupportedDigestAlgorithm(String),
+    UnsupportedSignatureAlgorithm(String),
+
+    /// The digest in the signature doesn't match the image's Authenticode digest.
+    ImageDigestMismatch {
+        signed: Vec<u8>,
+        actual: Vec<u8>,
+    },
+
+    /// The signer's authenticated attributes don't include a messageDigest.
+    MissingMessageDigest,
+
+    /// The messageDigest attribute doesn't match the hash of the signed content.
+    MessageDigestMismatch,
+
+    /// None of the certificates in the signature belong to the signer.
+    SignerCertificateNotFound,
+
+    /// The signer's signature over the authenticated attributes is invalid.
+    InvalidSignerSignature,
+
+    /// A certificate's signature doesn't verify with its issuer's key.
+    InvalidCertificateSignature {
+        subject: String,
+    },
+
+    /// A certificate wasn't valid at the time being checked.
+    CertificateNotValidAtTime {
+        subject: String,
+        not_before: i64,
+        not_after: i64,
+        time: i64,
+    },
+
+    /// A certificate's issuer couldn't be found in the signature or the trust store.
+    IssuerNotFound {
+        subject: String,
+        issuer: String,
+    },
+
+    /// The chain ended at a self-signed certificate that isn't in the trust store.
+    UntrustedRoot {
+        subject: String,
+    },
+
+    /// The chain was longer than makes sense, which usually means there's a cycle.
+    ChainTooLong,
+
+    /// A certificate that issued another one isn't a CA certificate.
+    IssuerNotCa {
+        subject: String,
+    },
+
+    /// A certificate that issued another one has a key usage that doesn't allow signing
+    /// certificates.
+    IssuerCannotSignCertificates {
+        subject: String,
+    },
+
+    /// The signer's certificate has an extended key usage extension that doesn't include the
+    /// usage it's being used for, such as code signing.
+    MissingExtendedKeyUsage {
+        subject: String,
+        usage: String,
+    },
+
+    /// A timestamp's imprint isn't the hash of the signer's encrypted digest, so the timestamp is
+    /// for some other signature.
+    TimestampImprintMismatch,
+
+    /// A timestamp couldn't be decoded.
+    MalformedTimestamp(String),
+}
+
+impl fmt::Display for VerificationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSigned => write!(f, "the image isn't signed"),
+            Self::Malformed(message) => write!(f, "the signature is malformed: {}", message),
+            Self::SignerCount(count) => write!(f, "expected one signer, but found {}", count),
+            Self::MissingIndirectData => write!(f, "the signature has no SpcIndirectDataContent"),
+            Self::UnsupportedDigestAlgorithm(oid) => {
+                write!(f, "unsupported digest algorithm {}", oid)
+            }
+            Self::UnsupportedSignatureAlgorithm(oid) => {
+                write!(f, "unsupported signature algorithm {}", oid)
+            }
+            Self::ImageDigestMismatch { signed, actual } => write!(
+                f,
+                "the signed image digest {} doesn't match the actual digest {}",
+                hex::encode(signed),
+                hex::encode(actual)
+            ),
+            Self::MissingMessageDigest => write!(f, "the signer has no messageDigest attribute"),
+            Self::MessageDigestMismatch => {
+                write!(
+                    f,
+                    "the messageDigest attribute doesn't match the signed content"
+                )
+            }
+            Self::SignerCertificateNotFound => write!(f, "the signer's certificate is missing"),
+            Self::InvalidSignerSignature => write!(f, "the signer's signature is invalid"),
+            Self::InvalidCertificateSignature { subject } => {
+                write!(
+                    f,
+                    "the signature on the certificate for {} is invalid",
+                    subject
+                )
+            }
+            Self::CertificateNotValidAtTime { subject, time, .. } => write!(
+                f,
+                "the certificate for {} wasn't valid at {}",
+                subject, time
+            ),
+            Self::IssuerNotFound { subject, issuer } => write!(
+                f,
+                "couldn't find the issuer {} of the certificate for {}",
+                issuer, subject
+            ),
+            Self::UntrustedRoot { subject } => {
+                write!(f, "the root certificate {} isn't trusted", subject)
+            }
+            Self::ChainTooLong => write!(f, "the certificate chain is too long"),
+            Self::IssuerNotCa { subject } => {
+                write!(f, "the certificate for {} isn't a CA certificate", subject)
+            }
+            Self::IssuerCannotSignCertificates { subject } => write!(
+                f,
+                "the key usage of the certificate for {} doesn't allow signing certificates",
+                subject
+            ),
+            Self::MissingExtendedKeyUsage { subject, usage } => write!(
+                f,
+                "the certificate for {} isn't allowed to be used for {}",
+                subject, usage
+            ),
+            Self::TimestampImprintMismatch => {
+                write!(f, "the timestamp isn't for the signer's signature")
+            }
+            Self::MalformedTimestamp(message) => {
+                write!(f, "the timestamp is malformed: {}", message)
+            }
+        }
+    }
+}
+
+/// The result of verifying one signature, which might be nested inside of another.
+#[derive(Debug, Clone)]
+pub struct SignatureReport {
+    /// The algorithm the image digest was computed with.
+    pub digest_algorithm: Option<HashAlgorithm>,
+
+    /// The signer's certificate, followed by each issuer up to (and including) the root, as far as
+    /// the chain could be built.
+    pub chain: Vec<Certificate>,
+
+    /// The time that certificates were checked against, in seconds since the Unix epoch.
+    pub verification_time: i64,
+
+    /// A report for each timestamp on the signature. These don't affect whether the signature
+    /// itself is valid, other than providing the verification time.
+    pub timestamps: Vec<TimestampReport>,
+
+    pub failures: Vec<VerificationFailure>,
+}
+
+impl SignatureReport {
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The signer's certificate, if it was found.
+    #[inline]
+    pub fn signer(&self) -> Option<&Certificate> {
+        self.chain.first()
+    }
+}
+
+/// The result of verifying every signature in an image.
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    /// Problems that aren't specific to one signature.
+    pub failures: Vec<VerificationFailure>,
+
+    /// A report for each signature, including nested ones, in the order they appear.
+    pub signatures: Vec<SignatureReport>,
+}
+
+impl VerificationReport {
+    /// If the image is signed and every signature in it verified.
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+            && !self.signatures.is_empty()
+            && self.signatures.iter().all(SignatureReport::is_valid)
+    }
+
+    /// If at least one signature in the image verified.
+    pub fn any_valid(&self) -> bool {
+        self.failures.is_empty() && self.signatures.iter().any(SignatureReport::is_valid)
+    }
+}
+
+/// Verifies every Authenticode signature in an image against the provided trust store. The
+/// certificates are checked against the time of the first valid timestamp, falling back to the
+/// current time. The signing time the signer claims isn't used, since nothing vouches for it.
+pub fn verify_signature(image: &PeFile, trust_store: &TrustStore) -> VerificationReport {
+    verify(image, trust_store, None)
+}
+
+/// Verifies every Authenticode signature in an image against the provided trust store, checking
+/// that the certificates were valid at the provided time (in seconds since the Unix epoch).
+pub fn verify_signature_at(
+    image: &PeFile,
+    trust_store: &TrustStore,
+    time: i64,
+) -> VerificationReport {
+    verify(image, trust_store, Some(time))
+}
+
+fn verify(image: &PeFile, trust_store: &TrustStore, time: Option<i64>) -> VerificationReport {
+    let mut report = VerificationReport::default();
+    let signatures = match image.signatures() {
+        Ok(signatures) => signatures,
+        Err(error) => {
+            report
+                .failures
+                .push(VerificationFailure::Malformed(error.to_string()));
+            return report;
+        }
+    };
+    if signatures.is_empty() {
+        report.failures.push(VerificationFailure::NotSigned);
+    }
+    for signature in signatures.iter().flat_map(SignedData::all_signatures) {
+        report
+            .signatures
+            .push(verify_signed_data(image, signature, trust_store, time));
+    }
+    report
+}
+
+fn verify_signed_data(
+    image: &PeFile,
+    signature: &SignedData,
+    trust_store: &TrustStore,
+    time: Option<i64>,
+) -> SignatureReport {
+    let mut report = SignatureReport {
+        digest_algorithm: None,
+        chain: Vec::new(),
+        verification_time: time.unwrap_or_else(now),
+        timestamps: Vec::new(),
+        failures: Vec::new(),
+    };
+
+    // The image digest.
+    match &signature.indirect_data {
+        Some(indirect_data) => match indirect_data.digest_algorithm.hash_algorithm() {
+            Some(algorithm) => {
+                report.digest_algorithm = Some(algorithm);
+                match image.authenticode_digest(algorithm) {
+                    Ok(actual) if actual != indirect_data.digest => {
+                        report
+                            .failures
+                            .push(VerificationFailure::ImageDigestMismatch {
+                                signed: indirect_data.digest.clone(),
+                                actual,
+                            })
+                    }
+                    Ok(_) => {}
+                    Err(error) => report
+                        .failures
+                        .push(VerificationFailure::Malformed(error.to_string())),
+                }
+            }
+            None => report
+                .failures
+                .push(VerificationFailure::UnsupportedDigestAlgorithm(
+                    indirect_data.digest_algorithm.oid.clone(),
+                )),
+        },
+        None => report
+            .failures
+            .push(VerificationFailure::MissingIndirectData),
+    }
+
+    let signer_info = match signature.signer_infos.as_slice() {
+        [signer_info] => signer_info,
+        signer_infos => {
+            report
+                .failures
+                .push(VerificationFailure::SignerCount(signer_infos.len()));
+            return report;
+        }
+    };
+
+    // A valid timestamp is the best evidence of when the image was signed, so certificates that
+    // have expired since then don't count against it.
+    report.timestamps = Timestamp::from_signer_info(signature, signer_info)
+        .into_iter()
+        .map(|timestamp| match timestamp {
+            Ok(timestamp) => timestamp.verify(signer_info, trust_store),
+            Err(error) => TimestampReport::malformed(error),
+        })
+        .collect();
+    if time.is_none() {
+        let timestamp_time = report
+            .timestamps
+            .iter()
+            .find(|timestamp| timestamp.is_valid())
+            .and_then(TimestampReport::signing_time);
+        if let Some(signing_time) = timestamp_time {
+            report.verification_time = signing_time;
+        }
+    }
+
+    // The signer's signature.
+    let signer_certificate = match signature.signer_certificate(signer_info) {
+        Some(certificate) => certificate,
+        None => {
+            report
+                .failures
+                .push(VerificationFailure::SignerCertificateNotFound);
+            return report;
+        }
+    };
+    if let Some(indirect_data) = &signature.indirect_data {
+        if let Err(failure) = verify_signer_info(
+            signer_info,
+            signer_certificate,
+            &indirect_data.signed_content,
+        ) {
+            report.failures.push(failure);
+        }
+    }
+
+    // The certificate chain.
+    let (chain, failures) = build_chain(
+        signer_certificate,
+        &signature.certificates,
+        trust_store,
+        report.verification_time,
+        Some(oid::CODE_SIGNING),
+    );
+    report.chain = chain;
+    report.failures.extend(failures);
+    report
+}
+
+/// Checks a signer's signature over some content. If the signer has authenticated attributes,
+/// the messageDigest attribute has to be the hash of the content, and the signature covers the
+/// attributes. Otherwise, the signature covers the content directly.
+pub(crate) fn verify_signer_info(
+    signer_info: &SignerInfo,
+    signer_certificate: &Certificate,
+    content: &[u8],
+) -> std::result::Result<(), VerificationFailure> {
+    let algorithm = signer_info
+        .digest_algorithm
+        .hash_algorithm()
+        .ok_or_else(|| {
+            VerificationFailure::UnsupportedDigestAlgorithm(
+                signer_info.digest_algorithm.oid.clone(),
+            )
+        })?;
+    let content_digest = algorithm.digest(content);
+    let signed_digest = match &signer_info.authenticated_attributes_der {
+        Some(attributes) => {
+            match signer_info
+                .message_digest()
+                .map_err(|error| VerificationFailure::Malformed(error.to_string()))?
+            {
+                Some(message_digest) if message_digest == content_digest => {}
+                Some(_) => return Err(VerificationFailure::MessageDigestMismatch),
+                None => return Err(VerificationFailure::MissingMessageDigest),
+            }
+            algorithm.digest(attributes)
+        }
+        None => content_digest,
+    };
+    if verify_digest(
+        signer_certificate,
+        algorithm,
+        &signed_digest,
+        &signer_info.encrypted_digest,
+    )? {
+        Ok(())
+    } else {
+        Err(VerificationFailure::InvalidSignerSignature)
+    }
+}
+
+/// Builds the chain from a certificate up to a root in the trust store, checking each
+/// certificate's signature and validity along the way. Every issuer has to be a CA that's allowed
+/// to sign certificates. If `leaf_usage` is provided and the leaf has an extended key usage
+/// extension, the extension has to include it.
+pub(crate) fn build_chain(
+    leaf: &Certificate,
+    intermediates: &[Certificate],
+    trust_store: &TrustStore,
+    time: i64,
+    leaf_usage: Option<&str>,
+) -> (Vec<Certificate>, Vec<VerificationFailure>) {
+    let mut chain = vec![leaf.clone()];
+    let mut failures = Vec::new();
+    if let (Some(usage), Some(usages)) = (leaf_usage, leaf.extended_key_usages()) {
+        if !usages.iter().any(|leaf_usage| leaf_usage == usage) {
+            failures.push(VerificationFailure::MissingExtendedKeyUsage {
+                subject: leaf.subject.to_string(),
+                usage: usage.into(),
+            });
+        }
+    }
+    loop {
+        let current = chain.last().unwrap();
+        if !current.is_valid_at(time) {
+            failures.push(VerificationFailure::CertificateNotValidAtTime {
+                subject: current.subject.to_string(),
+                not_before: current.not_before,
+                not_after: current.not_after,
+                time,
+            });
+        }
+        if trust_store.find_exact(current).is_some() {
+            break;
+        }
+        if chain.len() >= MAX_CHAIN_LENGTH {
+            failures.push(VerificationFailure::ChainTooLong);
+            break;
+        }
+
+        // Roots from the trust store are preferred over any certificates that came with the
+        // signature, so that cross-signed intermediates don't lead somewhere untrusted.
+        let candidates = trust_store.roots.iter().chain(intermediates.iter());
+        let mut issuer = None;
+        let mut has_bad_signature = false;
+        for candidate in candidates.filter(|candidate| {
+            candidate.subject.raw == current.issuer.raw && candidate.raw != current.raw
+        }) {
+            match verify_certificate(current, candidate) {
+                Ok(true) => {
+                    issuer = Some(candidate.clone());
+                    break;
+                }
+                Ok(false) => has_bad_signature = true,
+                Err(failure) => {
+                    failures.push(failure);
+                    return (chain, failures);
+                }
+            }
+        }
+        match issuer {
+            Some(issuer) => {
+                if !issuer.is_ca() {
+                    failures.push(VerificationFailure::IssuerNotCa {
+                        subject: issuer.subject.to_string(),
+                    });
+                }
+                if !issuer.allows_certificate_signing() {
+                    failures.push(VerificationFailure::IssuerCannotSignCertificates {
+                        subject: issuer.subject.to_string(),
+                    });
+                }
+                chain.push(issuer);
+            }
+            None if current.is_self_issued() => {
+                failures.push(VerificationFailure::UntrustedRoot {
+                    subject: current.subject.to_string(),
+                });
+                break;
+            }
+            None if has_bad_signature => {
+                failures.push(VerificationFailure::InvalidCertificateSignature {
+                    subject: current.subject.to_string(),
+                });
+                break;
+            }
+            None => {
+                failures.push(VerificationFailure::IssuerNotFound {
+                    subject: current.subject.to_string(),
+                    issuer: current.issuer.to_string(),
+                });
+                break;
+            }
+        }
+    }
+    (chain, failures)
+}
+
+/// Checks that `issuer` signed `certificate`.
+fn verify_certificate(
+    certificate: &Certificate,
+    issuer: &Certificate,
+) -> std::result::Result<bool, VerificationFailure> {
+    let algorithm = certificate
+        .signature_algorithm
+        .hash_algorithm()
+        .ok_or_else(|| {
+            VerificationFailure::UnsupportedSignatureAlgorithm(
+                certificate.signature_algorithm.oid.clone(),
+            )
+        })?;
+    verify_digest(
+        issuer,
+        algorithm,
+        &algorithm.digest(&certificate.tbs_certificate),
+        &certificate.signature,
+    )
+}
+
+/// Checks a signature over a digest using a certificate's public key. RSA (PKCS#1 v1.5) and ECDSA
+/// with P-256 or P-384 keys are supported.
+pub(crate) fn verify_digest(
+    certificate: &Certificate,
+    algorithm: HashAlgorithm,
+    digest: &[u8],
+    signature: &[u8],
+) -> std::result::Result<bool, VerificationFailure> {
+    let key_algorithm = &certificate.public_key_algorithm;
+    let unsupported =
+        || VerificationFailure::UnsupportedSignatureAlgorithm(key_algorithm.oid.clone());
+    match key_algorithm.oid.as_str() {
+        oid::RSA_ENCRYPTION => {
+            let key = RsaPublicKey::from_pkcs1_der(&certificate.public_key)
+                .map_err(|error| VerificationFailure::Malformed(error.to_string()))?;
+            let scheme = match algorithm {
+                HashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+                HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+                HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+                HashAlgorithm::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+            };
+            Ok(key.verify(scheme, digest, signature).is_ok())
+        }
+        oid::EC_PUBLIC_KEY => {
+            let curve = key_algorithm
+                .parameters
+                .as_deref()
+                .and_then(|parameters| DerValue::parse(parameters).ok()?.as_oid().ok())
+                .ok_or_else(unsupported)?;
+            match curve.as_str() {
+                oid::SECP256R1 => {
+                    let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&certificate.public_key)
+                        .map_err(|error| VerificationFailure::Malformed(error.to_string()))?;
+                    Ok(p256::ecdsa::Signature::from_der(signature)
+                        .map(|signature| key.verify_prehash(digest, &signature).is_ok())
+                        .unwrap_or(false))
+                }
+                oid::SECP384R1 => {
+                    let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(&certificate.public_key)
+                        .map_err(|error| VerificationFailure::Malformed(error.to_string()))?;
+                    Ok(p384::ecdsa::Signature::from_der(signature)
+                        .map(|signature| key.verify_prehash(digest, &signature).is_ok())
+                        .unwrap_or(false))
+                }
+                _ => Err(unsupported()),
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The certificates and signature in testdata are generated by testdata/generate.py.
+
+    /// 2021-06-01, when every certificate in the test chain was valid.
+    const SIGNING_TIME: i64 = 1_622_505_600;
+
+    /// 2023-01-01, after the leaf expired.
+    const AFTER_EXPIRY: i64 = 1_672_531_200;
+
+    fn root() -> Certificate {
+        Certificate::from_der(include_bytes!("testdata/root.der")).unwrap()
+    }
+
+    fn intermediate() -> Certificate {
+        Certificate::from_der(include_bytes!("testdata/intermediate.der")).unwrap()
+    }
+
+    fn leaf() -> Certificate {
+        Certificate::from_der(include_bytes!("testdata/leaf.der")).unwrap()
+    }
+
+    fn trust_store() -> TrustStore {
+        TrustStore {
+            roots: vec![root()],
+        }
+    }
+
+    fn subject(certificate: &Certificate) -> String {
+        certificate.subject.to_string()
+    }
+
+    fn chain_subjects(chain: &[Certificate]) -> Vec<String> {
+        chain.iter().map(subject).collect()
+    }
+
+    #[test]
+    fn chain_to_a_trusted_root() {
+        let (chain, failures) = build_chain(
+            &leaf(),
+            &[intermediate()],
+            &trust_store(),
+            SIGNING_TIME,
+            Some(oid::CODE_SIGNING),
+        );
+        assert_eq!(failures, vec![]);
+        assert_eq!(
+            chain_subjects(&chain),
+            vec![subject(&leaf()), subject(&intermediate()), subject(&root())]
+        );
+    }
+
+    #[test]
+    fn chain_to_an_untrusted_root() {
+        let (chain, failures) = build_chain(
+            &leaf(),
+            &[intermediate(), root()],
+            &TrustStore::new(),
+            SIGNING_TIME,
+            None,
+        );
+        assert_eq!(chain.len(), 3);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::UntrustedRoot {
+                subject: subject(&root())
+            }]
+        );
+    }
+
+    #[test]
+    fn chain_with_a_missing_issuer() {
+        let (chain, failures) = build_chain(&leaf(), &[], &trust_store(), SIGNING_TIME, None);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::IssuerNotFound {
+                subject: subject(&leaf()),
+                issuer: subject(&intermediate()),
+            }]
+        );
+    }
+
+    #[test]
+    fn expired_leaf() {
+        let leaf = leaf();
+        let (chain, failures) =
+            build_chain(&leaf, &[intermediate()], &trust_store(), AFTER_EXPIRY, None);
+        assert_eq!(chain.len(), 3);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::CertificateNotValidAtTime {
+                subject: subject(&leaf),
+                not_before: leaf.not_before,
+                not_after: leaf.not_after,
+                time: AFTER_EXPIRY,
+            }]
+        );
+    }
+
+    #[test]
+    fn issuer_that_is_not_a_ca() {
+        let issuer = Certificate::from_der(include_bytes!("testdata/not_ca.der")).unwrap();
+        let leaf = Certificate::from_der(include_bytes!("testdata/not_ca_leaf.der")).unwrap();
+        let (chain, failures) = build_chain(
+            &leaf,
+            std::slice::from_ref(&issuer),
+            &trust_store(),
+            SIGNING_TIME,
+            None,
+        );
+        assert_eq!(chain.len(), 3);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::IssuerNotCa {
+                subject: subject(&issuer)
+            }]
+        );
+    }
+
+    #[test]
+    fn issuer_without_the_key_cert_sign_bit() {
+        let issuer = Certificate::from_der(include_bytes!("testdata/no_cert_sign_ca.der")).unwrap();
+        let leaf = Certificate::from_der(include_bytes!("testdata/no_cert_sign_leaf.der")).unwrap();
+        let (chain, failures) = build_chain(
+            &leaf,
+            std::slice::from_ref(&issuer),
+            &trust_store(),
+            SIGNING_TIME,
+            None,
+        );
+        assert_eq!(chain.len(), 3);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::IssuerCannotSignCertificates {
+                subject: subject(&issuer)
+            }]
+        );
+    }
+
+    #[test]
+    fn leaf_without_the_required_usage() {
+        let (_, failures) = build_chain(
+            &leaf(),
+            &[intermediate()],
+            &trust_store(),
+            SIGNING_TIME,
+            Some(oid::TIME_STAMPING),
+        );
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::MissingExtendedKeyUsage {
+                subject: subject(&leaf()),
+                usage: oid::TIME_STAMPING.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn certificate_with_a_bad_signature() {
+        let mut leaf = leaf();
+        leaf.tbs_certificate[10] ^= 1;
+        let (chain, failures) =
+            build_chain(&leaf, &[intermediate()], &trust_store(), SIGNING_TIME, None);
+        assert_eq!(chain.len(), 1);
+        assert_eq!(
+            failures,
+            vec![VerificationFailure::InvalidCertificateSignature {
+                subject: subject(&leaf)
+            }]
+        );
+    }
+
+    #[test]
+    fn digest_signatures() {
+        let leaf = leaf();
+        let digest = HashAlgorithm::Sha256.digest(&leaf.tbs_certificate);
+        assert_eq!(
+            verify_digest(
+                &intermediate(),
+                HashAlgorithm::Sha256,
+                &digest,
+                &leaf.signature
+            ),
+            Ok(true)
+        );
+        // The wrong key, the wrong digest and a garbled signature all just fail to verify.
+        assert_eq!(
+            verify_digest(&root(), HashAlgorithm::Sha256, &digest, &leaf.signature),
+            Ok(false)
+        );
+        let mut other_digest = digest.clone();
+        other_digest[0] ^= 1;
+        assert_eq!(
+            verify_digest(
+                &intermediate(),
+                HashAlgorithm::Sha256,
+                &other_digest,
+                &leaf.signature
+            ),
+            Ok(false)
+        );
+        assert_eq!(
+            verify_digest(
+                &intermediate(),
+                HashAlgorithm::Sha256,
+                &digest,
+                b"signature"
+            ),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn unsupported_key_algorithm() {
+        let mut issuer = intermediate();
+        issuer.public_key_algorithm.oid = oid::SHA256.into();
+        let leaf = leaf();
+        assert_eq!(
+            verify_digest(
+                &issuer,
+                HashAlgorithm::Sha256,
+                &HashAlgorithm::Sha256.digest(&leaf.tbs_certificate),
+                &leaf.signature
+            ),
+            Err(VerificationFailure::UnsupportedSignatureAlgorithm(
+                oid::SHA256.into()
+            ))
+        );
+    }
+
+    fn signature() -> SignedData {
+        SignedData::from_der(include_bytes!("testdata/signed.der")).unwrap()
+    }
+
+    #[test]
+    fn signer_signature() {
+        let signature = signature();
+        let signer_info = &signature.signer_infos[0];
+        let signer_certificate = signature.signer_certificate(signer_info).unwrap();
+        assert_eq!(subject(signer_certificate), subject(&leaf()));
+        let content = &signature.indirect_data.as_ref().unwrap().signed_content;
+        assert_eq!(
+            verify_signer_info(signer_info, signer_certificate, content),
+            Ok(())
+        );
+
+        let mut other_content = content.clone();
+        other_content[0] ^= 1;
+        assert_eq!(
+            verify_signer_info(signer_info, signer_certificate, &other_content),
+            Err(VerificationFailure::MessageDigestMismatch)
+        );
+        assert_eq!(
+            verify_signer_info(signer_info, &intermediate(), content),
+            Err(VerificationFailure::InvalidSignerSignature)
+        );
+        let mut unsigned = signer_info.clone();
+        unsigned.authenticated_attributes.clear();
+        unsigned.authenticated_attributes_der = None;
+        assert_eq!(
+            verify_signer_info(&unsigned, signer_certificate, content),
+            Err(VerificationFailure::InvalidSignerSignature)
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! The parts of X.509 certificates that matter for checking Authenticode signatures.
+
+use super::{
+    der::{
+        context_tag, invalid, DerReader, DerValue, TAG_BIT_STRING, TAG_BOOLEAN, TAG_INTEGER,
+        TAG_SEQUENCE,
+    },
+    oid, HashAlgorithm,
+};
+use crate::error::Result;
+use std::fmt;
+
+/// An algorithm and its (still encoded) parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmIdentifier {
+    pub oid: String,
+    pub parameters: Option<Vec<u8>>,
+}
+
+impl AlgorithmIdentifier {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut reader = expect_sequence(value)?.reader();
+        let oid = reader.read()?.as_oid()?;
+        let parameters = if reader.is_empty() {
+            None
+        } else {
+            Some(reader.read()?.raw.to_vec())
+        };
+        Ok(Self { oid, parameters })
+    }
+
+    /// The hash algorithm this identifies, either directly or as part of a signature algorithm
+    /// (such as sha256WithRSAEncryption). Returns `None` for algorithms that aren't supported.
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        match self.oid.as_str() {
+            oid::SHA1 | oid::SHA1_WITH_RSA => Some(HashAlgorithm::Sha1),
+            oid::SHA256 | oid::SHA256_WITH_RSA | oid::ECDSA_WITH_SHA256 => {
+                Some(HashAlgorithm::Sha256)
+            }
+            oid::SHA384 | oid::SHA384_WITH_RSA | oid::ECDSA_WITH_SHA384 => {
+                Some(HashAlgorithm::Sha384)
+            }
+            oid::SHA512 | oid::SHA512_WITH_RSA | oid::ECDSA_WITH_SHA512 => {
+                Some(HashAlgorithm::Sha512)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A distinguished name, such as a certificate's subject or issuer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name {
+    /// Each attribute's OID and value, in the order they're encoded.
+    pub attributes: Vec<(String, String)>,
+
+    /// The encoded name, which is what names are compared by.
+    pub raw: Vec<u8>,
+}
+
+impl Name {
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut attributes = Vec::new();
+        let mut rdns = expect_sequence(value)?.reader();
+        while !rdns.is_empty() {
+            let mut rdn = rdns.read()?.reader();
+            while !rdn.is_empty() {
+                let mut attribute = rdn.read_tag(TAG_SEQUENCE)?.reader();
+                let oid = attribute.read()?.as_oid()?;
+                let value = attribute.read()?;
+                // Values that aren't strings are rare, so they're just shown as hex.
+                let value = value
+                    .as_string()
+                    .unwrap_or_else(|_| format!("#{}", hex::encode(value.raw)));
+                attributes.push((oid, value));
+            }
+        }
+        Ok(Self {
+            attributes,
+            raw: value.raw.to_vec(),
+        })
+    }
+
+    /// Gets the first value of the attribute with the provided OID.
+    pub fn get(&self, attribute_oid: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(oid, _)| oid == attribute_oid)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[inline]
+    pub fn common_name(&self) -> Option<&str> {
+        self.get(oid::COMMON_NAME)
+    }
+
+    #[inline]
+    pub fn organization(&self) -> Option<&str> {
+        self.get(oid::ORGANIZATION)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (attribute_oid, value)) in self.attributes.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            let label = match attribute_oid.as_str() {
+                oid::COMMON_NAME => "CN",
+                oid::COUNTRY => "C",
+                oid::LOCALITY => "L",
+                oid::STATE => "ST",
+                oid::ORGANIZATION => "O",
+                oid::ORGANIZATIONAL_UNIT => "OU",
+                oid::EMAIL_ADDRESS => "E",
+                other => other,
+            };
+            write!(f, "{}={}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A certificate extension, with its value still encoded.
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub oid: String,
+    pub is_critical: bool,
+    pub value: Vec<u8>,
+}
+
+/// An X.509 certificate.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub version: u64,
+
+    /// The serial number, as big-endian bytes.
+    pub serial_number: Vec<u8>,
+
+    pub issuer: Name,
+    pub subject: Name,
+
+    /// The start of the validity period, in seconds since the Unix epoch.
+    pub not_before: i64,
+
+    /// The end of the validity period, in seconds since the Unix epoch.
+    pub not_after: i64,
+
+    /// The algorithm of the subject's public key.
+    pub public_key_algorithm: AlgorithmIdentifier,
+
+    /// The subject's public key. For RSA keys, this is an encoded RSAPublicKey, and for EC keys it's
+    /// the encoded point.
+    pub public_key: Vec<u8>,
+
+    pub extensions: Vec<Extension>,
+
+    /// The algorithm the issuer signed the certificate with.
+    pub signature_algorithm: AlgorithmIdentifier,
+
+    pub signature: Vec<u8>,
+
+    /// The encoded TBSCertificate, which is what the issuer signed.
+    pub tbs_certificate: Vec<u8>,
+
+    /// The whole encoded certificate.
+    pub raw: Vec<u8>,
+}
+
+impl Certificate {
+    /// Parses a DER-encoded certificate.
+    pub fn from_der(data: &[u8]) -> Result<Self> {
+        Self::parse(&DerValue::parse(data)?)
+    }
+
+    pub fn parse(value: &DerValue<'_>) -> Result<Self> {
+        let mut certificate = expect_sequence(value)?.reader();
+        let tbs = certificate.read_tag(TAG_SEQUENCE)?;
+        let signature_algorithm = AlgorithmIdentifier::parse(&certificate.read()?)?;
+        let signature = certificate
+            .read_tag(TAG_BIT_STRING)?
+            .as_bit_string()?
+            .to_vec();
+
+        let mut fields = tbs.reader();
+        let version = match fields.read_optional(context_tag(0, true))? {
+            Some(version) => version.reader().read()?.as_u64()?,
+            None => 0,
+        };
+        let serial_number = fields.read_tag(TAG_INTEGER)?.as_integer_bytes()?.to_vec();
+        let _signature = fields.read_tag(TAG_SEQUENCE)?;
+        let issuer = Name::parse(&fields.read()?)?;
+        let mut validity = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let not_before = validity.read()?.as_time()?;
+        let not_after = validity.read()?.as_time()?;
+        let subject = Name::parse(&fields.read()?)?;
+        let mut public_key_info = fields.read_tag(TAG_SEQUENCE)?.reader();
+        let public_key_algorithm = AlgorithmIdentifier::parse(&public_key_info.read()?)?;
+        let public_key = public_key_info
+            .read_tag(TAG_BIT_STRING)?
+            .as_bit_string()?
+            .to_vec();
+        let _issuer_unique_id = fields.read_optional(context_tag(1, false))?;
+        let _subject_unique_id = fields.read_optional(context_tag(2, false))?;
+        let extensions = match fields.read_optional(context_tag(3, true))? {
+            Some(extensions) => parse_extensions(&extensions.reader().read()?)?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            version,
+            serial_number,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            public_key_algorithm,
+            public_key,
+            extensions,
+            signature_algorithm,
+            signature,
+            tbs_certificate: tbs.raw.to_vec(),
+            raw: value.raw.to_vec(),
+        })
+    }
+
+    /// Hashes the encoded certificate, which is how certificates are usually identified.
+    #[inline]
+    pub fn thumbprint(&self, algorithm: HashAlgorithm) -> Vec<u8> {
+        algorithm.digest(&self.raw)
+    }
+
+    /// The SHA-1 thumbprint, which is what Windows shows.
+    #[inline]
+    pub fn sha1_thumbprint(&self) -> Vec<u8> {
+        self.thumbprint(HashAlgorithm::Sha1)
+    }
+
+    #[inline]
+    pub fn sha256_thumbprint(&self) -> Vec<u8> {
+        self.thumbprint(HashAlgorithm::Sha256)
+    }
+
+    /// The serial number as a hex string.
+    #[inline]
+    pub fn serial_number_hex(&self) -> String {
+        hex::encode(&self.serial_number)
+    }
+
+    /// Gets the extension with the provided OID.
+    pub fn extension(&self, extension_oid: &str) -> Option<&Extension> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.oid == extension_oid)
+    }
+
+    /// If the basic constraints extension says that this is a CA certificate.
+    pub fn is_ca(&self) -> bool {
+        self.extension(oid::BASIC_CONSTRAINTS)
+            .and_then(|extension| {
+                let constraints = DerValue::parse(&extension.value).ok()?;
+                let is_ca = constraints.reader().read_optional(TAG_BOOLEAN).ok()?;
+                is_ca?.as_bool().ok()
+            })
+            .unwrap_or(false)
+    }
+
+    /// If the key usage extension allows the key to be used to sign certificates. Certificates
+    /// without the extension aren't restricted, so this is true for them.
+    pub fn allows_certificate_signing(&self) -> bool {
+        match self.extension(oid::KEY_USAGE) {
+            // keyCertSign is bit 5, counting from the most significant bit of the first byte.
+            Some(extension) => DerValue::parse(&extension.value)
+                .and_then(|key_usage| Ok(key_usage.as_bit_string()?.first().copied()))
+                .map(|bits| bits.unwrap_or(0) & 0x04 != 0)
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// The OIDs in the extended key usage extension, or `None` if the certificate doesn't have
+    /// one. A malformed extension is treated as having no usages at all.
+    pub fn extended_key_usages(&self) -> Option<Vec<String>> {
+        let extension = self.extension(oid::EXTENDED_KEY_USAGE)?;
+        let usages = DerValue::parse(&extension.value).and_then(|usages| {
+            expect_sequence(&usages)?
+                .reader()
+                .read_all()?
+                .iter()
+                .map(DerValue::as_oid)
+                .collect()
+        });
+        Some(usages.unwrap_or_default())
+    }
+
+    /// If the certificate was valid at the provided time (in seconds since the Unix epoch).
+    #[inline]
+    pub fn is_valid_at(&self, time: i64) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    /// If the certificate's subject and issuer are the same.
+    #[inline]
+    pub fn is_self_issued(&self) -> bool {
+        self.subject.raw == self.issuer.raw
+    }
+}
+
+fn parse_extensions(value: &DerValue<'_>) -> Result<Vec<Extension>> {
+    let mut reader = expect_sequence(value)?.reader();
+    let mut extensions = Vec::new();
+    while !reader.is_empty() {
+        let mut extension = reader.read_tag(TAG_SEQUENCE)?.reader();
+        let oid = extension.read()?.as_oid()?;
+        let is_critical = match extension.read_optional(TAG_BOOLEAN)? {
+            Some(is_critical) => is_critical.as_bool()?,
+            None => false,
+        };
+        let value = extension.read()?.as_octet_string()?.to_vec();
+        extensions.push(Extension {
+            oid,
+            is_critical,
+            value,
+        });
+    }
+    Ok(extensions)
+}
+
+pub(super) fn expect_sequence<'a>(value: &DerValue<'a>) -> Result<DerValue<'a>> {
+    if value.tag == TAG_SEQUENCE {
+        Ok(*value)
+    } else {
+        Err(invalid(format!(
+            "expected a sequence, but got tag 0x{:02x}",
+            value.tag
+        )))
+    }
+}
+
+/// Reads every certificate in a sequence of them, such as the certificates field of a
+/// SignedData.
+pub(super) fn parse_certificates(reader: &mut DerReader<'_>) -> Result<Vec<Certificate>> {
+    let mut certificates = Vec::new();
+    while !reader.is_empty() {
+        let value = reader.read()?;
+        // Attribute certificates and other choices are tagged, so they're skipped.
+        if value.tag == TAG_SEQUENCE {
+            certificates.push(Certificate::parse(&value)?);
+        }
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The certificates are generated by testdata/generate.py.
+
+    fn certificate(der: &[u8]) -> Certificate {
+        Certificate::from_der(der).unwrap()
+    }
+
+    #[test]
+    fn parses_a_certificate() {
+        let leaf = certificate(include_bytes!("testdata/leaf.der"));
+        assert_eq!(leaf.version, 2);
+        assert_eq!(leaf.serial_number, vec![3]);
+        assert_eq!(leaf.subject.common_name(), Some("peparse Test Publisher"));
+        assert_eq!(leaf.issuer.to_string(), "CN=peparse Test Code Signing CA");
+        // 2021-01-01 to 2022-01-01.
+        assert_eq!(leaf.not_before, 1_609_459_200);
+        assert_eq!(leaf.not_after, 1_640_995_200);
+        assert!(leaf.is_valid_at(1_622_505_600));
+        assert!(!leaf.is_valid_at(1_640_995_201));
+        assert_eq!(leaf.public_key_algorithm.oid, oid::EC_PUBLIC_KEY);
+        assert_eq!(leaf.signature_algorithm.oid, oid::ECDSA_WITH_SHA256);
+        assert!(!leaf.is_self_issued());
+        assert!(certificate(include_bytes!("testdata/root.der")).is_self_issued());
+    }
+
+    #[test]
+    fn basic_constraints() {
+        assert!(certificate(include_bytes!("testdata/root.der")).is_ca());
+        assert!(certificate(include_bytes!("testdata/intermediate.der")).is_ca());
+        assert!(!certificate(include_bytes!("testdata/leaf.der")).is_ca());
+        assert!(!certificate(include_bytes!("testdata/not_ca.der")).is_ca());
+    }
+
+    #[test]
+    fn key_usage() {
+        assert!(certificate(include_bytes!("testdata/root.der")).allows_certificate_signing());
+        assert!(certificate(include_bytes!("testdata/not_ca.der")).allows_certificate_signing());
+        assert!(!certificate(include_bytes!("testdata/leaf.der")).allows_certificate_signing());
+        assert!(!certificate(include_bytes!("testdata/no_cert_sign_ca.der"))
+            .allows_certificate_signing());
+
+        // Certificates without the extension aren't restricted.
+        let mut root = certificate(include_bytes!("testdata/root.der"));
+        root.extensions
+            .retain(|extension| extension.oid != oid::KEY_USAGE);
+        assert!(root.allows_certificate_signing());
+    }
+
+    #[test]
+    fn extended_key_usages() {
+        assert_eq!(
+            certificate(include_bytes!("testdata/leaf.der")).extended_key_usages(),
+            Some(vec![oid::CODE_SIGNING.to_string()])
+        );
+        assert_eq!(
+            certificate(include_bytes!("testdata/root.der")).extended_key_usages(),
+            None
+        );
+
+        let mut leaf = certificate(include_bytes!("testdata/leaf.der"));
+        for extension in &mut leaf.extensions {
+            if extension.oid == oid::EXTENDED_KEY_USAGE {
+                extension.value = vec![0x04, 0x00];
+            }
+        }
+        assert_eq!(leaf.extended_key_usages(), Some(vec![]));
+    }
+}
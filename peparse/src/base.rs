@@ -1,9 +1,40 @@
+#[cfg(feature = "authenticode")]
+use crate::authenticode::{pkcs7::SignedData, HashAlgorithm, Hasher};
 use crate::{
-    coff::CoffFileHeader,
+    coff::{constants::Machine, CoffFileHeader},
     error::{Error, Result},
-    image::{DataDirectoryPointer, MzHeader, OptionalHeader},
-    sections::{Section, SectionHeader},
-    util::iter_to_result,
+    image::{
+        constants::{DataDirectoryType, DllCharacteristics, WinCertType},
+        AttributeCertificate, AttributeCertificateTable, DataDirectoryPointer, MzHeader,
+        OptionalHeader,
+    },
+    sections::{
+        debug::{
+            codeview::{CodeView, PdbInfo},
+            records::EmbeddedPortablePdb,
+            DebugData, DebugDirectory, DebugType,
+        },
+        dynamic_relocation::{DynamicFixups, DynamicRelocationTable},
+        export::ExportDirectory,
+        hybrid::{
+            Arm64EcMetadata, ChpeX86Metadata, CodeRange, CodeRangeEntryPoint, HybridMetadata,
+            RedirectionEntry,
+        },
+        load_config::{GuardTableEntry, LoadConfig, SafeSehHandler},
+        pdata::{
+            arm::ArmUnwindData,
+            arm64::{Arm64UnwindCode, Arm64UnwindData},
+            xdata::{ArmUnwindCode, UnwindRecord},
+            ExceptionTable, FunctionInfo, FunctionUnwind, RuntimeFunction, UnwindFlags, UnwindInfo,
+            X64Function,
+        },
+        relocation::{BaseRelocationType, RelocationTable},
+        tls::{TlsCallback, TlsDirectory},
+        Section, SectionHeader,
+    },
+    util::{
+        iter_to_result, next_different_sizes, next_null_terminated, symbol_store_path, table_size,
+    },
     Rva, Va,
 };
 use bytes::Bytes;
@@ -13,7 +44,7 @@ use segsource::{
     U8Source as _,
 };
 use std::path::Path;
-use std::{convert::TryFrom, mem, ops::Range};
+use std::{cmp::min, convert::TryFrom, mem, ops::Range};
 
 #[derive(TryFromSegment, Debug, Clone)]
 #[from_seg(error(crate::Error))]
@@ -52,6 +83,102 @@ impl PeHeader {
     }
 }
 
+/// The alternate view of an ARM64X image: the image as it looks once its ARM64X dynamic
+/// relocations are applied. The relocations swap the entry point and the export directory, among
+/// other things, so they're read from here rather than from the [`PeFile`].
+#[derive(Debug)]
+pub struct Arm64XView {
+    pub header: PeHeader,
+
+    /// The mapped image, with the relocations applied.
+    #[debug(skip)]
+    pub image: BytesSource,
+}
+
+/// An exported symbol of an [`Arm64XView`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arm64XExport {
+    pub ordinal: u32,
+    pub name: Option<String>,
+
+    /// The RVA of the symbol. For forwarded exports, this is the RVA of the forwarder string
+    /// instead.
+    pub rva: Rva,
+}
+
+impl Arm64XView {
+    /// The RVA of the entry point of the alternate view.
+    #[inline]
+    pub fn entry_point(&self) -> Rva {
+        self.header.optional_header.address_of_entry_point
+    }
+
+    /// Reads every entry in the export directory of the alternate view. Unused slots in the export
+    /// address table are skipped.
+    pub fn exports(&self) -> Result<Vec<Arm64XExport>> {
+        let dir = match self
+            .header
+            .optional_header
+            .data_directory(DataDirectoryType::ExportTable)
+        {
+            Some(dir) => dir,
+            None => return Ok(Vec::new()),
+        };
+        let directory = ExportDirectory::try_from((
+            self.header.is_pe32_plus(),
+            &self.image.get_n(dir.rva as usize, dir.size as usize)?,
+        ))?;
+        let addresses = self.image.get_n(
+            directory.export_address_table as usize,
+            table_size(directory.address_table_entries as u64, 4)?,
+        )?;
+        let mut exports = (0..directory.address_table_entries)
+            .map(|i| {
+                Ok(Arm64XExport {
+                    ordinal: directory.ordinal_base.wrapping_add(i),
+                    name: None,
+                    rva: addresses.next_u32()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let name_count = directory.number_of_name_pointers as u64;
+        let names = self
+            .image
+            .get_n(directory.name_pointer as usize, table_size(name_count, 4)?)?;
+        let ordinals = self
+            .image
+            .get_n(directory.ordinal_table as usize, table_size(name_count, 2)?)?;
+        for _ in 0..name_count {
+            let name_rva = names.next_u32()?;
+            let index = ordinals.next_u16()? as usize;
+            let name = next_null_terminated(&self.image.all_after(name_rva as usize)?)?;
+            if let Some(export) = exports.get_mut(index) {
+                export.name = Some(String::from_utf8(name)?);
+            }
+        }
+        exports.retain(|export| export.rva != 0);
+        Ok(exports)
+    }
+}
+
+/// The most TLS callbacks that [`PeFile::tls_callbacks`] will read before giving up on finding the
+/// end of the array.
+pub const MAX_TLS_CALLBACKS: usize = 0x1000;
+
+/// An image that's been rebased with [`PeFile::rebase`].
+#[derive(Debug, Clone)]
+pub struct RebasedImage {
+    /// The mapped image, with the relocations applied.
+    #[debug(skip)]
+    pub image: Vec<u8>,
+
+    /// The RVAs of relocations whose targets aren't inside of any section, which were skipped.
+    pub out_of_bounds: Vec<Rva>,
+
+    /// Relocations with a type that can't be applied, which were skipped.
+    pub unsupported: Vec<(Rva, BaseRelocationType)>,
+}
+
 #[derive(Debug)]
 pub struct PeFile {
     pub header: PeHeader,
@@ -59,6 +186,8 @@ pub struct PeFile {
     pub file_size: u64,
     #[debug(skip)]
     pub overlay: BytesSource,
+    #[debug(skip)]
+    data: MappedFileSource,
 }
 
 impl PeFile {
@@ -70,13 +199,14 @@ impl PeFile {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file_md = path.as_ref().metadata()?;
         //TODO account for non-native Endidness
+        let data = MappedFileSource::from_file(path.as_ref(), Endidness::native())?;
         let mut source = MappedFileSource::from_file(path, Endidness::native())?;
         let mut header = PeHeader::try_from(&source.all()?)?;
         let is_pe32_plus = header.is_pe32_plus();
         let mut section_headers = mem::take(&mut header.section_headers);
         let mut overlay_offset = 0;
         for sec_header in &section_headers {
-            let maybe_offset = sec_header.calc_overlay_offset(&header);
+            let maybe_offset = sec_header.calc_overlay_offset(&header)?;
             if overlay_offset < maybe_offset {
                 overlay_offset = maybe_offset;
             }
@@ -107,18 +237,861 @@ impl PeFile {
             header,
             sections,
             overlay,
+            data,
             file_size: file_md.len(),
         })
     }
 
-    pub fn rva_to_offset(&self, rva: Rva) -> Result<u64> {
-        if let Some(section) = self.get_containing_section(rva) {
-            Ok(section.rva_to_offset(rva).unwrap())
+    /// Gets the requested data directory entry, or `None` if the image doesn't have one.
+    #[inline]
+    pub fn data_directory(&self, entry: DataDirectoryType) -> Option<&DataDirectoryPointer> {
+        self.header.optional_header.data_directory(entry)
+    }
+
+    /// Reads `size` bytes of the file starting at the provided file offset.
+    pub fn read_at_offset(&self, offset: u64, size: usize) -> Result<DataSegment<'_>> {
+        Ok(self.data.get_n(offset as usize, size)?)
+    }
+
+    /// Reads `size` bytes starting at the provided RVA as they'd look once the image is mapped.
+    /// See [`read_mapped_at_rva`](Self::read_mapped_at_rva).
+    pub fn read_at_rva(&self, rva: Rva, size: usize) -> Result<BytesSource> {
+        Ok(BytesSource::from_bytes(
+            Bytes::from(self.read_mapped_at_rva(rva, size)?),
+            self.data.endidness(),
+        )?)
+    }
+
+    /// Reads the `u32` at the provided RVA.
+    fn read_u32_at_rva(&self, rva: Rva) -> Result<u32> {
+        Ok(self.read_at_rva(rva, 4)?.all()?.next_u32()?)
+    }
+
+    /// Reads `size` bytes starting at the provided RVA as they'd look once the image is mapped:
+    /// anything past the section's data in the file reads as zeros. The bytes have to be inside
+    /// of a single section.
+    pub fn read_mapped_at_rva(&self, rva: Rva, size: usize) -> Result<Vec<u8>> {
+        let invalid = Error::InvalidRva { rva };
+        let section = self
+            .sections
+            .iter()
+            .find(|section| {
+                rva.checked_sub(section.header.virtual_address)
+                    .map(|start| start < section.loader_virtual_size())
+                    .unwrap_or(false)
+            })
+            .ok_or(invalid)?;
+        let start = (rva - section.header.virtual_address) as usize;
+        match start.checked_add(size) {
+            Some(end) if end <= section.loader_virtual_size() as usize => {}
+            _ => return Err(Error::InvalidRva { rva }),
+        }
+        let mut data = vec![0; size];
+        let offset = section.loader_pointer_to_raw_data() as u64;
+        let read_size = min(
+            section.loader_read_size() as u64,
+            self.file_size.saturating_sub(offset),
+        ) as usize;
+        if start < read_size {
+            let count = min(read_size - start, size);
+            data[..count]
+                .copy_from_slice(self.read_at_offset(offset + start as u64, count)?.as_ref());
+        }
+        Ok(data)
+    }
+
+    /// Parses every block in the base relocation data directory.
+    pub fn base_relocations(&self) -> Result<RelocationTable> {
+        if let Some(dir) = self.data_directory(DataDirectoryType::BaseRelocationTable) {
+            let source = self.read_at_rva(dir.rva, dir.size as usize)?;
+            let segment = source.all()?;
+            RelocationTable::try_from((self.header.coff_header.machine, &segment))
         } else {
-            Err(Error::InvalidRva { rva })
+            Ok(RelocationTable::default())
         }
     }
 
+    /// Creates a copy of the image as it would look if it was loaded at `new_base` instead of its
+    /// preferred image base: every base relocation that can be applied is, and the image base in
+    /// the optional header is updated. Relocations that can't be applied are listed in the
+    /// result instead of failing the whole rebase.
+    pub fn rebase(&self, new_base: Va) -> Result<RebasedImage> {
+        let mut image = self.map_image()?;
+        let mut out_of_bounds = Vec::new();
+        let mut unsupported = Vec::new();
+        let delta = new_base.wrapping_sub(self.header.optional_header.windows_specific.image_base);
+        for block in self.base_relocations()?.blocks {
+            for relocation in block.relocations {
+                let rva = block.page_rva.wrapping_add(relocation.offset as Rva);
+                match relocation.base_type {
+                    BaseRelocationType::Absolute => continue,
+                    BaseRelocationType::Reserved(_) => {
+                        unsupported.push((rva, relocation.base_type));
+                        continue;
+                    }
+                    _ => {}
+                }
+                let size = relocation.base_type.patch_size();
+                let target = if self.is_mapped_range(rva, size) {
+                    image.get_mut(rva as usize..rva as usize + size)
+                } else {
+                    None
+                };
+                match target {
+                    Some(target) => relocation
+                        .base_type
+                        .apply(target, delta, relocation.low_bits),
+                    None => out_of_bounds.push(rva),
+                }
+            }
+        }
+        // The image base sits right after the standard fields of the optional header. The
+        // headers are mapped at RVA 0, so its file offset is also its RVA.
+        let image_base_offset = self.header.ms_dos_header.pe_offset as usize + 24 + 24;
+        let (start, bytes) = if self.is_pe32_plus() {
+            (image_base_offset, new_base.to_le_bytes().to_vec())
+        } else {
+            (
+                image_base_offset + 4,
+                (new_base as u32).to_le_bytes().to_vec(),
+            )
+        };
+        image
+            .get_mut(start..start + bytes.len())
+            .ok_or(Error::InvalidRva { rva: start as Rva })?
+            .copy_from_slice(&bytes);
+        Ok(RebasedImage {
+            image,
+            out_of_bounds,
+            unsupported,
+        })
+    }
+
+    /// Checks whether the `size` bytes starting at `rva` are all inside of a single section, as
+    /// the loader maps it. Bytes in the headers, in the gaps between sections or past a section's
+    /// virtual size aren't.
+    fn is_mapped_range(&self, rva: Rva, size: usize) -> bool {
+        self.sections.iter().any(|section| {
+            rva.checked_sub(section.header.virtual_address)
+                .and_then(|start| (start as usize).checked_add(size))
+                .map(|end| end <= section.loader_virtual_size() as usize)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Builds the image the same way the Windows loader maps it into memory: the headers are
+    /// copied to the start of the image, each section is placed at its virtual address, and
+    /// everything past a section's raw data is zero-filled. The result is always `size_of_image`
+    /// bytes long.
+    pub fn map_image(&self) -> Result<Vec<u8>> {
+        let windows_specific = &self.header.optional_header.windows_specific;
+        let mut image = vec![0; windows_specific.size_of_image as usize];
+        let headers_size = min(
+            min(windows_specific.size_of_headers as u64, self.file_size),
+            image.len() as u64,
+        ) as usize;
+        image[..headers_size].copy_from_slice(self.read_at_offset(0, headers_size)?.as_ref());
+        for section in &self.sections {
+            let start = section.header.virtual_address as usize;
+            let offset = section.loader_pointer_to_raw_data() as u64;
+            let size = min(
+                section.loader_read_size() as u64,
+                self.file_size.saturating_sub(offset),
+            ) as usize;
+            let size = min(size, image.len().saturating_sub(start));
+            if size == 0 {
+                continue;
+            }
+            let data = self.read_at_offset(offset, size)?;
+            image[start..start + size].copy_from_slice(data.as_ref());
+        }
+        Ok(image)
+    }
+
+    /// Parses the attribute certificate table, which holds the image's Authenticode signatures.
+    /// The table isn't mapped into memory, so it's usually part of the overlay.
+    pub fn attribute_certificates(&self) -> Result<AttributeCertificateTable> {
+        match self.data_directory(DataDirectoryType::CertificateTable) {
+            Some(dir) => {
+                // The "RVA" of this data directory entry is actually a file offset.
+                let offset = dir.rva as u64;
+                let segment = self.read_at_offset(offset, dir.size as usize)?;
+                AttributeCertificate::parse_table(offset, &segment)
+            }
+            None => Ok(AttributeCertificateTable::default()),
+        }
+    }
+
+    /// Decodes every Authenticode signature in the attribute certificate table. Nested
+    /// signatures are reachable from the signature they're nested in.
+    #[cfg(feature = "authenticode")]
+    pub fn signatures(&self) -> Result<Vec<SignedData>> {
+        self.attribute_certificates()?
+            .entries
+            .iter()
+            .filter(|certificate| {
+                certificate.w_certificate_type == WinCertType::PkcsSignedData as u16
+            })
+            .map(AttributeCertificate::signed_data)
+            .collect()
+    }
+
+    /// Computes the image's Authenticode digest (its "authentihash"), which is what a signature
+    /// signs. The checksum, the certificate table's data directory entry and the certificate
+    /// table itself are left out, so the digest doesn't change when an image is re-signed. The
+    /// headers are hashed first, then each section in order of its file offset (which is
+    /// normally header order), then whatever data follows the last section.
+    #[cfg(feature = "authenticode")]
+    pub fn authenticode_digest(&self, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+        let mut hasher = Hasher::new(algorithm);
+        let optional_header_offset = self.header.ms_dos_header.pe_offset as u64 + 24;
+        let checksum_offset = optional_header_offset + 64;
+        let data_directory_offset =
+            optional_header_offset + if self.is_pe32_plus() { 112 } else { 96 };
+        let headers_size = min(
+            self.header.optional_header.windows_specific.size_of_headers as u64,
+            self.file_size,
+        );
+
+        let mut hash_range = |start: u64, end: u64| -> Result<()> {
+            if end > start {
+                hasher.update(self.read_at_offset(start, (end - start) as usize)?.as_ref());
+            }
+            Ok(())
+        };
+        hash_range(0, checksum_offset)?;
+        if self.header.optional_header.data_directory_ptrs.len()
+            > DataDirectoryType::CertificateTable as usize
+        {
+            let cert_entry_offset =
+                data_directory_offset + DataDirectoryType::CertificateTable as u64 * 8;
+            hash_range(checksum_offset + 4, cert_entry_offset)?;
+            hash_range(cert_entry_offset + 8, headers_size)?;
+        } else {
+            hash_range(checksum_offset + 4, headers_size)?;
+        }
+
+        let mut section_headers: Vec<&SectionHeader> = self
+            .sections
+            .iter()
+            .map(|section| &section.header)
+            .filter(|header| header.size_of_raw_data > 0)
+            .collect();
+        section_headers.sort_by_key(|header| header.pointer_to_raw_data);
+        let mut bytes_hashed = headers_size;
+        for header in section_headers {
+            let start = header.pointer_to_raw_data as u64;
+            let end = min(start + header.size_of_raw_data as u64, self.file_size);
+            hash_range(start, end)?;
+            bytes_hashed = bytes_hashed.max(end);
+        }
+
+        match self.data_directory(DataDirectoryType::CertificateTable) {
+            Some(dir) if dir.rva as u64 >= bytes_hashed => {
+                let cert_start = min(dir.rva as u64, self.file_size);
+                let cert_end = min(cert_start + dir.size as u64, self.file_size);
+                hash_range(bytes_hashed, cert_start)?;
+                hash_range(cert_end, self.file_size)?;
+            }
+            _ => hash_range(bytes_hashed, self.file_size)?,
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Parses the exception table (.pdata) data directory.
+    pub fn exception_table(&self) -> Result<ExceptionTable> {
+        if let Some(dir) = self.data_directory(DataDirectoryType::ExceptionTable) {
+            let source = self.read_at_rva(dir.rva, dir.size as usize)?;
+            let segment = source.all()?;
+            ExceptionTable::try_from((self.header.coff_header.machine, &segment))
+        } else {
+            Ok(ExceptionTable::default())
+        }
+    }
+
+    /// Parses the x64 unwind info at the provided RVA.
+    pub fn unwind_info(&self, rva: Rva) -> Result<UnwindInfo> {
+        let header = self.read_at_rva(rva, UnwindInfo::HEADER_SIZE)?;
+        let size = UnwindInfo::size_from_header(header.all()?.as_ref());
+        UnwindInfo::try_from((rva, &self.read_at_rva(rva, size)?.all()?))
+    }
+
+    /// Parses the unpacked ARM or ARM64 unwind record (.xdata) at the provided RVA. Use
+    /// [`ArmNtUnwindCode`](crate::sections::pdata::arm::ArmNtUnwindCode) for ARM images and
+    /// [`Arm64UnwindCode`](crate::sections::pdata::arm64::Arm64UnwindCode) for ARM64 images.
+    pub fn arm_unwind_record<C: ArmUnwindCode>(&self, rva: Rva) -> Result<UnwindRecord<C>> {
+        let mut header = C::parse_header(self.read_u32_at_rva(rva)?);
+        if header.needs_extension() {
+            header.extend(self.read_u32_at_rva(rva + 4)?);
+        }
+        UnwindRecord::try_from((rva, &self.read_at_rva(rva, header.record_size())?.all()?))
+    }
+
+    /// Finds the function containing the provided RVA using the exception table. Only x64, ARM and
+    /// ARM64 images are supported. If you need to look up many addresses, it's faster to parse
+    /// the [`ExceptionTable`] once and use [`ExceptionTable::find`].
+    pub fn function_containing(&self, rva: Rva) -> Result<Option<FunctionInfo>> {
+        let table = self.exception_table()?;
+        match table.find(rva) {
+            Some(function) => {
+                let info = self.function_info(function)?;
+                Ok(if rva < info.end_address {
+                    Some(info)
+                } else {
+                    None
+                })
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves the unwind information for an exception table entry.
+    pub fn function_info(&self, function: &RuntimeFunction) -> Result<FunctionInfo> {
+        let begin_address = function.begin_address();
+        match function {
+            RuntimeFunction::X64(function) => {
+                // If the low bit is set, the unwind data is actually the RVA of the primary
+                // RUNTIME_FUNCTION entry for the function.
+                let (unwind_rva, is_indirect) = if function.unwind_information & 1 == 1 {
+                    let primary_rva = function.unwind_information & !1;
+                    let primary =
+                        X64Function::try_from(&self.read_at_rva(primary_rva, 12)?.all()?)?;
+                    (primary.unwind_information, true)
+                } else {
+                    (function.unwind_information, false)
+                };
+                let unwind = self.unwind_info(unwind_rva)?;
+                Ok(FunctionInfo {
+                    begin_address,
+                    end_address: function.end_address,
+                    is_chained: is_indirect || unwind.flags.contains(UnwindFlags::ChainInfo),
+                    unwind: FunctionUnwind::X64(unwind),
+                })
+            }
+            RuntimeFunction::Arm64(function) => match &function.unwind_data {
+                Arm64UnwindData::Packed(packed) => Ok(FunctionInfo {
+                    begin_address,
+                    end_address: begin_address + packed.function_length,
+                    is_chained: packed.is_fragment(),
+                    unwind: FunctionUnwind::Arm64Packed(packed.clone()),
+                }),
+                Arm64UnwindData::Unpacked(rva) => {
+                    let record = self.arm_unwind_record(*rva)?;
+                    Ok(FunctionInfo {
+                        begin_address,
+                        end_address: begin_address + record.header.function_length,
+                        // Fragments mark that they chain to the parent function's unwind codes by
+                        // ending their prolog with end_c.
+                        is_chained: record.prolog.last() == Some(&Arm64UnwindCode::EndChained),
+                        unwind: FunctionUnwind::Arm64(record),
+                    })
+                }
+            },
+            RuntimeFunction::ArmNt(function) => match &function.unwind_data {
+                ArmUnwindData::Packed(packed) => Ok(FunctionInfo {
+                    begin_address,
+                    end_address: begin_address + packed.function_length,
+                    is_chained: packed.is_fragment(),
+                    unwind: FunctionUnwind::ArmNtPacked(packed.clone()),
+                }),
+                ArmUnwindData::Unpacked(rva) => {
+                    let record = self.arm_unwind_record(*rva)?;
+                    Ok(FunctionInfo {
+                        begin_address,
+                        end_address: begin_address + record.header.function_length,
+                        is_chained: record.header.is_fragment,
+                        unwind: FunctionUnwind::ArmNt(record),
+                    })
+                }
+            },
+            _ => Err(Error::UnsupportedMachine {
+                machine: self.header.coff_header.machine,
+                context: "function lookup".into(),
+            }),
+        }
+    }
+
+    /// Parses the load configuration directory, if the image has one. The structure's own size field
+    /// is used rather than the data directory's size, since older linkers set the latter to a
+    /// fixed value.
+    pub fn load_config(&self) -> Result<Option<LoadConfig>> {
+        if let Some(dir) = self.data_directory(DataDirectoryType::LoadConfigTable) {
+            let size = self.read_u32_at_rva(dir.rva)? as usize;
+            let size = min(size, LoadConfig::known_size(self.is_pe32_plus()));
+            let source = self.read_at_rva(dir.rva, size)?;
+            let segment = source.all()?;
+            Ok(Some(LoadConfig::try_from((self.is_pe32_plus(), &segment))?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads one of the Control Flow Guard tables, given the VA and entry count from the load
+    /// config.
+    fn guard_table(&self, config: &LoadConfig, va: Va, count: u64) -> Result<Vec<GuardTableEntry>> {
+        if va == 0 || count == 0 {
+            return Ok(Vec::new());
+        }
+        let stride = config.guard_flags.function_table_stride();
+        let source = self.read_at_rva(self.va_to_rva(va)?, table_size(count, 4 + stride)?)?;
+        let segment = source.all()?;
+        (0..count)
+            .map(|_| GuardTableEntry::try_from((stride, &segment)))
+            .collect()
+    }
+
+    /// Decodes the Control Flow Guard function table (GFIDS), which lists every valid indirect
+    /// call target.
+    pub fn guard_cf_functions(&self) -> Result<Vec<GuardTableEntry>> {
+        match self.load_config()? {
+            Some(config) => self.guard_table(
+                &config,
+                config.guard_cf_function_table,
+                config.guard_cf_function_count,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Decodes the Control Flow Guard address taken IAT table, which lists the IAT entries of
+    /// imports whose addresses are taken.
+    pub fn guard_address_taken_iat_entries(&self) -> Result<Vec<GuardTableEntry>> {
+        match self.load_config()? {
+            Some(config) => self.guard_table(
+                &config,
+                config.guard_address_taken_iat_entry_table,
+                config.guard_address_taken_iat_entry_count,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Decodes the Control Flow Guard long jump target table.
+    pub fn guard_long_jump_targets(&self) -> Result<Vec<GuardTableEntry>> {
+        match self.load_config()? {
+            Some(config) => self.guard_table(
+                &config,
+                config.guard_long_jump_target_table,
+                config.guard_long_jump_target_count,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Decodes the EH continuation target table.
+    pub fn guard_eh_continuation_targets(&self) -> Result<Vec<GuardTableEntry>> {
+        match self.load_config()? {
+            Some(config) => self.guard_table(
+                &config,
+                config.guard_eh_continuation_table,
+                config.guard_eh_continuation_count,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the SafeSEH handler table of an x86 image, which lists every exception handler that
+    /// may be called. Images for other machines don't have one.
+    pub fn safe_seh_handlers(&self) -> Result<Vec<SafeSehHandler>> {
+        let (table, count) = match self.load_config()? {
+            Some(config) if !self.is_pe32_plus() && config.se_handler_table != 0 => {
+                (config.se_handler_table, config.se_handler_count)
+            }
+            _ => return Ok(Vec::new()),
+        };
+        let source = self.read_at_rva(self.va_to_rva(table)?, table_size(count, 4)?)?;
+        let segment = source.all()?;
+        (0..count)
+            .map(|_| {
+                let rva = segment.next_u32()?;
+                Ok(SafeSehHandler {
+                    rva,
+                    section: self
+                        .get_containing_section(rva)
+                        .map(|section| section.header.name.clone()),
+                })
+            })
+            .collect()
+    }
+
+    /// If this is an x86 image that was built without SafeSEH. That is, it doesn't have a handler
+    /// table and doesn't declare that it never uses structured exception handling, so any
+    /// handler address on the stack will be accepted.
+    pub fn is_missing_safe_seh(&self) -> Result<bool> {
+        if self.header.coff_header.machine != Machine::I386
+            || self
+                .header
+                .optional_header
+                .windows_specific
+                .dll_characteristics
+                .contains(DllCharacteristics::NoSeh)
+        {
+            return Ok(false);
+        }
+        Ok(match self.load_config()? {
+            Some(config) => config.se_handler_table == 0,
+            None => true,
+        })
+    }
+
+    /// Parses the dynamic value relocation table (DVRT), if the load config points to one.
+    pub fn dynamic_relocations(&self) -> Result<Option<DynamicRelocationTable>> {
+        let config = match self.load_config()? {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let rva = if config.dynamic_value_reloc_table_section != 0 {
+            let index = config.dynamic_value_reloc_table_section as usize - 1;
+            match self.sections.get(index) {
+                Some(section) => section
+                    .header
+                    .virtual_address
+                    .checked_add(config.dynamic_value_reloc_table_offset)
+                    .ok_or(Error::InvalidRva {
+                        rva: config.dynamic_value_reloc_table_offset,
+                    })?,
+                None => {
+                    return Err(Error::Other {
+                        message: format!(
+                            "The dynamic value relocation table is in section {}, which doesn't \
+                            exist.",
+                            config.dynamic_value_reloc_table_section
+                        ),
+                    })
+                }
+            }
+        } else if config.dynamic_value_reloc_table != 0 {
+            self.va_to_rva(config.dynamic_value_reloc_table)?
+        } else {
+            return Ok(None);
+        };
+        let size_rva = rva.checked_add(4).ok_or(Error::InvalidRva { rva })?;
+        let size = self.read_u32_at_rva(size_rva)?;
+        // The size doesn't include the version and size fields themselves.
+        let source = self.read_at_rva(rva, table_size(size as u64 + 8, 1)?)?;
+        let segment = source.all()?;
+        Ok(Some(DynamicRelocationTable::try_from((
+            self.is_pe32_plus(),
+            &segment,
+        ))?))
+    }
+
+    /// Parses the hybrid metadata of an ARM64EC, ARM64X or CHPE x86 image.
+    pub fn hybrid_metadata(&self) -> Result<Option<HybridMetadata>> {
+        let va = match self.load_config()? {
+            Some(config) if config.chpe_metadata_pointer != 0 => config.chpe_metadata_pointer,
+            _ => return Ok(None),
+        };
+        let rva = self.va_to_rva(va)?;
+        let is_x86 = matches!(
+            self.header.coff_header.machine,
+            Machine::I386 | Machine::ChpeX86
+        );
+        let version = self.read_u32_at_rva(rva)?;
+        let source = self.read_at_rva(rva, HybridMetadata::size_for_version(is_x86, version))?;
+        let segment = source.all()?;
+        Ok(Some(if is_x86 {
+            HybridMetadata::X86(ChpeX86Metadata::try_from(&segment)?)
+        } else {
+            HybridMetadata::Arm64Ec(Arm64EcMetadata::try_from(&segment)?)
+        }))
+    }
+
+    /// Gets the code range map of a hybrid image, which says what architecture each range of code
+    /// is for.
+    pub fn code_ranges(&self) -> Result<Vec<CodeRange>> {
+        let metadata = match self.hybrid_metadata()? {
+            Some(metadata) => metadata,
+            None => return Ok(Vec::new()),
+        };
+        let is_x86 = matches!(metadata, HybridMetadata::X86(_));
+        let (rva, count) = metadata.code_map();
+        if rva == 0 {
+            return Ok(Vec::new());
+        }
+        let source = self.read_at_rva(rva, table_size(count as u64, 8)?)?;
+        let segment = source.all()?;
+        (0..count)
+            .map(|_| CodeRange::parse(is_x86, &segment))
+            .collect()
+    }
+
+    /// Gets the table mapping ranges of ARM64EC code to their entry point thunks.
+    pub fn code_range_entry_points(&self) -> Result<Vec<CodeRangeEntryPoint>> {
+        match self.hybrid_metadata()? {
+            Some(HybridMetadata::Arm64Ec(metadata))
+                if metadata.code_ranges_to_entry_points != 0 =>
+            {
+                let count = metadata.code_ranges_to_entry_points_count;
+                let source = self.read_at_rva(
+                    metadata.code_ranges_to_entry_points,
+                    table_size(count as u64, 12)?,
+                )?;
+                let segment = source.all()?;
+                (0..count)
+                    .map(|_| CodeRangeEntryPoint::try_from(&segment))
+                    .collect()
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Gets the redirection metadata of an ARM64EC image.
+    pub fn redirection_metadata(&self) -> Result<Vec<RedirectionEntry>> {
+        match self.hybrid_metadata()? {
+            Some(HybridMetadata::Arm64Ec(metadata)) if metadata.redirection_metadata != 0 => {
+                let count = metadata.redirection_metadata_count;
+                let source =
+                    self.read_at_rva(metadata.redirection_metadata, table_size(count as u64, 8)?)?;
+                let segment = source.all()?;
+                (0..count)
+                    .map(|_| RedirectionEntry::try_from(&segment))
+                    .collect()
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the auxiliary IAT of an ARM64EC image. It has the same number of entries as the
+    /// regular IAT.
+    pub fn auxiliary_iat(&self) -> Result<Vec<Va>> {
+        let rva = match self.hybrid_metadata()? {
+            Some(HybridMetadata::Arm64Ec(metadata)) if metadata.auxiliary_iat != 0 => {
+                metadata.auxiliary_iat
+            }
+            _ => return Ok(Vec::new()),
+        };
+        let size = match self.data_directory(DataDirectoryType::Iat) {
+            Some(dir) => dir.size as usize,
+            None => return Ok(Vec::new()),
+        };
+        let pointer_size = if self.is_pe32_plus() { 8 } else { 4 };
+        let source = self.read_at_rva(rva, size)?;
+        let segment = source.all()?;
+        (0..size / pointer_size)
+            .map(|_| next_different_sizes::<u32, u64>(!self.is_pe32_plus(), &segment))
+            .collect()
+    }
+
+    /// Maps the image and applies its ARM64X dynamic relocations, which gives the alternate view
+    /// of an ARM64X image (the ARM64EC view of a native ARM64 image). Returns `None` if the image
+    /// doesn't have any ARM64X relocations.
+    pub fn arm64x_alternate_image(&self) -> Result<Option<Vec<u8>>> {
+        let table = match self.dynamic_relocations()? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let mut pages = table
+            .relocations
+            .iter()
+            .filter_map(|relocation| match &relocation.fixups {
+                DynamicFixups::Arm64X(pages) => Some(pages),
+                _ => None,
+            })
+            .flatten()
+            .peekable();
+        if pages.peek().is_none() {
+            return Ok(None);
+        }
+        let mut image = self.map_image()?;
+        for page in pages {
+            for fixup in &page.fixups {
+                fixup.apply(&mut image, page.page_rva)?;
+            }
+        }
+        Ok(Some(image))
+    }
+
+    /// Parses the headers of the alternate view of an ARM64X image. See
+    /// [`PeFile::arm64x_alternate_image`].
+    pub fn arm64x_alternate_header(&self) -> Result<Option<PeHeader>> {
+        Ok(self.arm64x_alternate_view()?.map(|view| view.header))
+    }
+
+    /// Gets the alternate view of an ARM64X image, with its headers parsed, so that its entry
+    /// point and exports can be read. See [`PeFile::arm64x_alternate_image`].
+    pub fn arm64x_alternate_view(&self) -> Result<Option<Arm64XView>> {
+        match self.arm64x_alternate_image()? {
+            Some(image) => {
+                let image = BytesSource::from_bytes(Bytes::from(image), self.data.endidness())?;
+                let header = PeHeader::try_from(&image.all()?)?;
+                Ok(Some(Arm64XView { header, image }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parses every entry in the debug data directory.
+    pub fn debug_directories(&self) -> Result<Vec<DebugDirectory>> {
+        match self.data_directory(DataDirectoryType::Debug) {
+            Some(dir) => {
+                let source = self.read_at_rva(dir.rva, dir.size as usize)?;
+                let segment = source.all()?;
+                (0..dir.size as usize / DebugDirectory::SIZE)
+                    .map(|_| DebugDirectory::try_from((self.is_pe32_plus(), &segment)))
+                    .collect()
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the data a debug directory entry points to. This uses the file pointer rather than the
+    /// RVA, since the data isn't always mapped (or even inside of a section).
+    pub fn debug_data(&self, entry: &DebugDirectory) -> Result<BytesSource> {
+        let data = if entry.size_of_data == 0 {
+            // Entries such as ILTCG don't have any data, and both of their pointers are 0.
+            Bytes::new()
+        } else if entry.pointer_to_raw_data != 0 {
+            let segment = self.read_at_offset(
+                entry.pointer_to_raw_data as u64,
+                entry.size_of_data as usize,
+            )?;
+            Bytes::copy_from_slice(segment.as_ref())
+        } else {
+            return self.read_at_rva(entry.address_of_raw_data, entry.size_of_data as usize);
+        };
+        Ok(BytesSource::from_bytes(data, self.data.endidness())?)
+    }
+
+    /// Parses every entry in the debug data directory along with its decoded data. Each entry's
+    /// data is decoded separately, so one malformed entry doesn't hide the others.
+    pub fn debug_entries(&self) -> Result<Vec<(DebugDirectory, Result<DebugData>)>> {
+        Ok(self
+            .debug_directories()?
+            .into_iter()
+            .map(|entry| {
+                let data = self
+                    .debug_data(&entry)
+                    .and_then(|source| DebugData::parse(&entry, &source.all()?));
+                (entry, data)
+            })
+            .collect())
+    }
+
+    /// Extracts and decompresses the portable PDB embedded in the image, if there is one.
+    #[cfg(feature = "portable-pdb")]
+    pub fn embedded_portable_pdb(&self) -> Result<Option<Vec<u8>>> {
+        for entry in self.debug_directories()? {
+            if let Ok(DebugType::EmbeddedPortablePdb) = entry.debug_type() {
+                let pdb = EmbeddedPortablePdb::try_from(&self.debug_data(&entry)?.all()?)?;
+                return Ok(Some(pdb.decompress()?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decodes the first CodeView debug directory entry, if there is one.
+    pub fn codeview(&self) -> Result<Option<CodeView>> {
+        for entry in self.debug_directories()? {
+            if let Ok(DebugType::Codeview) = entry.debug_type() {
+                return Ok(Some(CodeView::try_from(&self.debug_data(&entry)?.all()?)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the name, GUID and age of the PDB that goes with this image.
+    pub fn pdb_info(&self) -> Result<Option<PdbInfo>> {
+        Ok(self.codeview()?.as_ref().and_then(PdbInfo::from_codeview))
+    }
+
+    /// The key the image is stored under on a symbol server: the COFF header's timestamp followed
+    /// by the size of the image.
+    pub fn symbol_server_key(&self) -> String {
+        format!(
+            "{:08X}{:x}",
+            self.header.coff_header.time_date_stamp,
+            self.header.optional_header.windows_specific.size_of_image
+        )
+    }
+
+    /// The path of the image relative to the root of a symbol store, given the image's file name.
+    pub fn symbol_server_path(&self, name: &str) -> String {
+        symbol_store_path(name, &self.symbol_server_key())
+    }
+
+    /// Parses the TLS directory, if the image has one.
+    pub fn tls_directory(&self) -> Result<Option<TlsDirectory>> {
+        if let Some(dir) = self.data_directory(DataDirectoryType::TlsTable) {
+            let source = self.read_at_rva(dir.rva, dir.size as usize)?;
+            let segment = source.all()?;
+            Ok(Some(TlsDirectory::try_from((
+                self.is_pe32_plus(),
+                &segment,
+            ))?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Walks the null-terminated array of TLS callbacks. These are run before the entry point.
+    pub fn tls_callbacks(&self) -> Result<Vec<TlsCallback>> {
+        let mut callbacks = Vec::new();
+        let array_va = match self.tls_directory()? {
+            Some(tls) if tls.address_of_callbacks != 0 => tls.address_of_callbacks,
+            _ => return Ok(callbacks),
+        };
+        let pointer_size = if self.is_pe32_plus() { 8 } else { 4 };
+        let mut rva = self.va_to_rva(array_va)?;
+        // The array is only supposed to end at a null pointer, so a corrupt one is cut off at the
+        // end of the section or after a number of callbacks that no real image comes
+        // close to.
+        while callbacks.len() < MAX_TLS_CALLBACKS {
+            let source = match self.read_at_rva(rva, pointer_size) {
+                Ok(source) => source,
+                Err(_) => break,
+            };
+            let va = next_different_sizes::<u32, u64>(!self.is_pe32_plus(), &source.all()?)?;
+            if va == 0 {
+                break;
+            }
+            let callback_rva = self.va_to_rva(va).ok();
+            callbacks.push(TlsCallback {
+                va,
+                rva: callback_rva,
+                section: callback_rva
+                    .and_then(|rva| self.get_containing_section(rva))
+                    .map(|section| section.header.name.clone()),
+            });
+            rva = match rva.checked_add(pointer_size as Rva) {
+                Some(rva) => rva,
+                None => break,
+            };
+        }
+        Ok(callbacks)
+    }
+
+    /// Gets the TLS template data (everything from `raw_data_start_va` to `raw_data_end_va`),
+    /// which is copied for every new thread. Any part of it that's in a section's zero-filled
+    /// area is zeros, just like it is once the image is loaded.
+    pub fn tls_template(&self) -> Result<Option<Vec<u8>>> {
+        let tls = match self.tls_directory()? {
+            Some(tls) if tls.raw_data_end_va > tls.raw_data_start_va => tls,
+            _ => return Ok(None),
+        };
+        let size = (tls.raw_data_end_va - tls.raw_data_start_va) as usize;
+        Ok(Some(self.read_mapped_at_rva(
+            self.va_to_rva(tls.raw_data_start_va)?,
+            size,
+        )?))
+    }
+
+    /// Converts a VA to an RVA, failing if the VA doesn't point inside of the image.
+    pub fn va_to_rva(&self, va: Va) -> Result<Rva> {
+        let windows_specific = &self.header.optional_header.windows_specific;
+        match va.checked_sub(windows_specific.image_base) {
+            Some(rva) if rva < windows_specific.size_of_image as u64 => Ok(rva as Rva),
+            _ => Err(Error::InvalidVa { va }),
+        }
+    }
+
+    pub fn rva_to_offset(&self, rva: Rva) -> Result<u64> {
+        self.sections
+            .iter()
+            .find_map(|section| section.rva_to_offset(rva))
+            .ok_or(Error::InvalidRva { rva })
+    }
+
     pub fn get_containing_section(&self, rva: Rva) -> Option<&Section> {
         for section in &self.sections {
             if section.contains_rva(rva) {
@@ -128,3 +1101,495 @@ impl PeFile {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    fn put_u16(data: &mut [u8], offset: usize, value: u16) {
+        data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    const OPTIONAL_HEADER: usize = 0x98;
+
+    fn put_data_directory(data: &mut [u8], entry: DataDirectoryType, rva: u32, size: u32) {
+        let offset = OPTIONAL_HEADER + 112 + entry as usize * 8;
+        put_u32(data, offset, rva);
+        put_u32(data, offset + 4, size);
+    }
+
+    /// A PE32+ image with a single .text section at file offset and RVA 0x200.
+    fn tiny_image() -> Vec<u8> {
+        let mut data = vec![0; 0x400];
+        data[..2].copy_from_slice(b"MZ");
+        put_u32(&mut data, 0x3c, 0x80);
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+
+        // The COFF file header.
+        put_u16(&mut data, 0x84, 0x8664);
+        put_u16(&mut data, 0x86, 1);
+        put_u16(&mut data, 0x94, 0xf0);
+        put_u16(&mut data, 0x96, 0x0022);
+
+        // The optional header.
+        let optional = OPTIONAL_HEADER;
+        put_u16(&mut data, optional, 0x20b);
+        data[optional + 2] = 14;
+        put_u32(&mut data, optional + 4, 0x200);
+        put_u32(&mut data, optional + 16, 0x200);
+        put_u32(&mut data, optional + 20, 0x200);
+        put_u64(&mut data, optional + 24, 0x1_4000_0000);
+        put_u32(&mut data, optional + 32, 0x200);
+        put_u32(&mut data, optional + 36, 0x200);
+        put_u16(&mut data, optional + 40, 6);
+        put_u16(&mut data, optional + 48, 6);
+        put_u32(&mut data, optional + 56, 0x400);
+        put_u32(&mut data, optional + 60, 0x200);
+        put_u16(&mut data, optional + 68, 3);
+        put_u16(&mut data, optional + 70, 0x8160);
+        put_u64(&mut data, optional + 72, 0x10_0000);
+        put_u64(&mut data, optional + 80, 0x1000);
+        put_u64(&mut data, optional + 88, 0x10_0000);
+        put_u64(&mut data, optional + 96, 0x1000);
+        put_u32(&mut data, optional + 108, 16);
+
+        // The section header.
+        data[0x188..0x18d].copy_from_slice(b".text");
+        put_u32(&mut data, 0x190, 0x10);
+        put_u32(&mut data, 0x194, 0x200);
+        put_u32(&mut data, 0x198, 0x200);
+        put_u32(&mut data, 0x19c, 0x200);
+        put_u32(&mut data, 0x1ac, 0x6000_0020);
+
+        for (index, byte) in data[0x200..0x400].iter_mut().enumerate() {
+            *byte = (index * 7 % 251) as u8;
+        }
+        data
+    }
+
+    /// Writes the image to a temporary file and parses it.
+    fn try_parse(name: &str, data: &[u8]) -> Result<PeFile> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("peparse-{}-{}.exe", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        let image = PeFile::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        image
+    }
+
+    fn parse(name: &str, data: &[u8]) -> PeFile {
+        try_parse(name, data).unwrap()
+    }
+
+    #[test]
+    fn rebase_applies_relocations_inside_of_sections() {
+        let mut data = tiny_image();
+        put_u64(&mut data, 0x208, 0x1_4000_0210);
+        let relocations = [
+            0x00, 0x02, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, // Page 0x200
+            0x08, 0xa2, 0xfc, 0xa3, 0x00, 0x50, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, // Page 0x0
+            0x10, 0xa0, 0x00, 0x00,
+        ];
+        data[0x300..0x300 + relocations.len()].copy_from_slice(&relocations);
+        put_data_directory(
+            &mut data,
+            DataDirectoryType::BaseRelocationTable,
+            0x300,
+            relocations.len() as u32,
+        );
+        let image = parse("rebase", &data);
+        let rebased = image.rebase(0x1_5000_0000).unwrap();
+        assert_eq!(rebased.image[0x208..0x210], 0x1_5000_0210u64.to_le_bytes());
+        assert_eq!(
+            rebased.image[OPTIONAL_HEADER + 24..OPTIONAL_HEADER + 32],
+            0x1_5000_0000u64.to_le_bytes()
+        );
+        // One runs past the end of the section, and the other points into the headers.
+        assert_eq!(rebased.out_of_bounds, vec![0x3fc, 0x10]);
+        assert_eq!(rebased.image[0x10..0x18], data[0x10..0x18]);
+        assert_eq!(
+            rebased.unsupported,
+            vec![(0x200, BaseRelocationType::Reserved(5))]
+        );
+    }
+
+    #[test]
+    fn map_image_zero_fills_sections_without_raw_data() {
+        let mut data = tiny_image();
+        put_u32(&mut data, 0x198, 0);
+        let image = parse("map", &data);
+        let mapped = image.map_image().unwrap();
+        assert_eq!(mapped.len(), 0x400);
+        assert_eq!(mapped[..0x200], data[..0x200]);
+        assert!(mapped[0x200..].iter().all(|&byte| byte == 0));
+        assert_eq!(
+            image
+                .read_at_rva(0x208, 0x10)
+                .unwrap()
+                .all()
+                .unwrap()
+                .as_ref(),
+            &[0; 0x10]
+        );
+        assert!(image.read_at_rva(0x3f8, 0x10).is_err());
+    }
+
+    #[test]
+    fn map_image_copies_the_file_backed_part_of_sections() {
+        let data = tiny_image();
+        let image = parse("map-raw", &data);
+        assert_eq!(image.map_image().unwrap(), data);
+        assert_eq!(
+            image.read_mapped_at_rva(0x3f0, 0x10).unwrap(),
+            data[0x3f0..0x400]
+        );
+    }
+
+    #[test]
+    fn section_sizes_that_overflow_are_invalid() {
+        let mut data = tiny_image();
+        put_u32(&mut data, 0x190, 0xffff_ff01);
+        assert!(matches!(
+            try_parse("overflow", &data),
+            Err(Error::InvalidSectionHeader { .. })
+        ));
+    }
+
+    /// Puts a PE32+ TLS directory at RVA 0x200 whose callback array starts at `callbacks`.
+    fn put_tls_directory(data: &mut [u8], callbacks: Rva) {
+        data[0x200..0x228].copy_from_slice(&[0; 0x28]);
+        put_u64(data, 0x218, 0x1_4000_0000 + callbacks as u64);
+        put_data_directory(data, DataDirectoryType::TlsTable, 0x200, 0x28);
+    }
+
+    #[test]
+    fn tls_callbacks_stop_at_the_end_of_the_section() {
+        let mut data = tiny_image();
+        put_tls_directory(&mut data, 0x3f0);
+        put_u64(&mut data, 0x3f0, 0x1_4000_0210);
+        put_u64(&mut data, 0x3f8, 0x7fff_0000_0000);
+        let callbacks = parse("tls", &data).tls_callbacks().unwrap();
+        assert_eq!(callbacks.len(), 2);
+        assert_eq!(callbacks[0].rva, Some(0x210));
+        assert!(callbacks[1].is_outside_image());
+    }
+
+    #[test]
+    fn tls_callbacks_are_bounded() {
+        let mut data = tiny_image();
+        data.resize(0x9200, 0);
+        put_u32(&mut data, OPTIONAL_HEADER + 56, 0x9200);
+        put_u32(&mut data, 0x190, 0x9000);
+        put_u32(&mut data, 0x198, 0x9000);
+        for offset in (0x228..0x9200).step_by(8) {
+            put_u64(&mut data, offset, 0x1_4000_0200);
+        }
+        put_tls_directory(&mut data, 0x228);
+        let callbacks = parse("tls-bound", &data).tls_callbacks().unwrap();
+        assert_eq!(callbacks.len(), MAX_TLS_CALLBACKS);
+    }
+
+    #[test]
+    fn guard_cf_functions_use_the_stride_from_the_load_config() {
+        let mut data = tiny_image();
+        data[0x200..0x294].copy_from_slice(&[0; 0x94]);
+        put_u32(&mut data, 0x200, 0x94);
+        put_u64(&mut data, 0x280, 0x1_4000_0300);
+        put_u64(&mut data, 0x288, 2);
+        put_u32(&mut data, 0x290, 0x1000_0500);
+        data[0x300..0x30a].copy_from_slice(&[0x10, 0x02, 0, 0, 0x01, 0x20, 0x02, 0, 0, 0x00]);
+        // Older linkers always put 0x40 in the data directory, whatever the structure's size.
+        put_data_directory(&mut data, DataDirectoryType::LoadConfigTable, 0x200, 0x40);
+        let image = parse("guard", &data);
+        let functions = image.guard_cf_functions().unwrap();
+        assert_eq!(
+            functions
+                .iter()
+                .map(|entry| (entry.rva, entry.flags.bits()))
+                .collect::<Vec<_>>(),
+            vec![(0x210, 0x01), (0x220, 0x00)]
+        );
+    }
+
+    #[test]
+    fn image_symbol_server_key() {
+        let mut data = tiny_image();
+        put_u32(&mut data, 0x88, 0x5f3e_2a01);
+        let image = parse("symbols", &data);
+        assert_eq!(image.symbol_server_key(), "5F3E2A01400");
+        assert_eq!(
+            image.symbol_server_path("app.exe"),
+            "app.exe/5F3E2A01400/app.exe"
+        );
+    }
+
+    #[test]
+    fn debug_entries_are_decoded_separately() {
+        let mut data = tiny_image();
+        data[0x300..0x338].copy_from_slice(&[0; 0x38]);
+        // A CodeView entry with a bad signature, followed by an ILTCG entry without any data.
+        put_u32(&mut data, 0x30c, 2);
+        put_u32(&mut data, 0x310, 8);
+        put_u32(&mut data, 0x314, 0x380);
+        put_u32(&mut data, 0x318, 0x380);
+        data[0x380..0x388].copy_from_slice(b"XXXX\0\0\0\0");
+        put_u32(&mut data, 0x328, 14);
+        put_data_directory(&mut data, DataDirectoryType::Debug, 0x300, 0x38);
+        let entries = parse("debug", &data).debug_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            entries[0].1,
+            Err(Error::InvalidHeaderMagic { .. })
+        ));
+        assert!(matches!(entries[1].1, Ok(DebugData::Iltcg)));
+    }
+
+    /// Appends a WIN_CERTIFICATE entry holding `contents` to the image, after eight bytes of
+    /// overlay, and points the certificate table at it.
+    fn append_certificate(data: &mut Vec<u8>, contents: &[u8]) {
+        data.extend_from_slice(b"OVERLAY!");
+        let offset = data.len();
+        data.extend_from_slice(&(8 + contents.len() as u32).to_le_bytes());
+        data.extend_from_slice(&0x0200u16.to_le_bytes());
+        data.extend_from_slice(&(WinCertType::PkcsSignedData as u16).to_le_bytes());
+        data.extend_from_slice(contents);
+        put_data_directory(
+            data,
+            DataDirectoryType::CertificateTable,
+            offset as u32,
+            8 + contents.len() as u32,
+        );
+    }
+
+    #[test]
+    fn attribute_certificates_are_read_from_the_file() {
+        let mut data = tiny_image();
+        append_certificate(&mut data, &[0; 8]);
+        let image = parse("certificates", &data);
+        let certificates = image.attribute_certificates().unwrap();
+        assert_eq!(certificates.entries.len(), 1);
+        assert_eq!(certificates.entries[0].offset, 0x408);
+        assert_eq!(certificates.entries[0].certificate, vec![0; 8]);
+        assert_eq!(certificates.invalid_entry, None);
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn authenticode_digest_known_answer() {
+        let mut data = tiny_image();
+        put_u32(&mut data, OPTIONAL_HEADER + 64, 0x1234_5678);
+        append_certificate(&mut data, &[0, 1, 2, 3, 4, 5, 6, 7]);
+        let image = parse("digest", &data);
+        // These were computed from the same bytes with goblin 0.10.7's
+        // `PE::authenticode_ranges`, hashed with the sha1 and sha2 crates.
+        assert_eq!(
+            hex::encode(image.authenticode_digest(HashAlgorithm::Sha1).unwrap()),
+            "7a893ea2bd7c725595436a2a99db61b1495a6f14"
+        );
+        assert_eq!(
+            hex::encode(image.authenticode_digest(HashAlgorithm::Sha256).unwrap()),
+            "bc3f1515524e111a36a8f89331ca44d19e084f603566774b9b1af0e7e1b65501"
+        );
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn authenticode_digest_ignores_checksum_and_certificates() {
+        let mut first = tiny_image();
+        append_certificate(&mut first, &[0; 8]);
+        let mut second = tiny_image();
+        put_u32(&mut second, OPTIONAL_HEADER + 64, 0xffff_ffff);
+        append_certificate(&mut second, &[0xff; 8]);
+        let first = parse("digest-first", &first);
+        let second = parse("digest-second", &second);
+        assert_eq!(
+            first.authenticode_digest(HashAlgorithm::Sha256).unwrap(),
+            second.authenticode_digest(HashAlgorithm::Sha256).unwrap()
+        );
+        // The certificate's contents aren't a signature.
+        assert!(first.signatures().is_err());
+    }
+
+    /// A tiny image signed with the signature from authenticode/testdata, which is generated by
+    /// authenticode/testdata/generate.py. `modify` is called on the image before it's parsed.
+    #[cfg(feature = "authenticode")]
+    fn signed_image(name: &str, modify: impl FnOnce(&mut Vec<u8>)) -> PeFile {
+        let mut data = tiny_image();
+        modify(&mut data);
+        append_certificate(
+            &mut data,
+            include_bytes!("authenticode/testdata/signed.der"),
+        );
+        parse(name, &data)
+    }
+
+    #[cfg(feature = "authenticode")]
+    fn test_trust_store() -> crate::authenticode::verify::TrustStore {
+        use crate::authenticode::{verify::TrustStore, x509::Certificate};
+        let mut store = TrustStore::new();
+        store.add(Certificate::from_der(include_bytes!("authenticode/testdata/root.der")).unwrap());
+        store
+    }
+
+    /// 2021-06-01, when the signer's certificate was valid.
+    #[cfg(feature = "authenticode")]
+    const SIGNING_TIME: i64 = 1_622_505_600;
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn signature_verifies() {
+        let image = signed_image("signed", |_| {});
+        let report = crate::authenticode::verify::verify_signature_at(
+            &image,
+            &test_trust_store(),
+            SIGNING_TIME,
+        );
+        assert!(report.is_valid(), "{:?}", report);
+        assert_eq!(report.signatures.len(), 1);
+        let signature = &report.signatures[0];
+        assert_eq!(signature.digest_algorithm, Some(HashAlgorithm::Sha256));
+        assert_eq!(signature.verification_time, SIGNING_TIME);
+        assert_eq!(signature.chain.len(), 3);
+        assert_eq!(
+            signature.signer().unwrap().subject.common_name(),
+            Some("peparse Test Publisher")
+        );
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn signature_of_a_modified_image() {
+        use crate::authenticode::verify::{verify_signature_at, VerificationFailure};
+        let image = signed_image("modified", |data| data[0x200] ^= 1);
+        let report = verify_signature_at(&image, &test_trust_store(), SIGNING_TIME);
+        assert!(!report.is_valid());
+        let failures = &report.signatures[0].failures;
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            failures[0],
+            VerificationFailure::ImageDigestMismatch { .. }
+        ));
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn signature_checked_after_the_signer_expired() {
+        use crate::authenticode::verify::{verify_signature_at, VerificationFailure};
+        let image = signed_image("expired", |_| {});
+        // 2023-01-01.
+        let report = verify_signature_at(&image, &test_trust_store(), 1_672_531_200);
+        let failures = &report.signatures[0].failures;
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            &failures[0],
+            VerificationFailure::CertificateNotValidAtTime { subject, .. }
+                if subject == "CN=peparse Test Publisher"
+        ));
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn signature_without_a_trusted_root() {
+        use crate::authenticode::verify::{verify_signature_at, TrustStore, VerificationFailure};
+        let image = signed_image("untrusted", |_| {});
+        let report = verify_signature_at(&image, &TrustStore::new(), SIGNING_TIME);
+        assert_eq!(
+            report.signatures[0].failures,
+            vec![VerificationFailure::IssuerNotFound {
+                subject: "CN=peparse Test Code Signing CA".into(),
+                issuer: "CN=peparse Test Root".into(),
+            }]
+        );
+    }
+
+    /// A tiny image signed with one of the timestamped signatures from authenticode/testdata.
+    #[cfg(feature = "authenticode")]
+    fn timestamped_image(name: &str, signature: &[u8]) -> PeFile {
+        let mut data = tiny_image();
+        append_certificate(&mut data, signature);
+        parse(name, &data)
+    }
+
+    /// 2021-07-01, when the test signatures were timestamped. The signer claims to have signed a
+    /// month earlier, but nothing vouches for that.
+    #[cfg(feature = "authenticode")]
+    const TIMESTAMP_TIME: i64 = 1_625_097_600;
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn verification_time_comes_from_a_valid_timestamp() {
+        use crate::authenticode::{timestamp::TimestampKind, verify::verify_signature};
+        for (name, signature, kind) in [
+            (
+                "countersigned",
+                &include_bytes!("authenticode/testdata/countersigned.der")[..],
+                TimestampKind::Countersignature,
+            ),
+            (
+                "rfc3161",
+                &include_bytes!("authenticode/testdata/rfc3161.der")[..],
+                TimestampKind::Rfc3161,
+            ),
+        ] {
+            let image = timestamped_image(name, signature);
+            // The signer's certificate expired long ago, so this only verifies at the time of
+            // the timestamp.
+            let report = verify_signature(&image, &test_trust_store());
+            assert!(report.is_valid(), "{:?}", report);
+            let signature = &report.signatures[0];
+            assert_eq!(signature.verification_time, TIMESTAMP_TIME);
+            assert_eq!(signature.timestamps.len(), 1);
+            assert!(signature.timestamps[0].is_valid());
+            assert_eq!(
+                signature.timestamps[0]
+                    .timestamp
+                    .as_ref()
+                    .map(|timestamp| timestamp.kind),
+                Some(kind)
+            );
+        }
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn invalid_timestamps_are_not_used_for_the_verification_time() {
+        use crate::authenticode::verify::{verify_signature, VerificationFailure};
+        let image = timestamped_image(
+            "timestamp-without-eku",
+            include_bytes!("authenticode/testdata/countersigned_without_eku.der"),
+        );
+        let report = verify_signature(&image, &test_trust_store());
+        let signature = &report.signatures[0];
+        assert!(!signature.timestamps[0].is_valid());
+        assert_ne!(signature.verification_time, TIMESTAMP_TIME);
+        assert_ne!(signature.verification_time, SIGNING_TIME);
+        assert!(matches!(
+            signature.failures[..],
+            [VerificationFailure::CertificateNotValidAtTime { .. }]
+        ));
+    }
+
+    #[cfg(feature = "authenticode")]
+    #[test]
+    fn supplied_time_overrides_the_timestamp() {
+        use crate::authenticode::verify::verify_signature_at;
+        let image = timestamped_image(
+            "timestamp-overridden",
+            include_bytes!("authenticode/testdata/countersigned.der"),
+        );
+        let report = verify_signature_at(&image, &test_trust_store(), SIGNING_TIME);
+        assert!(report.is_valid(), "{:?}", report);
+        assert_eq!(report.signatures[0].verification_time, SIGNING_TIME);
+        assert!(report.signatures[0].timestamps[0].is_valid());
+    }
+}
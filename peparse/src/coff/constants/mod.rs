@@ -13,10 +13,15 @@ constants_enum! {
         (Amd64, 0x8664, "x64"),
         (Arm, 0x1c0, "ARM little endian"),
         (Arm64, 0xaa64, "ARM64 little endian"),
+        (Arm64Ec, 0xa641, "ARM64 little endian with x64 interoperability (ARM64EC)"),
+        (Arm64X, 0xa64e, "ARM64 little endian image that also contains ARM64EC code (ARM64X)"),
         (ArmNt, 0x1c4, "ARM Thumb-2 little endian"),
+        (ChpeX86, 0x3a64, "x86 image with compiled hybrid ARM64 code (CHPE)"),
         (Ebc, 0xebc, "EFI byte code"),
         (I386, 0x14c, "Intel 386 or later processors and compatible processors"),
         (Ia64, 0x200, "Intel Itanium processor family"),
+        (LoongArch32, 0x6232, "LoongArch 32-bit processor family"),
+        (LoongArch64, 0x6264, "LoongArch 64-bit processor family"),
         (M32r, 0x9041, "Mitsubishi M32R little endian"),
         (Mips16, 0x266, "MIPS16"),
         (MipsFpu, 0x366, "MIPS with FPU"),
@@ -36,6 +41,31 @@ constants_enum! {
     ]
 }
 
+impl Machine {
+    /// If this is one of the MIPS machine types.
+    pub fn is_mips(&self) -> bool {
+        matches!(
+            self,
+            Self::R4000 | Self::Mips16 | Self::MipsFpu | Self::MipsFpu16 | Self::WceMipsV2
+        )
+    }
+
+    /// If this is one of the 32-bit ARM machine types (including Thumb).
+    pub fn is_arm32(&self) -> bool {
+        matches!(self, Self::Arm | Self::ArmNt | Self::Thumb)
+    }
+
+    /// If this is one of the RISC-V machine types.
+    pub fn is_riscv(&self) -> bool {
+        matches!(self, Self::RiscV32 | Self::RiscV64 | Self::RiscV128)
+    }
+
+    /// If this is one of the LoongArch machine types.
+    pub fn is_loongarch(&self) -> bool {
+        matches!(self, Self::LoongArch32 | Self::LoongArch64)
+    }
+}
+
 flags! {
     name: Characteristics,
     doc: "",
@@ -1,4 +1,4 @@
-use crate::Rva;
+use crate::{coff::constants::Machine, Rva, Va};
 use snafu::Snafu;
 use std::{io, string::FromUtf8Error};
 
@@ -23,6 +23,31 @@ pub enum Error {
     InvalidHeaderMagic { expected: String, received: String },
     #[snafu(display("Invalid RVA: {:08x}", rva))]
     InvalidRva { rva: Rva },
+    #[snafu(display("Invalid VA: {:016x}", va))]
+    InvalidVa { va: Va },
+    #[snafu(display(
+        "Invalid size {} for the base relocation block of page {:08x}",
+        block_size,
+        page_rva
+    ))]
+    InvalidRelocationBlock { page_rva: Rva, block_size: u32 },
+    #[snafu(display("Base relocation at RVA {:08x} is outside of the mapped sections", rva))]
+    RelocationOutOfBounds { rva: Rva },
+    #[snafu(display(
+        "A table of {} entries of {} bytes each is too large",
+        count,
+        entry_size
+    ))]
+    TableTooLarge { count: u64, entry_size: usize },
+    #[snafu(display(
+        "The header of section {} has sizes or alignments that don't fit",
+        name
+    ))]
+    InvalidSectionHeader { name: String },
+    #[snafu(display("Invalid DER data: {}", message))]
+    InvalidDer { message: String },
+    #[snafu(display("Unsupported machine type {} for {}", machine, context))]
+    UnsupportedMachine { machine: Machine, context: String },
     #[snafu(display("{}", error))]
     SegSourceError { error: segsource::Error },
     #[snafu(display("{}", message))]
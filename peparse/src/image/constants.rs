@@ -60,3 +60,27 @@ constants_enum! {
         (TsStackSigned, 0x0004, "Terminal Server Protocol Stack Certificate signing"),
     ]
 }
+
+constants_enum! {
+    name: DataDirectoryType,
+    doc: "The index of each entry in the optional header's data directory.",
+    value_type: u32,
+    items: [
+        (ExportTable, 0, "The export table address and size."),
+        (ImportTable, 1, "The import table address and size."),
+        (ResourceTable, 2, "The resource table address and size."),
+        (ExceptionTable, 3, "The exception table address and size."),
+        (CertificateTable, 4, "The attribute certificate table address and size. Note that the address is a file offset, not an RVA."),
+        (BaseRelocationTable, 5, "The base relocation table address and size."),
+        (Debug, 6, "The debug data starting address and size."),
+        (Architecture, 7, "Reserved, must be 0."),
+        (GlobalPtr, 8, "The RVA of the value to be stored in the global pointer register."),
+        (TlsTable, 9, "The thread local storage (TLS) table address and size."),
+        (LoadConfigTable, 10, "The load configuration table address and size."),
+        (BoundImport, 11, "The bound import table address and size."),
+        (Iat, 12, "The import address table address and size."),
+        (DelayImportDescriptor, 13, "The delay import descriptor address and size."),
+        (ClrRuntimeHeader, 14, "The CLR runtime header address and size."),
+        (Reserved, 15, "Reserved, must be zero."),
+    ]
+}
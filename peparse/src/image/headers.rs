@@ -1,12 +1,17 @@
-use super::constants::{DllCharacteristics, WindowsSubsystem};
+use super::constants::{
+    DataDirectoryType, DllCharacteristics, WinCertRevision, WinCertType, WindowsSubsystem,
+};
+#[cfg(feature = "authenticode")]
+use crate::authenticode::pkcs7::SignedData;
 use crate::{
     coff::CoffFileHeader,
     error::{Error, Result},
     util::next_different_sizes,
     Rva, Va,
 };
+use core::convert::TryFrom;
 use custom_debug_derive::Debug;
-use segsource::TryFromSegment;
+use segsource::{DataSegment, TryFromSegment};
 
 constants_enum! {
     name: ImageType,
@@ -105,6 +110,14 @@ impl OptionalHeader {
     pub fn is_pe32_plus(&self) -> bool {
         self.image_type == ImageType::Pe32Plus
     }
+
+    /// Gets the requested data directory entry, or `None` if the image doesn't have that entry or
+    /// if the entry is empty.
+    pub fn data_directory(&self, entry: DataDirectoryType) -> Option<&DataDirectoryPointer> {
+        self.data_directory_ptrs
+            .get(entry as usize)
+            .filter(|ptr| ptr.rva != 0 && ptr.size != 0)
+    }
 }
 
 #[derive(TryFromSegment, Debug, Clone)]
@@ -264,9 +277,13 @@ pub struct DataDirectory {
     _reserved: u64,
 }
 
-#[derive(TryFromSegment, Debug, Clone)]
-#[from_seg(error(Error))]
+/// An entry in the attribute certificate table (a WIN_CERTIFICATE).
+#[derive(Debug, Clone)]
 pub struct AttributeCertificate {
+    /// The file offset of the entry.
+    #[debug(format = "0x{:x}")]
+    pub offset: u64,
+
     /// Specifies the length of the attribute certificate entry.
     pub dw_length: u32,
 
@@ -277,7 +294,123 @@ pub struct AttributeCertificate {
     pub w_certificate_type: u16,
 
     /// Contains a certificate, such as an Authenticode signature.
-    pub _todo: u8,
+    #[debug(skip)]
+    pub certificate: Vec<u8>,
+}
+
+impl AttributeCertificate {
+    /// The size of the fields that come before the certificate.
+    pub const HEADER_SIZE: usize = 8;
+
+    /// The alignment of each entry in the table.
+    pub const ALIGNMENT: u64 = 8;
+
+    /// Gets the version of the entry. Fails for versions that aren't known yet.
+    #[inline]
+    pub fn revision(&self) -> Result<WinCertRevision> {
+        WinCertRevision::try_from(self.w_revision)
+    }
+
+    /// Gets the type of the certificate. Fails for types that aren't known yet.
+    #[inline]
+    pub fn certificate_type(&self) -> Result<WinCertType> {
+        WinCertType::try_from(self.w_certificate_type)
+    }
+
+    /// Decodes the PKCS#7 SignedData in a PKCS_SIGNED_DATA entry, which is an Authenticode
+    /// signature. Fails for other types of entries.
+    #[cfg(feature = "authenticode")]
+    pub fn signed_data(&self) -> Result<SignedData> {
+        match self.certificate_type()? {
+            WinCertType::PkcsSignedData => SignedData::from_der(&self.certificate),
+            other => Err(Error::Other {
+                message: format!("Certificate entries of type {} aren't signatures.", other),
+            }),
+        }
+    }
+
+    /// Parses every entry in the attribute certificate table. `offset` is the file offset the
+    /// table starts at, which is what the data directory entry holds instead of an RVA. Each entry
+    /// has to start on an 8-byte boundary and end inside of the table. A bad entry doesn't fail
+    /// the whole table: the entries before it are kept and it's recorded as the invalid entry.
+    pub fn parse_table(
+        offset: u64,
+        segment: &DataSegment<'_>,
+    ) -> Result<AttributeCertificateTable> {
+        let table_end = offset + segment.remaining()? as u64;
+        let mut entry_offset = offset;
+        let mut certificates = Vec::new();
+        let mut invalid_entry = None;
+        while entry_offset < table_end {
+            let length = if table_end - entry_offset >= Self::HEADER_SIZE as u64 {
+                segment.next_u32()?
+            } else {
+                0
+            };
+            if entry_offset % Self::ALIGNMENT != 0
+                || (length as usize) < Self::HEADER_SIZE
+                || entry_offset + length as u64 > table_end
+            {
+                // The entry's length is the only way to find the next one, so there's no going on
+                // after a bad entry.
+                invalid_entry = Some(InvalidCertificateEntry {
+                    offset: entry_offset,
+                    length,
+                });
+                break;
+            }
+            let w_revision = segment.next_u16()?;
+            let w_certificate_type = segment.next_u16()?;
+            let certificate = segment
+                .next_n(length as usize - Self::HEADER_SIZE)?
+                .as_ref()
+                .to_vec();
+            certificates.push(Self {
+                offset: entry_offset,
+                dw_length: length,
+                w_revision,
+                w_certificate_type,
+                certificate,
+            });
+
+            // Entries are padded to the alignment, but the padding after the last entry isn't
+            // always included in the table's size.
+            let next_offset = align_up(entry_offset + length as u64, Self::ALIGNMENT);
+            let padding = (next_offset.min(table_end) - (entry_offset + length as u64)) as usize;
+            segment.next_n(padding)?;
+            entry_offset = next_offset;
+        }
+        Ok(AttributeCertificateTable {
+            entries: certificates,
+            invalid_entry,
+        })
+    }
+}
+
+/// The entries of the attribute certificate table.
+#[derive(Debug, Clone, Default)]
+pub struct AttributeCertificateTable {
+    pub entries: Vec<AttributeCertificate>,
+
+    /// The entry that parsing stopped at, if the table has one that's malformed. Every entry
+    /// before it is still in `entries`.
+    pub invalid_entry: Option<InvalidCertificateEntry>,
+}
+
+/// An attribute certificate table entry that's misaligned, too short to hold its own header, or
+/// that runs past the end of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCertificateEntry {
+    /// The file offset of the entry.
+    pub offset: u64,
+
+    /// The length the entry claims to have, or 0 if there wasn't room for it.
+    pub length: u32,
+}
+
+#[inline]
+fn align_up(value: u64, alignment: u64) -> u64 {
+    (value + alignment - 1) / alignment * alignment
 }
 
 #[derive(TryFromSegment, Debug, Clone)]
@@ -312,3 +445,79 @@ pub struct DelayLoadImport {
     /// The timestamp of the DLL to which this image has been bound.
     pub timestamp: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse_table(offset: u64, data: &[u8]) -> AttributeCertificateTable {
+        let source =
+            BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little).unwrap();
+        AttributeCertificate::parse_table(offset, &source.all().unwrap()).unwrap()
+    }
+
+    fn entry(length: u32, contents: &[u8]) -> Vec<u8> {
+        let mut entry = length.to_le_bytes().to_vec();
+        entry.extend_from_slice(&0x0200u16.to_le_bytes());
+        entry.extend_from_slice(&0x0002u16.to_le_bytes());
+        entry.extend_from_slice(contents);
+        entry
+    }
+
+    #[test]
+    fn keeps_entries_before_an_invalid_one() {
+        let mut data = entry(10, &[0xaa, 0xbb]);
+        data.extend_from_slice(&[0; 6]);
+        data.extend(entry(0x100, &[]));
+        let table = parse_table(0x400, &data);
+        assert_eq!(table.entries.len(), 1);
+        assert_eq!(table.entries[0].offset, 0x400);
+        assert_eq!(table.entries[0].certificate, vec![0xaa, 0xbb]);
+        assert_eq!(
+            table.invalid_entry,
+            Some(InvalidCertificateEntry {
+                offset: 0x410,
+                length: 0x100,
+            })
+        );
+    }
+
+    #[test]
+    fn last_entry_may_leave_out_its_padding() {
+        let table = parse_table(0x400, &entry(10, &[0xaa, 0xbb]));
+        assert_eq!(table.entries.len(), 1);
+        assert_eq!(table.invalid_entry, None);
+    }
+
+    #[test]
+    fn rejects_short_and_misaligned_entries() {
+        let table = parse_table(0x400, &entry(4, &[]));
+        assert!(table.entries.is_empty());
+        assert_eq!(
+            table.invalid_entry,
+            Some(InvalidCertificateEntry {
+                offset: 0x400,
+                length: 4,
+            })
+        );
+
+        let table = parse_table(0x404, &entry(8, &[]));
+        assert!(table.entries.is_empty());
+        assert_eq!(table.invalid_entry.map(|entry| entry.offset), Some(0x404));
+
+        // A header that's cut off.
+        let mut data = entry(8, &[]);
+        data.extend_from_slice(&[0xff; 4]);
+        let table = parse_table(0x400, &data);
+        assert_eq!(table.entries.len(), 1);
+        assert_eq!(
+            table.invalid_entry,
+            Some(InvalidCertificateEntry {
+                offset: 0x408,
+                length: 0,
+            })
+        );
+    }
+}
@@ -13,10 +13,13 @@ mod base;
 pub use base::*;
 
 pub mod archive;
+#[cfg(feature = "authenticode")]
+pub mod authenticode;
 pub mod coff;
 pub mod constants;
 mod error;
 pub mod image;
+pub mod pdb;
 pub use error::*;
 pub mod sections;
 pub mod util;
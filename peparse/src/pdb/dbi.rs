@@ -0,0 +1,212 @@
+use crate::{
+    coff::constants::Machine,
+    error::{Error, Result},
+    util::Guid,
+};
+use core::convert::TryFrom;
+use segsource::{DataSegment, TryFromSegment};
+
+/// The index of the PDB info stream.
+pub const PDB_INFO_STREAM: usize = 1;
+
+/// The index of the DBI stream.
+pub const DBI_STREAM: usize = 3;
+
+/// The first version of the PDB info stream that has a GUID.
+const PDB_INFO_VERSION_VC70: u32 = 20000404;
+
+/// The stream that records the version and identity of the PDB.
+#[derive(Debug, Clone)]
+pub struct PdbInfoStream {
+    pub version: u32,
+
+    /// The time the PDB was created. PDB 2.0 images use this to identify the PDB.
+    pub signature: u32,
+
+    /// Incremented every time the PDB is written.
+    pub age: u32,
+
+    /// The GUID of the PDB. Only present in PDB 7.0 files.
+    pub guid: Option<Guid>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for PdbInfoStream {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let version = segment.next_u32()?;
+        let signature = segment.next_u32()?;
+        let age = segment.next_u32()?;
+        let guid = if version >= PDB_INFO_VERSION_VC70 {
+            Some(Guid::try_from(segment)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            version,
+            signature,
+            age,
+            guid,
+        })
+    }
+}
+
+/// The header of the DBI (debug information) stream, which says where to find everything else.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct DbiHeader {
+    /// Always -1 (0xffffffff) for the current format.
+    pub version_signature: u32,
+
+    pub version_header: u32,
+
+    /// Incremented every time the PDB is written. This is the age that's recorded in the image.
+    pub age: u32,
+
+    /// The index of the global symbol stream.
+    pub global_stream_index: u16,
+
+    pub build_number: u16,
+
+    /// The index of the public symbol stream.
+    pub public_stream_index: u16,
+
+    pub pdb_dll_version: u16,
+
+    /// The index of the stream that holds the actual public and global symbol records.
+    pub sym_record_stream: u16,
+
+    pub pdb_dll_rbld: u16,
+    pub mod_info_size: u32,
+    pub section_contribution_size: u32,
+    pub section_map_size: u32,
+    pub source_info_size: u32,
+    pub type_server_map_size: u32,
+    pub mfc_type_server_index: u32,
+    pub optional_debug_header_size: u32,
+    pub ec_substream_size: u32,
+    pub flags: u16,
+    pub machine: u16,
+    pub padding: u32,
+}
+
+impl DbiHeader {
+    /// The size of the header.
+    pub const SIZE: usize = 64;
+
+    /// The machine the PDB is for.
+    #[inline]
+    pub fn machine(&self) -> Result<Machine> {
+        Machine::try_from(self.machine)
+    }
+}
+
+/// The streams listed in the DBI stream's optional debug header.
+#[derive(Debug, Clone, Default)]
+pub struct DebugStreams {
+    pub fpo: Option<u16>,
+    pub exception: Option<u16>,
+    pub fixup: Option<u16>,
+    pub omap_to_src: Option<u16>,
+    pub omap_from_src: Option<u16>,
+    pub section_headers: Option<u16>,
+    pub token_rid_map: Option<u16>,
+    pub xdata: Option<u16>,
+    pub pdata: Option<u16>,
+    pub new_fpo: Option<u16>,
+    pub original_section_headers: Option<u16>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for DebugStreams {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let mut indices = Vec::new();
+        while segment.remaining()? >= 2 {
+            indices.push(match segment.next_u16()? {
+                0xffff => None,
+                index => Some(index),
+            });
+        }
+        let get = |index: usize| indices.get(index).copied().flatten();
+        Ok(Self {
+            fpo: get(0),
+            exception: get(1),
+            fixup: get(2),
+            omap_to_src: get(3),
+            omap_from_src: get(4),
+            section_headers: get(5),
+            token_rid_map: get(6),
+            xdata: get(7),
+            pdata: get(8),
+            new_fpo: get(9),
+            original_section_headers: get(10),
+        })
+    }
+}
+
+/// The version of the section contribution substream that doesn't have a COFF section index.
+pub const SECTION_CONTRIBUTION_V60: u32 = 0xeffe0000 + 19970605;
+
+/// The version of the section contribution substream that has a COFF section index.
+pub const SECTION_CONTRIBUTION_V2: u32 = 0xeffe0000 + 20140516;
+
+/// A range of a section that came from a single module (object file).
+#[derive(Debug, Clone)]
+pub struct SectionContribution {
+    /// The 1-based index of the section.
+    pub section: u16,
+
+    /// The offset of the contribution within the section.
+    pub offset: u32,
+
+    pub size: u32,
+    pub characteristics: u32,
+
+    /// The index of the module that contributed the data.
+    pub module_index: u16,
+
+    pub data_crc: u32,
+    pub reloc_crc: u32,
+
+    /// The section's index in the module's object file. Only present in newer PDBs.
+    pub coff_section: Option<u32>,
+}
+
+/// Parses the section contribution substream.
+pub fn parse_section_contributions(segment: &DataSegment<'_>) -> Result<Vec<SectionContribution>> {
+    if segment.remaining()? < 4 {
+        return Ok(Vec::new());
+    }
+    let version = segment.next_u32()?;
+    let has_coff_section = version == SECTION_CONTRIBUTION_V2;
+    let entry_size = if has_coff_section { 32 } else { 28 };
+    let mut contributions = Vec::new();
+    while segment.remaining()? >= entry_size {
+        let section = segment.next_u16()?;
+        let _padding = segment.next_u16()?;
+        let offset = segment.next_u32()?;
+        let size = segment.next_u32()?;
+        let characteristics = segment.next_u32()?;
+        let module_index = segment.next_u16()?;
+        let _padding = segment.next_u16()?;
+        let data_crc = segment.next_u32()?;
+        let reloc_crc = segment.next_u32()?;
+        let coff_section = if has_coff_section {
+            Some(segment.next_u32()?)
+        } else {
+            None
+        };
+        contributions.push(SectionContribution {
+            section,
+            offset,
+            size,
+            characteristics,
+            module_index,
+            data_crc,
+            reloc_crc,
+            coff_section,
+        });
+    }
+    Ok(contributions)
+}
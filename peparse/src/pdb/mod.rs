@@ -0,0 +1,257 @@
+//! A reader for PDB files, which is just enough to get from an RVA to the nearest public symbol.
+
+pub mod dbi;
+pub mod msf;
+pub mod symbols;
+
+use crate::{
+    error::{Error, Result},
+    sections::{
+        debug::{codeview::PdbInfo, OmapTable},
+        SectionHeader,
+    },
+    PeFile, Rva,
+};
+use bytes::Bytes;
+use core::convert::TryFrom;
+use dbi::{
+    parse_section_contributions, DbiHeader, DebugStreams, PdbInfoStream, SectionContribution,
+    DBI_STREAM, PDB_INFO_STREAM,
+};
+use msf::Msf;
+use segsource::Source as _;
+use std::{cmp::max, path::Path};
+use symbols::{parse_symbol_records, PublicSymbol, SymbolRecord};
+
+/// A parsed PDB file.
+#[derive(Debug)]
+pub struct PdbFile {
+    pub msf: Msf,
+    pub info: PdbInfoStream,
+    pub dbi: DbiHeader,
+    pub debug_streams: DebugStreams,
+
+    /// The headers of the image's sections.
+    pub section_headers: Vec<SectionHeader>,
+
+    /// The headers of the image's sections from before it was rearranged, if it was.
+    pub original_section_headers: Option<Vec<SectionHeader>>,
+
+    pub section_contributions: Vec<SectionContribution>,
+
+    /// The records from the symbol record stream, which holds every public and global symbol.
+    pub symbols: Vec<SymbolRecord>,
+
+    omap_from_src: Option<OmapTable>,
+
+    /// The RVA of every public symbol along with its index in `symbols`, sorted by RVA.
+    public_index: Vec<(Rva, usize)>,
+}
+
+/// The result of looking up the public symbol closest to an RVA.
+#[derive(Debug, Clone)]
+pub struct NearestSymbol<'a> {
+    pub symbol: &'a PublicSymbol,
+
+    /// The RVA of the symbol.
+    pub rva: Rva,
+
+    /// How far past the start of the symbol the RVA that was looked up is.
+    pub displacement: u32,
+}
+
+impl PdbFile {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(Bytes::from(std::fs::read(path)?))
+    }
+
+    pub fn from_bytes(data: Bytes) -> Result<Self> {
+        let msf = Msf::from_bytes(data)?;
+        let info = PdbInfoStream::try_from(&required_stream(&msf, PDB_INFO_STREAM)?.all()?)?;
+
+        let dbi_source = required_stream(&msf, DBI_STREAM)?;
+        let dbi_segment = dbi_source.all()?;
+        if dbi_segment.as_ref().get(..4) != Some(&[0xff; 4][..]) {
+            return Err(Error::Other {
+                message: "PDBs that use the pre-VC 4.1 DBI format aren't supported.".into(),
+            });
+        }
+        let dbi = DbiHeader::try_from(&dbi_segment)?;
+        dbi_segment.next_n(dbi.mod_info_size as usize)?;
+        let section_contributions = parse_section_contributions(
+            &dbi_segment.next_n(dbi.section_contribution_size as usize)?,
+        )?;
+        dbi_segment.next_n(
+            dbi.section_map_size as usize
+                + dbi.source_info_size as usize
+                + dbi.type_server_map_size as usize
+                + dbi.ec_substream_size as usize,
+        )?;
+        let debug_streams =
+            DebugStreams::try_from(&dbi_segment.next_n(dbi.optional_debug_header_size as usize)?)?;
+
+        let section_headers = match debug_streams.section_headers {
+            Some(index) => parse_section_headers(&msf, index)?,
+            None => Vec::new(),
+        };
+        let (original_section_headers, omap_from_src) = match (
+            debug_streams.original_section_headers,
+            debug_streams.omap_from_src,
+        ) {
+            (Some(headers), Some(omap)) => (
+                Some(parse_section_headers(&msf, headers)?),
+                Some(OmapTable::try_from(
+                    &required_stream(&msf, omap as usize)?.all()?,
+                )?),
+            ),
+            _ => (None, None),
+        };
+        let symbols = match msf.stream_source(dbi.sym_record_stream as usize)? {
+            Some(source) => parse_symbol_records(&source.all()?)?,
+            None => Vec::new(),
+        };
+
+        let mut pdb = Self {
+            msf,
+            info,
+            dbi,
+            debug_streams,
+            section_headers,
+            original_section_headers,
+            section_contributions,
+            symbols,
+            omap_from_src,
+            public_index: Vec::new(),
+        };
+        let mut public_index: Vec<(Rva, usize)> = pdb
+            .symbols
+            .iter()
+            .enumerate()
+            .filter_map(|(index, record)| match record {
+                SymbolRecord::Public(symbol) => pdb
+                    .section_offset_to_rva(symbol.section, symbol.offset)
+                    .map(|rva| (rva, index)),
+                _ => None,
+            })
+            .collect();
+        public_index.sort_unstable();
+        pdb.public_index = public_index;
+        Ok(pdb)
+    }
+
+    /// If this is the PDB that the provided CodeView information refers to. For PDB 7.0 files,
+    /// the GUID has to match, as well as the age recorded in the DBI stream.
+    pub fn matches(&self, pdb_info: &PdbInfo) -> bool {
+        match (pdb_info.guid, pdb_info.signature) {
+            (Some(guid), _) => self.info.guid == Some(guid) && self.dbi.age == pdb_info.age,
+            (None, Some(signature)) => {
+                self.info.signature == signature && self.info.age == pdb_info.age
+            }
+            (None, None) => false,
+        }
+    }
+
+    /// If this is the PDB for the provided image.
+    pub fn matches_image(&self, image: &PeFile) -> Result<bool> {
+        Ok(image
+            .pdb_info()?
+            .map(|pdb_info| self.matches(&pdb_info))
+            .unwrap_or(false))
+    }
+
+    /// Converts a section index and offset, which is how symbols are addressed, into an RVA. If
+    /// the image was rearranged after it was linked, the OMAP table is used to find the final
+    /// RVA.
+    pub fn section_offset_to_rva(&self, section: u16, offset: u32) -> Option<Rva> {
+        let index = section.checked_sub(1)? as usize;
+        match (&self.original_section_headers, &self.omap_from_src) {
+            (Some(headers), Some(omap)) => {
+                omap.translate(headers.get(index)?.virtual_address.checked_add(offset)?)
+            }
+            _ => self
+                .section_headers
+                .get(index)?
+                .virtual_address
+                .checked_add(offset),
+        }
+    }
+
+    /// Iterates over every public symbol.
+    pub fn public_symbols(&self) -> impl Iterator<Item = &PublicSymbol> {
+        self.symbols.iter().filter_map(|record| match record {
+            SymbolRecord::Public(symbol) => Some(symbol),
+            _ => None,
+        })
+    }
+
+    /// Finds the public symbol with the highest RVA that's less than or equal to the provided
+    /// one. The RVA has to be in the same section contribution as the symbol (or the same section,
+    /// if there isn't a contribution for it), so an RVA far past the last symbol in a section
+    /// isn't attributed to it.
+    pub fn nearest_public_symbol(&self, rva: Rva) -> Option<NearestSymbol<'_>> {
+        let index = self
+            .public_index
+            .partition_point(|(symbol_rva, _)| *symbol_rva <= rva);
+        let (symbol_rva, symbol_index) = *self.public_index.get(index.checked_sub(1)?)?;
+        if let Some(end) = self.containing_range_end(symbol_rva) {
+            if rva >= end {
+                return None;
+            }
+        }
+        match &self.symbols[symbol_index] {
+            SymbolRecord::Public(symbol) => Some(NearestSymbol {
+                symbol,
+                rva: symbol_rva,
+                displacement: rva - symbol_rva,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The end of the section contribution that holds the provided RVA or, if there isn't one,
+    /// the end of the section that does. Contributions are in terms of the original layout, so
+    /// they're only used for images that weren't rearranged.
+    fn containing_range_end(&self, rva: Rva) -> Option<Rva> {
+        let contains = |start: Rva, size: u32| {
+            let end = start.checked_add(size)?;
+            if (start..end).contains(&rva) {
+                Some(end)
+            } else {
+                None
+            }
+        };
+        if self.omap_from_src.is_none() {
+            let end = self.section_contributions.iter().find_map(|contribution| {
+                contains(
+                    self.section_offset_to_rva(contribution.section, contribution.offset)?,
+                    contribution.size,
+                )
+            });
+            if end.is_some() {
+                return end;
+            }
+        }
+        self.section_headers.iter().find_map(|header| {
+            contains(
+                header.virtual_address,
+                max(header.virtual_size, header.size_of_raw_data),
+            )
+        })
+    }
+}
+
+fn required_stream(msf: &Msf, index: usize) -> Result<segsource::BytesSource> {
+    msf.stream_source(index)?.ok_or_else(|| Error::Other {
+        message: format!("The PDB is missing stream {}.", index),
+    })
+}
+
+fn parse_section_headers(msf: &Msf, index: u16) -> Result<Vec<SectionHeader>> {
+    let source = required_stream(msf, index as usize)?;
+    let segment = source.all()?;
+    let mut headers = Vec::new();
+    while segment.remaining()? >= 40 {
+        headers.push(SectionHeader::try_from(&segment)?);
+    }
+    Ok(headers)
+}
@@ -0,0 +1,273 @@
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use segsource::{BytesSource, Endidness, Source as _};
+
+/// The magic at the start of an MSF 7.0 ("big") file.
+pub const BIG_MSF_MAGIC: &[u8] = b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0";
+
+/// The magic at the start of an MSF 2.0 ("small") file.
+pub const SMALL_MSF_MAGIC: &[u8] = b"Microsoft C/C++ program database 2.00\r\n\x1aJG\0\0";
+
+/// The size of a stream that doesn't exist.
+const NIL_STREAM_SIZE: u32 = 0xffffffff;
+
+/// A stream inside of an MSF file.
+#[derive(Debug, Clone)]
+struct MsfStream {
+    size: u32,
+    pages: Vec<u32>,
+}
+
+/// The multi-stream file (MSF) container that PDBs are stored in. An MSF is made up of fixed-size
+/// pages, and each stream is a list of (not necessarily contiguous) pages.
+#[derive(Debug)]
+pub struct Msf {
+    source: BytesSource,
+
+    /// If this is an MSF 7.0 file, which uses 32-bit page numbers.
+    pub is_big: bool,
+
+    pub page_size: u32,
+    streams: Vec<Option<MsfStream>>,
+}
+
+impl Msf {
+    pub fn from_bytes(data: Bytes) -> Result<Self> {
+        let source = BytesSource::from_bytes(data, Endidness::Little)?;
+        if source.all()?.as_ref().starts_with(BIG_MSF_MAGIC) {
+            Self::parse_big(source)
+        } else if source.all()?.as_ref().starts_with(SMALL_MSF_MAGIC) {
+            Self::parse_small(source)
+        } else {
+            Err(Error::InvalidHeaderMagic {
+                expected: "an MSF 2.0 or 7.0 header".into(),
+                received: hex::encode(&source.all()?.as_ref()[..min_len(&source, 32)?]),
+            })
+        }
+    }
+
+    fn parse_big(source: BytesSource) -> Result<Self> {
+        let header = source.get_n(BIG_MSF_MAGIC.len(), 24)?;
+        let page_size = check_page_size(header.next_u32()?)?;
+        let _free_page_map = header.next_u32()?;
+        let _page_count = header.next_u32()?;
+        let directory_size = header.next_u32()?;
+        let _reserved = header.next_u32()?;
+        let block_map_page = header.next_u32()?;
+
+        let directory_page_count = pages_needed(directory_size, page_size);
+        let block_map = source.get_n(
+            page_size as usize * block_map_page as usize,
+            directory_page_count * 4,
+        )?;
+        let directory_pages = (0..directory_page_count)
+            .map(|_| Ok(block_map.next_u32()?))
+            .collect::<Result<Vec<_>>>()?;
+        let mut msf = Self {
+            source,
+            is_big: true,
+            page_size,
+            streams: Vec::new(),
+        };
+        let directory = msf.read_pages(&directory_pages, directory_size)?;
+        let directory = BytesSource::from_bytes(Bytes::from(directory), Endidness::Little)?;
+        let directory = directory.all()?;
+
+        let stream_count = directory.next_u32()?;
+        let sizes = (0..stream_count)
+            .map(|_| Ok(directory.next_u32()?))
+            .collect::<Result<Vec<_>>>()?;
+        for size in sizes {
+            msf.streams.push(if size == NIL_STREAM_SIZE {
+                None
+            } else {
+                let pages = (0..pages_needed(size, page_size))
+                    .map(|_| Ok(directory.next_u32()?))
+                    .collect::<Result<_>>()?;
+                Some(MsfStream { size, pages })
+            });
+        }
+        Ok(msf)
+    }
+
+    fn parse_small(source: BytesSource) -> Result<Self> {
+        let header = source.get_n(SMALL_MSF_MAGIC.len(), 16)?;
+        let page_size = check_page_size(header.next_u32()?)?;
+        let _free_page_map = header.next_u16()?;
+        let _page_count = header.next_u16()?;
+        let directory_size = header.next_u32()?;
+        let _reserved = header.next_u32()?;
+
+        let directory_page_count = pages_needed(directory_size, page_size);
+        let page_list = source.get_n(SMALL_MSF_MAGIC.len() + 16, directory_page_count * 2)?;
+        let directory_pages = (0..directory_page_count)
+            .map(|_| Ok(page_list.next_u16()? as u32))
+            .collect::<Result<Vec<_>>>()?;
+        let mut msf = Self {
+            source,
+            is_big: false,
+            page_size,
+            streams: Vec::new(),
+        };
+        let directory = msf.read_pages(&directory_pages, directory_size)?;
+        let directory = BytesSource::from_bytes(Bytes::from(directory), Endidness::Little)?;
+        let directory = directory.all()?;
+
+        let stream_count = directory.next_u16()?;
+        let _reserved = directory.next_u16()?;
+        let sizes = (0..stream_count)
+            .map(|_| {
+                let size = directory.next_u32()?;
+                let _reserved = directory.next_u32()?;
+                Ok(size)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for size in sizes {
+            msf.streams.push(if size == NIL_STREAM_SIZE {
+                None
+            } else {
+                let pages = (0..pages_needed(size, page_size))
+                    .map(|_| Ok(directory.next_u16()? as u32))
+                    .collect::<Result<_>>()?;
+                Some(MsfStream { size, pages })
+            });
+        }
+        Ok(msf)
+    }
+
+    /// The number of streams in the file, including ones that don't exist.
+    #[inline]
+    pub fn stream_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Reads the entire contents of a stream. Returns `None` if the stream doesn't exist.
+    pub fn stream(&self, index: usize) -> Result<Option<Vec<u8>>> {
+        match self.streams.get(index) {
+            Some(Some(stream)) => Ok(Some(self.read_pages(&stream.pages, stream.size)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads a stream and wraps it in a source so it can be parsed.
+    pub fn stream_source(&self, index: usize) -> Result<Option<BytesSource>> {
+        match self.stream(index)? {
+            Some(data) => Ok(Some(BytesSource::from_bytes(
+                Bytes::from(data),
+                Endidness::Little,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_pages(&self, pages: &[u32], size: u32) -> Result<Vec<u8>> {
+        // The size comes from the file, so it's checked against the pages (and the file itself)
+        // before anything is allocated for it.
+        if (size as u64) > pages.len() as u64 * self.page_size as u64
+            || size as usize > self.source.all()?.as_ref().len()
+        {
+            return Err(Error::Other {
+                message: format!(
+                    "A stream of {} bytes doesn't fit in its {} pages.",
+                    size,
+                    pages.len()
+                ),
+            });
+        }
+        let mut data = Vec::with_capacity(size as usize);
+        let mut remaining = size as usize;
+        for page in pages {
+            let to_read = remaining.min(self.page_size as usize);
+            let offset = (*page as usize)
+                .checked_mul(self.page_size as usize)
+                .ok_or_else(|| Error::Other {
+                    message: format!("MSF page {} is out of range.", page),
+                })?;
+            data.extend_from_slice(self.source.get_n(offset, to_read)?.as_ref());
+            remaining -= to_read;
+        }
+        Ok(data)
+    }
+}
+
+/// The number of pages needed to hold `size` bytes.
+#[inline]
+fn pages_needed(size: u32, page_size: u32) -> usize {
+    ((size as u64 + page_size as u64 - 1) / page_size as u64) as usize
+}
+
+fn check_page_size(page_size: u32) -> Result<u32> {
+    if page_size.is_power_of_two() {
+        Ok(page_size)
+    } else {
+        Err(Error::Other {
+            message: format!("{} isn't a valid MSF page size.", page_size),
+        })
+    }
+}
+
+fn min_len(source: &BytesSource, len: usize) -> Result<usize> {
+    Ok(len.min(source.all()?.as_ref().len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 512;
+
+    /// An MSF 7.0 file with four pages: the header, "abcd" in page 1, the block map in page 2 and
+    /// the stream directory in page 3.
+    fn big_msf(directory: &[u32]) -> Bytes {
+        let mut data = vec![0; PAGE_SIZE * 4];
+        data[..BIG_MSF_MAGIC.len()].copy_from_slice(BIG_MSF_MAGIC);
+        let header = [PAGE_SIZE as u32, 1, 4, directory.len() as u32 * 4, 0, 2];
+        for (index, value) in header.iter().enumerate() {
+            let offset = BIG_MSF_MAGIC.len() + index * 4;
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        data[PAGE_SIZE..PAGE_SIZE + 4].copy_from_slice(b"abcd");
+        data[PAGE_SIZE * 2..PAGE_SIZE * 2 + 4].copy_from_slice(&3u32.to_le_bytes());
+        for (index, value) in directory.iter().enumerate() {
+            let offset = PAGE_SIZE * 3 + index * 4;
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        Bytes::from(data)
+    }
+
+    #[test]
+    fn reads_streams() {
+        let msf = Msf::from_bytes(big_msf(&[2, 4, NIL_STREAM_SIZE, 1])).unwrap();
+        assert!(msf.is_big);
+        assert_eq!(msf.stream_count(), 2);
+        assert_eq!(msf.stream(0).unwrap(), Some(b"abcd".to_vec()));
+        assert_eq!(msf.stream(1).unwrap(), None);
+        assert_eq!(msf.stream(2).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_streams_larger_than_the_file() {
+        let msf = Msf::from_bytes(big_msf(&[1, 0x1000, 1, 1, 1, 1, 1, 1, 1, 1])).unwrap();
+        assert!(msf.stream(0).is_err());
+    }
+
+    #[test]
+    fn rejects_pages_outside_of_the_file() {
+        let msf = Msf::from_bytes(big_msf(&[1, 4, 0xffff_ffff])).unwrap();
+        assert!(msf.stream(0).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut data = big_msf(&[0]).to_vec();
+        data[BIG_MSF_MAGIC.len()..BIG_MSF_MAGIC.len() + 4].copy_from_slice(&500u32.to_le_bytes());
+        assert!(Msf::from_bytes(Bytes::from(data)).is_err());
+
+        let mut data = big_msf(&[0]).to_vec();
+        let directory_size = BIG_MSF_MAGIC.len() + 12;
+        data[directory_size..directory_size + 4].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        assert!(Msf::from_bytes(Bytes::from(data)).is_err());
+
+        assert!(Msf::from_bytes(Bytes::from_static(b"not an MSF")).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    error::{Error, Result},
+    util::next_null_terminated,
+};
+use segsource::DataSegment;
+
+pub const S_PUB32_ST: u16 = 0x1009;
+pub const S_LDATA32: u16 = 0x110c;
+pub const S_GDATA32: u16 = 0x110d;
+pub const S_PUB32: u16 = 0x110e;
+pub const S_PROCREF: u16 = 0x1125;
+pub const S_LPROCREF: u16 = 0x1127;
+
+/// A public symbol (S_PUB32).
+#[derive(Debug, Clone)]
+pub struct PublicSymbol {
+    /// CV_PUBSYMFLAGS: bit 0 is code, bit 1 is function, bit 2 is managed code and bit 3 is MSIL.
+    pub flags: u32,
+
+    /// The offset of the symbol within its section.
+    pub offset: u32,
+
+    /// The 1-based index of the section the symbol is in.
+    pub section: u16,
+
+    /// The decorated name of the symbol.
+    pub name: String,
+}
+
+impl PublicSymbol {
+    /// If the symbol refers to code.
+    #[inline]
+    pub fn is_code(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    /// If the symbol refers to a function.
+    #[inline]
+    pub fn is_function(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+}
+
+/// A global or module-local variable (S_GDATA32 or S_LDATA32).
+#[derive(Debug, Clone)]
+pub struct DataSymbol {
+    /// If this is a global (rather than module-local) variable.
+    pub is_global: bool,
+
+    /// The index of the variable's type in the TPI stream.
+    pub type_index: u32,
+
+    pub offset: u32,
+    pub section: u16,
+    pub name: String,
+}
+
+/// A reference to a procedure symbol in a module's symbol stream (S_PROCREF or S_LPROCREF).
+#[derive(Debug, Clone)]
+pub struct ProcedureReference {
+    /// If the procedure is module-local.
+    pub is_local: bool,
+
+    /// The checksum of the name.
+    pub sum_name: u32,
+
+    /// The offset of the procedure symbol in the module's symbol stream.
+    pub symbol_offset: u32,
+
+    /// The 1-based index of the module.
+    pub module: u16,
+
+    pub name: String,
+}
+
+/// A record from the symbol record stream, which holds the public and global symbols.
+#[derive(Debug, Clone)]
+pub enum SymbolRecord {
+    Public(PublicSymbol),
+    Data(DataSymbol),
+    ProcedureReference(ProcedureReference),
+
+    /// A record of a kind that isn't decoded.
+    Other {
+        kind: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl SymbolRecord {
+    /// Parses a single record, given its kind and its data (everything after the kind).
+    pub fn parse(kind: u16, segment: &DataSegment<'_>) -> Result<Self> {
+        Ok(match kind {
+            S_PUB32 | S_PUB32_ST => Self::Public(PublicSymbol {
+                flags: segment.next_u32()?,
+                offset: segment.next_u32()?,
+                section: segment.next_u16()?,
+                name: next_name(kind == S_PUB32_ST, segment)?,
+            }),
+            S_GDATA32 | S_LDATA32 => Self::Data(DataSymbol {
+                is_global: kind == S_GDATA32,
+                type_index: segment.next_u32()?,
+                offset: segment.next_u32()?,
+                section: segment.next_u16()?,
+                name: next_name(false, segment)?,
+            }),
+            S_PROCREF | S_LPROCREF => Self::ProcedureReference(ProcedureReference {
+                is_local: kind == S_LPROCREF,
+                sum_name: segment.next_u32()?,
+                symbol_offset: segment.next_u32()?,
+                module: segment.next_u16()?,
+                name: next_name(false, segment)?,
+            }),
+            _ => Self::Other {
+                kind,
+                data: segment.as_ref().to_vec(),
+            },
+        })
+    }
+}
+
+/// Parses every record in the symbol record stream.
+pub fn parse_symbol_records(segment: &DataSegment<'_>) -> Result<Vec<SymbolRecord>> {
+    let mut records = Vec::new();
+    while segment.remaining()? >= 4 {
+        let length = segment.next_u16()? as usize;
+        if length < 2 {
+            return Err(Error::Other {
+                message: format!("Symbol record has an invalid length of {}.", length),
+            });
+        }
+        let kind = segment.next_u16()?;
+        let data = segment.next_n(length - 2)?;
+        records.push(SymbolRecord::parse(kind, &data)?);
+    }
+    Ok(records)
+}
+
+/// Reads a symbol name, which is length-prefixed in older (_ST) records and null-terminated in
+/// newer ones. Names aren't guaranteed to be valid UTF-8.
+fn next_name(is_length_prefixed: bool, segment: &DataSegment<'_>) -> Result<String> {
+    let name = if is_length_prefixed {
+        let length = segment.next_u8()? as usize;
+        segment.next_n(length)?.as_ref().to_vec()
+    } else {
+        next_null_terminated(segment)?
+    };
+    Ok(String::from_utf8_lossy(&name).into_owned())
+}
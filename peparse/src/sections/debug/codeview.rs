@@ -0,0 +1,265 @@
+use crate::{
+    error::{Error, Result},
+    util::{next_null_terminated, symbol_store_path, Guid},
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
+
+/// The signature of a PDB 7.0 CodeView record.
+pub const RSDS_SIGNATURE: u32 = 0x53445352;
+
+/// The signature of a PDB 2.0 CodeView record.
+pub const NB10_SIGNATURE: u32 = 0x3031424e;
+
+/// The signature of a CodeView record written by Mono and some CLR tools.
+pub const MTOC_SIGNATURE: u32 = 0x434f544d;
+
+/// The data of a CodeView debug directory entry, which identifies the PDB that goes with the
+/// image.
+#[derive(Debug, Clone)]
+pub enum CodeView {
+    Rsds(RsdsInfo),
+    Nb10(Nb10Info),
+    Mtoc(MtocInfo),
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for CodeView {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        match segment.next_u32()? {
+            RSDS_SIGNATURE => Ok(Self::Rsds(RsdsInfo {
+                guid: Guid::try_from(segment)?,
+                age: segment.next_u32()?,
+                path: String::from_utf8(next_null_terminated(segment)?)?,
+            })),
+            NB10_SIGNATURE => Ok(Self::Nb10(Nb10Info {
+                offset: segment.next_u32()?,
+                time_date_stamp: segment.next_u32()?,
+                age: segment.next_u32()?,
+                // The path is in the system code page, which we can't know.
+                path: String::from_utf8_lossy(&next_null_terminated(segment)?).into_owned(),
+            })),
+            MTOC_SIGNATURE => Ok(Self::Mtoc(MtocInfo {
+                guid: Guid::try_from(segment)?,
+                path: String::from_utf8(next_null_terminated(segment)?)?,
+            })),
+            signature => Err(Error::InvalidHeaderMagic {
+                expected: "RSDS, NB10 or MTOC".into(),
+                received: format!("0x{:08x}", signature),
+            }),
+        }
+    }
+}
+
+impl CodeView {
+    /// The path of the PDB, as it was when the image was built.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Rsds(info) => &info.path,
+            Self::Nb10(info) => &info.path,
+            Self::Mtoc(info) => &info.path,
+        }
+    }
+}
+
+/// A PDB 7.0 CodeView record.
+#[derive(Debug, Clone)]
+pub struct RsdsInfo {
+    /// The GUID of the PDB, which changes every time it's rewritten from scratch.
+    pub guid: Guid,
+
+    /// Incremented every time the PDB is updated.
+    pub age: u32,
+
+    /// The path of the PDB, as it was when the image was built.
+    pub path: String,
+}
+
+/// A PDB 2.0 CodeView record.
+#[derive(Debug, Clone)]
+pub struct Nb10Info {
+    /// The offset of the CodeView data. This is always 0, since the data is in a separate file.
+    pub offset: u32,
+
+    /// The time the PDB was created, which is used as its signature.
+    pub time_date_stamp: u32,
+
+    /// Incremented every time the PDB is updated.
+    pub age: u32,
+
+    /// The path of the PDB, as it was when the image was built.
+    pub path: String,
+}
+
+/// A CodeView record written by Mono and some CLR tools.
+#[derive(Debug, Clone)]
+pub struct MtocInfo {
+    pub guid: Guid,
+
+    /// The path of the image, as it was when it was built.
+    pub path: String,
+}
+
+/// The identity of the PDB that goes with an image.
+#[derive(Debug, Clone)]
+pub struct PdbInfo {
+    /// The file name of the PDB, without any directories.
+    pub name: String,
+
+    /// The full path of the PDB, as it was when the image was built. This often contains internal
+    /// machine or user names, so be careful where it ends up.
+    pub build_path: String,
+
+    /// The GUID of the PDB. This is `None` for PDB 2.0 files, which use `signature` instead.
+    pub guid: Option<Guid>,
+
+    /// The signature of a PDB 2.0 file. `None` for newer PDBs.
+    pub signature: Option<u32>,
+
+    pub age: u32,
+}
+
+impl PdbInfo {
+    /// Gets the PDB identity from a CodeView record. MTOC records don't refer to a PDB, so they
+    /// return `None`.
+    pub fn from_codeview(codeview: &CodeView) -> Option<Self> {
+        match codeview {
+            CodeView::Rsds(info) => Some(Self {
+                name: basename(&info.path).into(),
+                build_path: info.path.clone(),
+                guid: Some(info.guid),
+                signature: None,
+                age: info.age,
+            }),
+            CodeView::Nb10(info) => Some(Self {
+                name: basename(&info.path).into(),
+                build_path: info.path.clone(),
+                guid: None,
+                signature: Some(info.time_date_stamp),
+                age: info.age,
+            }),
+            CodeView::Mtoc(_) => None,
+        }
+    }
+
+    /// The key the PDB is stored under on a symbol server: the GUID followed by the age for PDB 7.0
+    /// files, or the signature followed by the age for PDB 2.0 files. The GUID and signature are in
+    /// uppercase hex, while the age is in lowercase hex, which is what symbol servers expect.
+    pub fn symbol_server_key(&self) -> String {
+        match (self.guid, self.signature) {
+            (Some(guid), _) => format!("{}{:x}", guid.to_simple_string(), self.age),
+            (None, signature) => format!("{:08X}{:x}", signature.unwrap_or(0), self.age),
+        }
+    }
+
+    /// The path of the PDB relative to the root of a symbol store.
+    pub fn symbol_server_path(&self) -> String {
+        symbol_store_path(&self.name, &self.symbol_server_key())
+    }
+}
+
+/// Gets the last component of a path that may use either Windows or Unix separators.
+pub fn basename(path: &str) -> &str {
+    path.rsplit(|c| c == '\\' || c == '/')
+        .next()
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse(data: &[u8]) -> Result<CodeView> {
+        let source = BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little)?;
+        CodeView::try_from(&source.all()?)
+    }
+
+    #[test]
+    fn rsds_record() {
+        let mut data = b"RSDS".to_vec();
+        data.extend_from_slice(&[
+            0x78, 0x56, 0x34, 0x12, 0xbc, 0x9a, 0xf0, 0xde, // GUID
+            0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, //
+            0x1a, 0x00, 0x00, 0x00, // Age
+        ]);
+        data.extend_from_slice(b"C:\\build\\app.pdb\0");
+        let codeview = parse(&data).unwrap();
+        assert_eq!(codeview.path(), r"C:\build\app.pdb");
+        let info = PdbInfo::from_codeview(&codeview).unwrap();
+        assert_eq!(info.name, "app.pdb");
+        assert_eq!(info.age, 0x1a);
+        assert_eq!(info.signature, None);
+        assert_eq!(
+            info.guid,
+            Some(Guid {
+                data1: 0x1234_5678,
+                data2: 0x9abc,
+                data3: 0xdef0,
+                data4: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+            })
+        );
+    }
+
+    #[test]
+    fn nb10_record() {
+        let mut data = b"NB10".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0x2f, 0x1b, 0x5a, 0x3c, 2, 0, 0, 0]);
+        data.extend_from_slice(b"/build/app.pdb\0");
+        let info = PdbInfo::from_codeview(&parse(&data).unwrap()).unwrap();
+        assert_eq!(info.name, "app.pdb");
+        assert_eq!(info.guid, None);
+        assert_eq!(info.signature, Some(0x3c5a_1b2f));
+        assert_eq!(info.age, 2);
+    }
+
+    #[test]
+    fn unknown_signature_is_an_error() {
+        assert!(matches!(
+            parse(b"XXXX\0\0\0\0"),
+            Err(Error::InvalidHeaderMagic { .. })
+        ));
+    }
+
+    #[test]
+    fn basename_handles_both_separators() {
+        assert_eq!(basename(r"C:\build\app.pdb"), "app.pdb");
+        assert_eq!(basename("/build/app.pdb"), "app.pdb");
+        assert_eq!(basename("app.pdb"), "app.pdb");
+    }
+
+    fn pdb_info(guid: Option<Guid>, signature: Option<u32>, age: u32) -> PdbInfo {
+        PdbInfo {
+            name: "app.pdb".into(),
+            build_path: r"C:\build\app.pdb".into(),
+            guid,
+            signature,
+            age,
+        }
+    }
+
+    #[test]
+    fn symbol_server_keys() {
+        let guid = Guid {
+            data1: 0x1234_5678,
+            data2: 0x9abc,
+            data3: 0xdef0,
+            data4: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+        };
+        let info = pdb_info(Some(guid), None, 0x1a);
+        assert_eq!(
+            info.symbol_server_key(),
+            "123456789ABCDEF00123456789ABCDEF1a"
+        );
+        assert_eq!(
+            info.symbol_server_path(),
+            "app.pdb/123456789ABCDEF00123456789ABCDEF1a/app.pdb"
+        );
+        assert_eq!(
+            pdb_info(None, Some(0x3c5a_1b2f), 2).symbol_server_key(),
+            "3C5A1B2F2"
+        );
+    }
+}
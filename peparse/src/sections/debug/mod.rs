@@ -0,0 +1,321 @@
+use crate::{
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::{DataSegment, TryFromSegment};
+
+pub mod codeview;
+pub mod records;
+
+use codeview::CodeView;
+use records::{EmbeddedPortablePdb, PdbChecksum, Pogo, Repro, VcFeature};
+
+constants_enum! {
+    name: DebugType,
+    doc: "The format of the debugging information pointed to by a debug directory entry.",
+    value_type: u32,
+    items: [
+        (Unknown, 0, "An unknown value that is ignored by all tools."),
+        (Coff, 1, "The COFF debug information (line numbers, symbol table, and string table). This type of debug information is also pointed to by fields in the file headers."),
+        (Codeview, 2, "The Visual C++ debug information."),
+        (Fpo, 3, "The frame pointer omission (FPO) information. This information tells the debugger how to interpret nonstandard stack frames, which use the EBP register for a purpose other than as a frame pointer."),
+        (Misc, 4, "The location of DBG file."),
+        (Exception, 5, "A copy of .pdata section."),
+        (Fixup, 6, "Reserved."),
+        (OmapToSrc, 7, "The mapping from an RVA in image to an RVA in source image."),
+        (OmapFromSrc, 8, "The mapping from an RVA in source image to an RVA in image."),
+        (Borland, 9, "Reserved for Borland."),
+        (Reserved10, 10, "Reserved."),
+        (Clsid, 11, "Reserved."),
+        (VcFeature, 12, "Visual C++ feature information, such as the number of /GS and /sdl functions."),
+        (Pogo, 13, "Profile guided optimization information."),
+        (Iltcg, 14, "Incremental link-time code generation information."),
+        (Mpx, 15, "Intel Memory Protection Extensions information."),
+        (Repro, 16, "PE determinism or reproducibility."),
+        (EmbeddedPortablePdb, 17, "An embedded, compressed portable PDB."),
+        (Spgo, 18, "Sample profile guided optimization information."),
+        (PdbChecksum, 19, "The checksum of the PDB file that goes with the image."),
+        (ExDllCharacteristics, 20, "Extended DLL characteristics bits."),
+    ]
+}
+
+flags! {
+    name: ExtendedDllCharacteristics,
+    doc: "The flags stored in an ExDllCharacteristics debug directory entry.",
+    value_type: u32,
+    items: [
+        (CetCompat, 0x0001, "Image is CET compatible."),
+        (CetCompatStrictMode, 0x0002, "Image is CET compatible in strict mode."),
+        (CetSetContextIpValidationRelaxedMode, 0x0004, "Relaxed mode for context IP validation under CET is allowed."),
+        (CetDynamicApisAllowInProc, 0x0008, "Use of dynamic APIs is restricted to processes that are not CET compatible."),
+        (CetReserved1, 0x0010, "Reserved for future use."),
+        (CetReserved2, 0x0020, "Reserved for future use."),
+        (ForwardCfiCompat, 0x0040, "Image is compatible with forward control flow integrity."),
+        (HotpatchCompatible, 0x0080, "Image is hot patch compatible."),
+    ]
+}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error), also_needs(is_32_plus: bool))]
+pub struct DebugDirectory {
+    /// Reserved, must be zero.
+    _characteristics: u32,
+
+    /// The time and date that the debug data was created.
+    pub time_date_stamp: u32,
+
+    /// The major version number of the debug data format.
+    pub major_version: u16,
+
+    /// The minor version number of the debug data format.
+    pub minor_version: u16,
+
+    /// The format of debugging information. This field enables support of multiple debuggers. See
+    /// [`DebugDirectory::debug_type`].
+    pub info_type: u32,
+
+    /// The size of the debug data (not including the debug directory itself).
+    pub size_of_data: u32,
+
+    /// The address of the debug data when loaded, relative to the image base.
+    pub address_of_raw_data: u32,
+
+    /// The file pointer to the debug data.
+    pub pointer_to_raw_data: u32,
+}
+
+/// The decoded data of a debug directory entry.
+#[derive(Debug, Clone)]
+pub enum DebugData {
+    CodeView(CodeView),
+    Pogo(Pogo),
+    VcFeature(VcFeature),
+
+    /// The image was built with incremental link-time code generation. These entries don't have
+    /// any data.
+    Iltcg,
+
+    Repro(Repro),
+    ExDllCharacteristics(ExtendedDllCharacteristics),
+
+    EmbeddedPortablePdb(EmbeddedPortablePdb),
+    PdbChecksum(PdbChecksum),
+    Fpo(Vec<FrameFpo>),
+    OmapToSrc(OmapTable),
+    OmapFromSrc(OmapTable),
+
+    /// The raw data of an entry whose type isn't decoded.
+    Other(Vec<u8>),
+}
+
+impl DebugData {
+    /// Decodes the data of a debug directory entry according to its type.
+    pub fn parse(entry: &DebugDirectory, segment: &DataSegment<'_>) -> Result<Self> {
+        Ok(match entry.debug_type() {
+            Ok(DebugType::Codeview) => Self::CodeView(CodeView::try_from(segment)?),
+            Ok(DebugType::Pogo) => Self::Pogo(Pogo::try_from(segment)?),
+            Ok(DebugType::VcFeature) => Self::VcFeature(VcFeature::try_from(segment)?),
+            Ok(DebugType::Iltcg) => Self::Iltcg,
+            Ok(DebugType::EmbeddedPortablePdb) => {
+                Self::EmbeddedPortablePdb(EmbeddedPortablePdb::try_from(segment)?)
+            }
+            Ok(DebugType::PdbChecksum) => Self::PdbChecksum(PdbChecksum::try_from(segment)?),
+            Ok(DebugType::Fpo) => Self::Fpo(
+                (0..segment.remaining()? / FrameFpo::SIZE)
+                    .map(|_| FrameFpo::try_from(segment))
+                    .collect::<Result<_>>()?,
+            ),
+            Ok(DebugType::OmapToSrc) => Self::OmapToSrc(OmapTable::try_from(segment)?),
+            Ok(DebugType::OmapFromSrc) => Self::OmapFromSrc(OmapTable::try_from(segment)?),
+            Ok(DebugType::Repro) => Self::Repro(Repro::try_from(segment)?),
+            Ok(DebugType::ExDllCharacteristics) => {
+                Self::ExDllCharacteristics(ExtendedDllCharacteristics::try_from(segment)?)
+            }
+            _ => Self::Other(segment.as_ref().to_vec()),
+        })
+    }
+}
+
+impl DebugDirectory {
+    /// The size of a single debug directory entry.
+    pub const SIZE: usize = 28;
+
+    /// Gets the format of the debug data. Fails for types that aren't known yet.
+    #[inline]
+    pub fn debug_type(&self) -> Result<DebugType> {
+        DebugType::try_from(self.info_type)
+    }
+}
+
+/// An FPO_DATA entry, which describes the stack frame of a function compiled with frame pointer
+/// omission.
+#[derive(Debug, Clone)]
+pub struct FrameFpo {
+    /// The offset of the first byte of the function's code.
+    pub ul_off_start: u32,
+
+    /// The number of bytes in the function.
+    pub cb_proc_size: u32,
+
+    /// The number of bytes of locals, divided by 4.
+    pub cdw_locals: u32,
+
+    /// The number of bytes of parameters, divided by 4.
+    pub cdw_params: u16,
+
+    /// The number of bytes in the prolog.
+    pub cb_prolog: u8,
+
+    /// The number of registers saved.
+    pub cb_regs: u8,
+
+    /// If the function uses structured exception handling.
+    pub f_has_seh: bool,
+
+    /// If EBP has been allocated.
+    pub f_use_bp: bool,
+
+    /// Reserved for future use.
+    pub reserved: bool,
+
+    /// The frame type.
+    pub cb_frame: FpoFrameType,
+}
+
+impl FrameFpo {
+    /// The size of a single FPO_DATA entry.
+    pub const SIZE: usize = 16;
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for FrameFpo {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let ul_off_start = segment.next_u32()?;
+        let cb_proc_size = segment.next_u32()?;
+        let cdw_locals = segment.next_u32()?;
+        let cdw_params = segment.next_u16()?;
+        // The rest of the fields are bitfields packed into a single word.
+        let bits = segment.next_u16()?;
+        Ok(Self {
+            ul_off_start,
+            cb_proc_size,
+            cdw_locals,
+            cdw_params,
+            cb_prolog: (bits & 0xff) as u8,
+            cb_regs: ((bits >> 8) & 0x7) as u8,
+            f_has_seh: (bits >> 11) & 1 == 1,
+            f_use_bp: (bits >> 12) & 1 == 1,
+            reserved: (bits >> 13) & 1 == 1,
+            cb_frame: FpoFrameType::try_from((bits >> 14) as u8)?,
+        })
+    }
+}
+
+constants_enum! {
+    name: FpoFrameType,
+    doc: "The type of frame an FPO_DATA entry describes.",
+    value_type: u8,
+    items: [
+        (Fpo, 0, "A frame pointer omitted frame."),
+        (Trap, 1, "A trap frame."),
+        (Tss, 2, "A task state segment frame."),
+        (NonFpo, 3, "A normal frame that uses a frame pointer."),
+    ]
+}
+
+/// An entry in an OMAP table, which maps the RVAs of an image that was rearranged after linking
+/// (for example by BBT) to the RVAs of the original image, or vice versa.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct OmapEntry {
+    /// The RVA this entry starts at.
+    pub rva: Rva,
+
+    /// The RVA that `rva` maps to, or 0 if the range doesn't map to anything.
+    pub rva_to: Rva,
+}
+
+/// An OmapToSrc or OmapFromSrc table.
+#[derive(Debug, Clone)]
+pub struct OmapTable {
+    /// The entries, sorted by `rva`.
+    pub entries: Vec<OmapEntry>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for OmapTable {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let mut entries = Vec::new();
+        while segment.remaining()? >= 8 {
+            entries.push(OmapEntry::try_from(segment)?);
+        }
+        Ok(Self { entries })
+    }
+}
+
+impl OmapTable {
+    /// Translates an RVA using the table. Returns `None` if the RVA is in a range that doesn't
+    /// map to anything (e.g. code that was removed), or if the translated RVA would overflow.
+    pub fn translate(&self, rva: Rva) -> Option<Rva> {
+        let index = self.entries.partition_point(|entry| entry.rva <= rva);
+        if index == 0 {
+            return None;
+        }
+        let entry = &self.entries[index - 1];
+        if entry.rva_to == 0 {
+            None
+        } else {
+            entry.rva_to.checked_add(rva - entry.rva)
+        }
+    }
+}
+
+//TODO figure out the format of these sections.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct DebugSubsection {}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct DebugSymbolSubsection {}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct DebugPrecompiledSubsection {}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct DebugTypeSubsection {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omap_translate() {
+        let table = OmapTable {
+            entries: vec![
+                OmapEntry {
+                    rva: 0x1000,
+                    rva_to: 0x5000,
+                },
+                OmapEntry {
+                    rva: 0x2000,
+                    rva_to: 0,
+                },
+                OmapEntry {
+                    rva: 0x3000,
+                    rva_to: 0xffff_fff0,
+                },
+            ],
+        };
+        assert_eq!(table.translate(0x0fff), None);
+        assert_eq!(table.translate(0x1010), Some(0x5010));
+        assert_eq!(table.translate(0x2004), None);
+        assert_eq!(table.translate(0x3008), Some(0xffff_fff8));
+        assert_eq!(table.translate(0x3020), None);
+    }
+}
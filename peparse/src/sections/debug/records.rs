@@ -0,0 +1,280 @@
+use crate::{
+    error::{Error, Result},
+    util::next_null_terminated,
+    Rva,
+};
+use core::convert::TryFrom;
+#[cfg(feature = "portable-pdb")]
+use flate2::read::DeflateDecoder;
+use segsource::{DataSegment, TryFromSegment};
+#[cfg(feature = "portable-pdb")]
+use std::io::Read;
+
+/// The signature of POGO data from a link-time code generation build.
+pub const POGO_LTCG_SIGNATURE: u32 = 0x4c544347;
+
+/// The signature of POGO data from an instrumented (/GENPROFILE) build.
+pub const POGO_PGI_SIGNATURE: u32 = 0x50474900;
+
+/// The signature of POGO data from a profile-optimized (/USEPROFILE) build.
+pub const POGO_PGU_SIGNATURE: u32 = 0x50475500;
+
+/// Profile guided optimization data, which lists the contributions that make up each section.
+#[derive(Debug, Clone)]
+pub struct Pogo {
+    /// Says what kind of build produced the data. See the `POGO_*_SIGNATURE` constants.
+    pub signature: u32,
+
+    pub entries: Vec<PogoEntry>,
+}
+
+/// A single contribution to a section, such as `.text$mn` or `.rdata$zzzdbg`.
+#[derive(Debug, Clone)]
+pub struct PogoEntry {
+    pub rva: Rva,
+    pub size: u32,
+    pub name: String,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for Pogo {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let signature = segment.next_u32()?;
+        let mut entries = Vec::new();
+        while segment.remaining()? >= 8 {
+            let rva = segment.next_u32()?;
+            let size = segment.next_u32()?;
+            let name = next_null_terminated(segment)?;
+            // Each name is null-terminated and then padded so the next entry is 4-byte aligned.
+            let padding = (4 - (name.len() + 1) % 4) % 4;
+            segment.next_n(min_remaining(segment, padding)?)?;
+            entries.push(PogoEntry {
+                rva,
+                size,
+                name: String::from_utf8(name)?,
+            });
+        }
+        Ok(Self { signature, entries })
+    }
+}
+
+/// Counts of the functions that were compiled with each security feature.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct VcFeature {
+    /// The number of object files built by compilers older than Visual C++ 11.
+    pub pre_vc11: u32,
+
+    /// The number of C and C++ object files.
+    pub c_and_cpp: u32,
+
+    /// The number of functions protected with /GS.
+    pub gs: u32,
+
+    /// The number of functions compiled with /sdl.
+    pub sdl: u32,
+
+    /// The number of functions compiled with /guardN.
+    pub guard_n: u32,
+}
+
+/// Deterministic build information. The hash is the same every time the image is built from the
+/// same inputs.
+#[derive(Debug, Clone)]
+pub struct Repro {
+    /// The hash of the inputs. Older linkers don't write one, in which case the image's timestamp
+    /// fields hold a hash instead.
+    pub hash: Option<Vec<u8>>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for Repro {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        if segment.remaining()? < 4 {
+            return Ok(Self { hash: None });
+        }
+        let size = segment.next_u32()? as usize;
+        Ok(Self {
+            hash: Some(segment.next_n(size)?.as_ref().to_vec()),
+        })
+    }
+}
+
+/// The signature of an embedded portable PDB ("MPDB").
+pub const EMBEDDED_PORTABLE_PDB_SIGNATURE: u32 = 0x4244504d;
+
+/// A portable PDB that's embedded in the image, as written by the .NET compilers.
+#[derive(Debug, Clone)]
+pub struct EmbeddedPortablePdb {
+    /// The size of the PDB once it's decompressed.
+    pub uncompressed_size: u32,
+
+    /// The deflate-compressed PDB.
+    pub compressed_data: Vec<u8>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for EmbeddedPortablePdb {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let signature = segment.next_u32()?;
+        if signature != EMBEDDED_PORTABLE_PDB_SIGNATURE {
+            return Err(Error::InvalidHeaderMagic {
+                expected: format!("0x{:08x}", EMBEDDED_PORTABLE_PDB_SIGNATURE),
+                received: format!("0x{:08x}", signature),
+            });
+        }
+        let uncompressed_size = segment.next_u32()?;
+        let remaining = segment.remaining()?;
+        Ok(Self {
+            uncompressed_size,
+            compressed_data: segment.next_n(remaining)?.as_ref().to_vec(),
+        })
+    }
+}
+
+impl EmbeddedPortablePdb {
+    /// Decompresses the PDB.
+    #[cfg(feature = "portable-pdb")]
+    pub fn decompress(&self) -> Result<Vec<u8>> {
+        // The size comes from the file, so it's only used to limit how much is decompressed. One
+        // byte more than it is read so that data that's too long is caught.
+        let mut pdb = Vec::new();
+        DeflateDecoder::new(self.compressed_data.as_slice())
+            .take(self.uncompressed_size as u64 + 1)
+            .read_to_end(&mut pdb)?;
+        if pdb.len() != self.uncompressed_size as usize {
+            return Err(Error::Other {
+                message: format!(
+                    "The embedded portable PDB decompressed to {} bytes, but should have been {}.",
+                    pdb.len(),
+                    self.uncompressed_size
+                ),
+            });
+        }
+        Ok(pdb)
+    }
+}
+
+/// The checksum of the PDB that goes with the image, which can be used to check that a PDB
+/// matches.
+#[derive(Debug, Clone)]
+pub struct PdbChecksum {
+    /// The name of the hash algorithm, such as "SHA256".
+    pub algorithm: String,
+
+    pub checksum: Vec<u8>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for PdbChecksum {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let algorithm = String::from_utf8(next_null_terminated(segment)?)?;
+        let remaining = segment.remaining()?;
+        Ok(Self {
+            algorithm,
+            checksum: segment.next_n(remaining)?.as_ref().to_vec(),
+        })
+    }
+}
+
+fn min_remaining(segment: &DataSegment<'_>, size: usize) -> Result<usize> {
+    Ok(size.min(segment.remaining()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn source(data: &[u8]) -> BytesSource {
+        BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little).unwrap()
+    }
+
+    #[test]
+    fn pogo_entries_are_padded_to_four_bytes() {
+        let mut data = POGO_LTCG_SIGNATURE.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0x00, 0x10, 0, 0, 0x20, 0, 0, 0]);
+        data.extend_from_slice(b".text$mn\0\0\0\0");
+        data.extend_from_slice(&[0x00, 0x20, 0, 0, 0x08, 0, 0, 0]);
+        data.extend_from_slice(b".rdata\0\0");
+        let pogo = Pogo::try_from(&source(&data).all().unwrap()).unwrap();
+        assert_eq!(pogo.signature, POGO_LTCG_SIGNATURE);
+        let entries: Vec<_> = pogo
+            .entries
+            .iter()
+            .map(|entry| (entry.rva, entry.size, entry.name.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![(0x1000, 0x20, ".text$mn"), (0x2000, 0x08, ".rdata")]
+        );
+    }
+
+    #[test]
+    fn vc_feature_counts() {
+        let data: Vec<u8> = (1..=5u32)
+            .flat_map(|count| count.to_le_bytes().to_vec())
+            .collect();
+        let feature = VcFeature::try_from(&source(&data).all().unwrap()).unwrap();
+        assert_eq!(
+            (
+                feature.pre_vc11,
+                feature.c_and_cpp,
+                feature.gs,
+                feature.sdl,
+                feature.guard_n
+            ),
+            (1, 2, 3, 4, 5)
+        );
+    }
+
+    #[test]
+    fn repro_hash_is_optional() {
+        let repro = Repro::try_from(&source(&[]).all().unwrap()).unwrap();
+        assert_eq!(repro.hash, None);
+        let repro =
+            Repro::try_from(&source(&[3, 0, 0, 0, 0xaa, 0xbb, 0xcc]).all().unwrap()).unwrap();
+        assert_eq!(repro.hash, Some(vec![0xaa, 0xbb, 0xcc]));
+    }
+
+    /// "BSJB" in a single stored (uncompressed) deflate block.
+    const COMPRESSED: [u8; 9] = [0x01, 0x04, 0x00, 0xfb, 0xff, b'B', b'S', b'J', b'B'];
+
+    #[cfg(feature = "portable-pdb")]
+    fn pdb(uncompressed_size: u32) -> EmbeddedPortablePdb {
+        EmbeddedPortablePdb {
+            uncompressed_size,
+            compressed_data: COMPRESSED.to_vec(),
+        }
+    }
+
+    #[cfg(feature = "portable-pdb")]
+    #[test]
+    fn decompresses_to_the_declared_size() {
+        assert_eq!(pdb(4).decompress().unwrap(), b"BSJB");
+    }
+
+    #[cfg(feature = "portable-pdb")]
+    #[test]
+    fn rejects_data_that_does_not_match_the_declared_size() {
+        assert!(pdb(3).decompress().is_err());
+        assert!(pdb(5).decompress().is_err());
+        assert!(pdb(0).decompress().is_err());
+    }
+
+    #[test]
+    fn embedded_portable_pdb_signature() {
+        let mut data = b"MPDB".to_vec();
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&COMPRESSED);
+        let pdb = EmbeddedPortablePdb::try_from(&source(&data).all().unwrap()).unwrap();
+        assert_eq!(pdb.uncompressed_size, 4);
+        assert_eq!(pdb.compressed_data, COMPRESSED);
+        assert!(EmbeddedPortablePdb::try_from(&source(b"BSJB\0\0\0\0").all().unwrap()).is_err());
+    }
+}
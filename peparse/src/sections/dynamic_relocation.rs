@@ -0,0 +1,587 @@
+use crate::{
+    error::{Error, Result},
+    util::next_different_sizes,
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
+
+pub const IMAGE_DYNAMIC_RELOCATION_GUARD_RF_PROLOGUE: u64 = 1;
+pub const IMAGE_DYNAMIC_RELOCATION_GUARD_RF_EPILOGUE: u64 = 2;
+pub const IMAGE_DYNAMIC_RELOCATION_GUARD_IMPORT_CONTROL_TRANSFER: u64 = 3;
+pub const IMAGE_DYNAMIC_RELOCATION_GUARD_INDIR_CONTROL_TRANSFER: u64 = 4;
+pub const IMAGE_DYNAMIC_RELOCATION_GUARD_SWITCHTABLE_BRANCH: u64 = 5;
+pub const IMAGE_DYNAMIC_RELOCATION_ARM64X: u64 = 6;
+pub const IMAGE_DYNAMIC_RELOCATION_FUNCTION_OVERRIDE: u64 = 7;
+pub const IMAGE_DYNAMIC_RELOCATION_ARM64_KERNEL_IMPORT_CALL_TRANSFER: u64 = 8;
+
+/// The size of an IMAGE_BASE_RELOCATION header.
+const BLOCK_HEADER_SIZE: u32 = 8;
+
+/// The dynamic value relocation table (DVRT), which describes code and data that the kernel (or
+/// loader) patches at runtime, such as retpoline and import call sites.
+#[derive(Debug, Clone)]
+pub struct DynamicRelocationTable {
+    /// The version of the table. Versions 1 and 2 are defined.
+    pub version: u32,
+
+    /// The size of the table, not including this header.
+    pub size: u32,
+
+    pub relocations: Vec<DynamicRelocation>,
+}
+
+impl<'s> TryFrom<(bool, &DataSegment<'s>)> for DynamicRelocationTable {
+    type Error = Error;
+
+    fn try_from((is_32_plus, segment): (bool, &DataSegment<'s>)) -> Result<Self> {
+        let version = segment.next_u32()?;
+        let size = segment.next_u32()?;
+        let entries = segment.next_n(size as usize)?;
+        let mut relocations = Vec::new();
+        while entries.remaining()? > 0 {
+            relocations.push(match version {
+                1 => DynamicRelocation::parse_v1(is_32_plus, &entries)?,
+                2 => DynamicRelocation::parse_v2(is_32_plus, &entries)?,
+                _ => {
+                    return Err(Error::InvalidConstant {
+                        value_given: version as u64,
+                        constant_type: "DynamicRelocationTable version".into(),
+                    })
+                }
+            });
+        }
+        Ok(Self {
+            version,
+            size,
+            relocations,
+        })
+    }
+}
+
+/// A single entry in the dynamic value relocation table, covering every fixup for one symbol.
+#[derive(Debug, Clone)]
+pub struct DynamicRelocation {
+    /// The symbol the fixups are for. This is one of the `IMAGE_DYNAMIC_RELOCATION_*` constants.
+    pub symbol: u64,
+
+    /// The symbol group. Version 2 only.
+    pub symbol_group: Option<u32>,
+
+    /// The entry's flags. Version 2 only.
+    pub flags: Option<u32>,
+
+    pub fixups: DynamicFixups,
+}
+
+impl DynamicRelocation {
+    fn parse_v1(is_32_plus: bool, segment: &DataSegment<'_>) -> Result<Self> {
+        let symbol = next_different_sizes::<u32, u64>(!is_32_plus, segment)?;
+        let base_reloc_size = segment.next_u32()?;
+        let fixup_data = segment.next_n(base_reloc_size as usize)?;
+        Ok(Self {
+            symbol,
+            symbol_group: None,
+            flags: None,
+            fixups: DynamicFixups::parse(symbol, &fixup_data)?,
+        })
+    }
+
+    /// Version 2 entries carry their own header size. The fixup info of version 2 entries isn't
+    /// documented, so it's kept as raw bytes.
+    fn parse_v2(is_32_plus: bool, segment: &DataSegment<'_>) -> Result<Self> {
+        let header_size = segment.next_u32()?;
+        let fixup_info_size = segment.next_u32()?;
+        let symbol = next_different_sizes::<u32, u64>(!is_32_plus, segment)?;
+        let symbol_group = segment.next_u32()?;
+        let flags = segment.next_u32()?;
+        let known_size = if is_32_plus { 24 } else { 20 };
+        if header_size > known_size {
+            segment.next_n((header_size - known_size) as usize)?;
+        }
+        let fixup_info = segment.next_n(fixup_info_size as usize)?;
+        Ok(Self {
+            symbol,
+            symbol_group: Some(symbol_group),
+            flags: Some(flags),
+            fixups: DynamicFixups::Other(fixup_info.as_ref().to_vec()),
+        })
+    }
+}
+
+/// The fixups for a single dynamic relocation entry, decoded according to its symbol.
+#[derive(Debug, Clone)]
+pub enum DynamicFixups {
+    ImportControlTransfer(Vec<DynamicRelocationPage<ImportControlTransfer>>),
+    IndirectControlTransfer(Vec<DynamicRelocationPage<IndirectControlTransfer>>),
+    SwitchableBranch(Vec<DynamicRelocationPage<SwitchableBranch>>),
+    Arm64X(Vec<DynamicRelocationPage<Arm64XFixup>>),
+    FunctionOverride(FunctionOverrides),
+
+    /// Fixups for a symbol whose format isn't known, as raw bytes.
+    Other(Vec<u8>),
+}
+
+impl DynamicFixups {
+    fn parse(symbol: u64, segment: &DataSegment<'_>) -> Result<Self> {
+        Ok(match symbol {
+            IMAGE_DYNAMIC_RELOCATION_GUARD_IMPORT_CONTROL_TRANSFER => {
+                Self::ImportControlTransfer(parse_pages(segment, |block| {
+                    let mut fixups = Vec::new();
+                    while block.remaining()? >= 4 {
+                        fixups.push(ImportControlTransfer::from(block.next_u32()?));
+                    }
+                    Ok(fixups)
+                })?)
+            }
+            IMAGE_DYNAMIC_RELOCATION_GUARD_INDIR_CONTROL_TRANSFER => {
+                Self::IndirectControlTransfer(parse_pages(segment, |block| {
+                    parse_u16_fixups(block, IndirectControlTransfer::from)
+                })?)
+            }
+            IMAGE_DYNAMIC_RELOCATION_GUARD_SWITCHTABLE_BRANCH => {
+                Self::SwitchableBranch(parse_pages(segment, |block| {
+                    parse_u16_fixups(block, SwitchableBranch::from)
+                })?)
+            }
+            IMAGE_DYNAMIC_RELOCATION_ARM64X => {
+                Self::Arm64X(parse_pages(segment, Arm64XFixup::parse_block)?)
+            }
+            IMAGE_DYNAMIC_RELOCATION_FUNCTION_OVERRIDE => {
+                Self::FunctionOverride(FunctionOverrides::try_from(segment)?)
+            }
+            _ => Self::Other(segment.as_ref().to_vec()),
+        })
+    }
+}
+
+/// The fixups that apply to a single page, in the same layout as a base relocation block.
+#[derive(Debug, Clone)]
+pub struct DynamicRelocationPage<T> {
+    /// The RVA of the page the fixups apply to.
+    pub page_rva: Rva,
+
+    pub fixups: Vec<T>,
+}
+
+impl<T> DynamicRelocationPage<T> {
+    /// Gets the RVA of a fixup, given its offset within the page.
+    #[inline]
+    pub fn rva_of(&self, page_offset: u16) -> Rva {
+        self.page_rva.wrapping_add(page_offset as Rva)
+    }
+}
+
+/// Parses a run of base relocation style blocks, using `parse_fixups` to decode the contents of
+/// each block.
+fn parse_pages<T, F>(
+    segment: &DataSegment<'_>,
+    parse_fixups: F,
+) -> Result<Vec<DynamicRelocationPage<T>>>
+where
+    F: Fn(&DataSegment<'_>) -> Result<Vec<T>>,
+{
+    let mut pages = Vec::new();
+    while segment.remaining()? >= BLOCK_HEADER_SIZE as usize {
+        let page_rva = segment.next_u32()?;
+        let block_size = segment.next_u32()?;
+        if block_size < BLOCK_HEADER_SIZE
+            || (block_size - BLOCK_HEADER_SIZE) as usize > segment.remaining()?
+        {
+            return Err(Error::InvalidRelocationBlock {
+                page_rva,
+                block_size,
+            });
+        }
+        let block = segment.next_n((block_size - BLOCK_HEADER_SIZE) as usize)?;
+        pages.push(DynamicRelocationPage {
+            page_rva,
+            fixups: parse_fixups(&block)?,
+        });
+    }
+    Ok(pages)
+}
+
+/// Parses a block of 16-bit fixups. Blocks are padded to a multiple of 4 bytes, so a trailing
+/// zero entry is ignored.
+fn parse_u16_fixups<T>(block: &DataSegment<'_>, convert: fn(u16) -> T) -> Result<Vec<T>> {
+    let mut fixups = Vec::new();
+    while block.remaining()? >= 2 {
+        let value = block.next_u16()?;
+        if value == 0 && block.remaining()? == 0 {
+            break;
+        }
+        fixups.push(convert(value));
+    }
+    Ok(fixups)
+}
+
+/// A call or jump through the IAT that can be rewritten into a direct call.
+#[derive(Debug, Clone)]
+pub struct ImportControlTransfer {
+    /// The offset of the instruction within the page.
+    pub page_offset: u16,
+
+    /// If this is a call (as opposed to a jump).
+    pub indirect_call: bool,
+
+    /// The index of the IAT entry the instruction goes through.
+    pub iat_index: u32,
+}
+
+impl From<u32> for ImportControlTransfer {
+    fn from(value: u32) -> Self {
+        Self {
+            page_offset: (value & 0xfff) as u16,
+            indirect_call: (value >> 12) & 1 == 1,
+            iat_index: value >> 13,
+        }
+    }
+}
+
+/// An indirect call or jump (e.g. through a retpoline) that can be patched.
+#[derive(Debug, Clone)]
+pub struct IndirectControlTransfer {
+    /// The offset of the instruction within the page.
+    pub page_offset: u16,
+
+    /// If this is a call (as opposed to a jump).
+    pub indirect_call: bool,
+
+    /// If the instruction has a REX.W prefix.
+    pub rex_w_prefix: bool,
+
+    /// If the instruction is preceded by a Control Flow Guard check.
+    pub cfg_check: bool,
+}
+
+impl From<u16> for IndirectControlTransfer {
+    fn from(value: u16) -> Self {
+        Self {
+            page_offset: value & 0xfff,
+            indirect_call: (value >> 12) & 1 == 1,
+            rex_w_prefix: (value >> 13) & 1 == 1,
+            cfg_check: (value >> 14) & 1 == 1,
+        }
+    }
+}
+
+/// A switch table branch that can be patched.
+#[derive(Debug, Clone)]
+pub struct SwitchableBranch {
+    /// The offset of the instruction within the page.
+    pub page_offset: u16,
+
+    /// The register that holds the jump target.
+    pub register_number: u8,
+}
+
+impl From<u16> for SwitchableBranch {
+    fn from(value: u16) -> Self {
+        Self {
+            page_offset: value & 0xfff,
+            register_number: (value >> 12) as u8,
+        }
+    }
+}
+
+/// A fixup that the loader applies to turn the native ARM64 view of an ARM64X image into the
+/// ARM64EC view.
+#[derive(Debug, Clone)]
+pub enum Arm64XFixup {
+    /// Fill `size` bytes with zeros.
+    ZeroFill { page_offset: u16, size: u8 },
+
+    /// Overwrite the data with `value`.
+    Value { page_offset: u16, value: Vec<u8> },
+
+    /// Add `delta` to the 32-bit value at the offset.
+    Delta { page_offset: u16, delta: i64 },
+}
+
+impl Arm64XFixup {
+    /// Applies the fixup to a mapped image, given the RVA of the page it's in.
+    pub fn apply(&self, image: &mut [u8], page_rva: Rva) -> Result<()> {
+        let (page_offset, size) = match self {
+            Self::ZeroFill { page_offset, size } => (*page_offset, *size as usize),
+            Self::Value { page_offset, value } => (*page_offset, value.len()),
+            Self::Delta { page_offset, .. } => (*page_offset, 4),
+        };
+        let rva = page_rva
+            .checked_add(page_offset as Rva)
+            .ok_or(Error::RelocationOutOfBounds { rva: page_rva })?;
+        let start = rva as usize;
+        let data = image
+            .get_mut(start..start + size)
+            .ok_or(Error::RelocationOutOfBounds { rva })?;
+        match self {
+            Self::ZeroFill { .. } => data.iter_mut().for_each(|byte| *byte = 0),
+            Self::Value { value, .. } => data.copy_from_slice(value),
+            Self::Delta { delta, .. } => {
+                let value = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                data.copy_from_slice(&(value as i64).wrapping_add(*delta).to_le_bytes()[..4]);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_block(block: &DataSegment<'_>) -> Result<Vec<Self>> {
+        let mut fixups = Vec::new();
+        while block.remaining()? >= 2 {
+            let header = block.next_u16()?;
+            if header == 0 && block.remaining()? == 0 {
+                break;
+            }
+            let page_offset = header & 0xfff;
+            let size_bits = (header >> 14) as u8;
+            fixups.push(match (header >> 12) & 0x3 {
+                0 => Self::ZeroFill {
+                    page_offset,
+                    size: 1 << size_bits,
+                },
+                1 => Self::Value {
+                    page_offset,
+                    value: block.next_n(1 << size_bits)?.as_ref().to_vec(),
+                },
+                2 => {
+                    // The low size bit is the sign, and the high one is the scale.
+                    let scale = if size_bits & 0x2 != 0 { 8 } else { 4 };
+                    let delta = block.next_u16()? as i64 * scale;
+                    Self::Delta {
+                        page_offset,
+                        delta: if size_bits & 0x1 != 0 { -delta } else { delta },
+                    }
+                }
+                kind => {
+                    return Err(Error::InvalidConstant {
+                        value_given: kind as u64,
+                        constant_type: "Arm64XFixup".into(),
+                    })
+                }
+            });
+        }
+        Ok(fixups)
+    }
+}
+
+/// The function override fixups, which let the kernel swap in alternate versions of functions.
+#[derive(Debug, Clone)]
+pub struct FunctionOverrides {
+    pub functions: Vec<FunctionOverride>,
+
+    /// The binary decision diagrams that decide which override to use. The raw bytes start at the
+    /// first BDD info, which is what `FunctionOverride::bdd_offset` is relative to.
+    pub bdd_data: Vec<u8>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for FunctionOverrides {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let overrides_size = segment.next_u32()?;
+        let overrides = segment.next_n(overrides_size as usize)?;
+        let mut functions = Vec::new();
+        while overrides.remaining()? > 0 {
+            functions.push(FunctionOverride::try_from(&overrides)?);
+        }
+        let remaining = segment.remaining()?;
+        Ok(Self {
+            functions,
+            bdd_data: segment.next_n(remaining)?.as_ref().to_vec(),
+        })
+    }
+}
+
+/// A single function that can be overridden.
+#[derive(Debug, Clone)]
+pub struct FunctionOverride {
+    /// The RVA of the original function.
+    pub original_rva: Rva,
+
+    /// The offset of the BDD that decides which override is used.
+    pub bdd_offset: u32,
+
+    /// The RVAs of the functions that can replace the original one.
+    pub override_rvas: Vec<Rva>,
+
+    /// The locations that need to be patched to point to the chosen override.
+    pub pages: Vec<DynamicRelocationPage<u16>>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for FunctionOverride {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let original_rva = segment.next_u32()?;
+        let bdd_offset = segment.next_u32()?;
+        let rva_size = segment.next_u32()?;
+        let base_reloc_size = segment.next_u32()?;
+        let override_rvas = (0..rva_size / 4)
+            .map(|_| Ok(segment.next_u32()?))
+            .collect::<Result<_>>()?;
+        let pages = parse_pages(&segment.next_n(base_reloc_size as usize)?, |block| {
+            parse_u16_fixups(block, |value| value & 0xfff)
+        })?;
+        Ok(Self {
+            original_rva,
+            bdd_offset,
+            override_rvas,
+            pages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse(is_32_plus: bool, version: u32, entries: &[u8]) -> Result<DynamicRelocationTable> {
+        let mut data = version.to_le_bytes().to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        data.extend_from_slice(entries);
+        let source = BytesSource::from_bytes(Bytes::from(data), Endidness::Little)?;
+        DynamicRelocationTable::try_from((is_32_plus, &source.all()?))
+    }
+
+    #[test]
+    fn v1_control_transfer_fixups() {
+        let entries = [
+            0x03, 0, 0, 0, 0, 0, 0, 0, 0x0c, 0, 0, 0, // Import control transfer
+            0x00, 0x10, 0, 0, 0x0c, 0, 0, 0, 0x23, 0xb1, 0, 0, //
+            0x04, 0, 0, 0, 0, 0, 0, 0, 0x0c, 0, 0, 0, // Indirect control transfer
+            0x00, 0x20, 0, 0, 0x0c, 0, 0, 0, 0x10, 0x70, 0, 0,
+        ];
+        let table = parse(true, 1, &entries).unwrap();
+        assert_eq!(table.version, 1);
+        assert_eq!(table.relocations.len(), 2);
+
+        let relocation = &table.relocations[0];
+        assert_eq!(
+            relocation.symbol,
+            IMAGE_DYNAMIC_RELOCATION_GUARD_IMPORT_CONTROL_TRANSFER
+        );
+        assert_eq!(relocation.symbol_group, None);
+        match &relocation.fixups {
+            DynamicFixups::ImportControlTransfer(pages) => {
+                assert_eq!(pages.len(), 1);
+                assert_eq!(pages[0].page_rva, 0x1000);
+                let fixup = &pages[0].fixups[0];
+                assert_eq!(pages[0].rva_of(fixup.page_offset), 0x1123);
+                assert!(fixup.indirect_call);
+                assert_eq!(fixup.iat_index, 5);
+            }
+            other => panic!("unexpected fixups {:?}", other),
+        }
+
+        match &table.relocations[1].fixups {
+            DynamicFixups::IndirectControlTransfer(pages) => {
+                // The padding entry at the end of the block is dropped.
+                assert_eq!(pages[0].fixups.len(), 1);
+                let fixup = &pages[0].fixups[0];
+                assert_eq!(fixup.page_offset, 0x10);
+                assert!(fixup.indirect_call && fixup.rex_w_prefix && fixup.cfg_check);
+            }
+            other => panic!("unexpected fixups {:?}", other),
+        }
+    }
+
+    #[test]
+    fn v2_entries_skip_unknown_header_fields() {
+        let entries = [
+            24, 0, 0, 0, 3, 0, 0, 0, // Header and fixup info sizes
+            9, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, // Symbol, group and flags
+            0xff, 0xff, 0xff, 0xff, // A field this crate doesn't know about
+            1, 2, 3,
+        ];
+        let table = parse(false, 2, &entries).unwrap();
+        assert_eq!(table.relocations.len(), 1);
+        let relocation = &table.relocations[0];
+        assert_eq!(relocation.symbol, 9);
+        assert_eq!(relocation.symbol_group, Some(2));
+        assert_eq!(relocation.flags, Some(1));
+        assert!(matches!(&relocation.fixups, DynamicFixups::Other(data) if data == &[1, 2, 3]));
+    }
+
+    #[test]
+    fn unknown_version_is_an_error() {
+        assert!(matches!(
+            parse(true, 3, &[0; 4]),
+            Err(Error::InvalidConstant { value_given: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn block_larger_than_its_fixups_is_an_error() {
+        let entries = [
+            0x04, 0, 0, 0, 0, 0, 0, 0, 0x0c, 0, 0, 0, //
+            0x00, 0x20, 0, 0, 0x40, 0, 0, 0, 0x10, 0x70, 0, 0,
+        ];
+        assert!(matches!(
+            parse(true, 1, &entries),
+            Err(Error::InvalidRelocationBlock {
+                page_rva: 0x2000,
+                block_size: 0x40,
+            })
+        ));
+    }
+
+    #[test]
+    fn arm64x_fixups() {
+        let entries = [
+            0x06, 0, 0, 0, 0, 0, 0, 0, 0x18, 0, 0, 0, //
+            0x00, 0x10, 0, 0, 0x18, 0, 0, 0, //
+            0x10, 0x80, // Zero fill four bytes
+            0x20, 0xd0, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, // Set eight bytes
+            0x30, 0xe0, 0x02, 0x00, // Subtract 2 * 8
+        ];
+        let table = parse(true, 1, &entries).unwrap();
+        let pages = match &table.relocations[0].fixups {
+            DynamicFixups::Arm64X(pages) => pages,
+            other => panic!("unexpected fixups {:?}", other),
+        };
+        assert_eq!(pages[0].page_rva, 0x1000);
+        assert_eq!(pages[0].fixups.len(), 3);
+
+        let mut image = vec![0xff; 0x1040];
+        image[0x1030..0x1034].copy_from_slice(&100u32.to_le_bytes());
+        for fixup in &pages[0].fixups {
+            fixup.apply(&mut image, pages[0].page_rva).unwrap();
+        }
+        assert_eq!(image[0x100f..0x1015], [0xff, 0, 0, 0, 0, 0xff]);
+        assert_eq!(
+            image[0x1020..0x1028],
+            [0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18]
+        );
+        assert_eq!(image[0x1030..0x1034], 84u32.to_le_bytes());
+    }
+
+    #[test]
+    fn arm64x_fixup_outside_of_the_image_is_an_error() {
+        let fixup = Arm64XFixup::ZeroFill {
+            page_offset: 0x10,
+            size: 4,
+        };
+        assert!(matches!(
+            fixup.apply(&mut [0; 0x100], 0x2000),
+            Err(Error::RelocationOutOfBounds { rva: 0x2010 })
+        ));
+        assert!(matches!(
+            fixup.apply(&mut [0; 0x100], u32::MAX - 8),
+            Err(Error::RelocationOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn arm64x_reserved_fixup_type_is_an_error() {
+        let entries = [
+            0x06, 0, 0, 0, 0, 0, 0, 0, 0x0c, 0, 0, 0, //
+            0x00, 0x10, 0, 0, 0x0c, 0, 0, 0, 0x00, 0x30, 0, 0,
+        ];
+        assert!(matches!(
+            parse(true, 1, &entries),
+            Err(Error::InvalidConstant { value_given: 3, .. })
+        ));
+    }
+}
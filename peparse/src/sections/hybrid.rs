@@ -0,0 +1,330 @@
+use crate::{
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::{DataSegment, TryFromSegment};
+use std::ops::Range;
+
+/// The metadata that hybrid images keep so that native ARM64 code and emulated x86 or x64 code
+/// can call each other. The load config's `chpe_metadata_pointer` points to it.
+#[derive(Debug, Clone)]
+pub enum HybridMetadata {
+    /// The metadata of an ARM64EC or ARM64X image.
+    Arm64Ec(Arm64EcMetadata),
+
+    /// The metadata of an x86 image with compiled hybrid ARM64 code (CHPE).
+    X86(ChpeX86Metadata),
+}
+
+impl HybridMetadata {
+    /// The number of bytes needed to parse the metadata, given its version.
+    pub fn size_for_version(is_x86: bool, version: u32) -> usize {
+        if is_x86 {
+            match version {
+                0 | 1 => 40,
+                2 => 44,
+                _ => 48,
+            }
+        } else if version >= 2 {
+            92
+        } else {
+            80
+        }
+    }
+
+    /// The RVA and number of entries of the code range map.
+    pub fn code_map(&self) -> (Rva, u32) {
+        match self {
+            Self::Arm64Ec(metadata) => (metadata.code_map, metadata.code_map_count),
+            Self::X86(metadata) => (
+                metadata.code_address_range_offset,
+                metadata.code_address_range_count,
+            ),
+        }
+    }
+}
+
+/// The IMAGE_ARM64EC_METADATA structure.
+#[derive(Debug, Clone)]
+pub struct Arm64EcMetadata {
+    pub version: u32,
+
+    /// The RVA of the code range map, which says what architecture each range of code is for.
+    pub code_map: Rva,
+
+    /// The number of entries in the code range map.
+    pub code_map_count: u32,
+
+    /// The RVA of the table that maps code ranges to their entry point thunks.
+    pub code_ranges_to_entry_points: Rva,
+
+    /// The RVA of the redirection metadata, which maps x64 entry points to the ARM64EC functions
+    /// they redirect to.
+    pub redirection_metadata: Rva,
+
+    pub os_arm64x_dispatch_call_no_redirect: Rva,
+    pub os_arm64x_dispatch_ret: Rva,
+    pub os_arm64x_dispatch_call: Rva,
+    pub os_arm64x_dispatch_icall: Rva,
+    pub os_arm64x_dispatch_icall_cfg: Rva,
+
+    /// The RVA of the entry point used for the alternate (x64) view of the image.
+    pub alternate_entry_point: Rva,
+
+    /// The RVA of the auxiliary IAT, which holds the ARM64EC view of the imports.
+    pub auxiliary_iat: Rva,
+
+    /// The number of entries in the code range to entry point table.
+    pub code_ranges_to_entry_points_count: u32,
+
+    /// The number of entries in the redirection metadata.
+    pub redirection_metadata_count: u32,
+
+    pub get_x64_information_function_pointer: Rva,
+    pub set_x64_information_function_pointer: Rva,
+
+    /// The RVA of the extra exception table entries for the x64 code in the image.
+    pub extra_rfe_table: Rva,
+
+    /// The size of the extra exception table, in bytes.
+    pub extra_rfe_table_size: u32,
+
+    pub os_arm64x_dispatch_fptr: Rva,
+
+    /// The RVA of the copy of the auxiliary IAT.
+    pub auxiliary_iat_copy: Rva,
+
+    /// The RVA of the auxiliary delay load IAT. Version 2 and up.
+    pub auxiliary_delayload_iat: Option<Rva>,
+
+    /// The RVA of the copy of the auxiliary delay load IAT. Version 2 and up.
+    pub auxiliary_delayload_iat_copy: Option<Rva>,
+
+    /// Version 2 and up.
+    pub hybrid_image_info_bitfield: Option<u32>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for Arm64EcMetadata {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let version = segment.next_u32()?;
+        let mut metadata = Self {
+            version,
+            code_map: segment.next_u32()?,
+            code_map_count: segment.next_u32()?,
+            code_ranges_to_entry_points: segment.next_u32()?,
+            redirection_metadata: segment.next_u32()?,
+            os_arm64x_dispatch_call_no_redirect: segment.next_u32()?,
+            os_arm64x_dispatch_ret: segment.next_u32()?,
+            os_arm64x_dispatch_call: segment.next_u32()?,
+            os_arm64x_dispatch_icall: segment.next_u32()?,
+            os_arm64x_dispatch_icall_cfg: segment.next_u32()?,
+            alternate_entry_point: segment.next_u32()?,
+            auxiliary_iat: segment.next_u32()?,
+            code_ranges_to_entry_points_count: segment.next_u32()?,
+            redirection_metadata_count: segment.next_u32()?,
+            get_x64_information_function_pointer: segment.next_u32()?,
+            set_x64_information_function_pointer: segment.next_u32()?,
+            extra_rfe_table: segment.next_u32()?,
+            extra_rfe_table_size: segment.next_u32()?,
+            os_arm64x_dispatch_fptr: segment.next_u32()?,
+            auxiliary_iat_copy: segment.next_u32()?,
+            auxiliary_delayload_iat: None,
+            auxiliary_delayload_iat_copy: None,
+            hybrid_image_info_bitfield: None,
+        };
+        if version >= 2 {
+            metadata.auxiliary_delayload_iat = Some(segment.next_u32()?);
+            metadata.auxiliary_delayload_iat_copy = Some(segment.next_u32()?);
+            metadata.hybrid_image_info_bitfield = Some(segment.next_u32()?);
+        }
+        Ok(metadata)
+    }
+}
+
+/// The IMAGE_CHPE_METADATA_X86 structure.
+#[derive(Debug, Clone)]
+pub struct ChpeX86Metadata {
+    pub version: u32,
+
+    /// The RVA of the code range map.
+    pub code_address_range_offset: Rva,
+
+    /// The number of entries in the code range map.
+    pub code_address_range_count: u32,
+
+    pub wow_a64_exception_handler_function_pointer: u32,
+    pub wow_a64_dispatch_call_function_pointer: u32,
+    pub wow_a64_dispatch_indirect_call_function_pointer: u32,
+    pub wow_a64_dispatch_indirect_call_cfg_function_pointer: u32,
+    pub wow_a64_dispatch_ret_function_pointer: u32,
+    pub wow_a64_dispatch_ret_leaf_function_pointer: u32,
+    pub wow_a64_dispatch_jump_function_pointer: u32,
+
+    /// Version 2 and up.
+    pub compiler_iat_pointer: Option<u32>,
+
+    /// Version 3 and up.
+    pub wow_a64_rdtsc_function_pointer: Option<u32>,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for ChpeX86Metadata {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let version = segment.next_u32()?;
+        let mut metadata = Self {
+            version,
+            code_address_range_offset: segment.next_u32()?,
+            code_address_range_count: segment.next_u32()?,
+            wow_a64_exception_handler_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_call_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_indirect_call_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_indirect_call_cfg_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_ret_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_ret_leaf_function_pointer: segment.next_u32()?,
+            wow_a64_dispatch_jump_function_pointer: segment.next_u32()?,
+            compiler_iat_pointer: None,
+            wow_a64_rdtsc_function_pointer: None,
+        };
+        if version >= 2 {
+            metadata.compiler_iat_pointer = Some(segment.next_u32()?);
+        }
+        if version >= 3 {
+            metadata.wow_a64_rdtsc_function_pointer = Some(segment.next_u32()?);
+        }
+        Ok(metadata)
+    }
+}
+
+/// The architecture of a range of code in a hybrid image.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CodeRangeArchitecture {
+    Arm64,
+    Arm64Ec,
+    Amd64,
+    X86,
+}
+
+/// A single entry from the code range map of a hybrid image.
+#[derive(Debug, Clone)]
+pub struct CodeRange {
+    pub range: Range<Rva>,
+    pub architecture: CodeRangeArchitecture,
+}
+
+impl CodeRange {
+    /// Parses an IMAGE_CHPE_RANGE_ENTRY. The low bits of the start offset hold the architecture,
+    /// which is encoded differently for CHPE x86 images.
+    pub fn parse(is_x86: bool, segment: &DataSegment<'_>) -> Result<Self> {
+        let start_offset = segment.next_u32()?;
+        let length = segment.next_u32()?;
+        let (start, architecture) = if is_x86 {
+            let architecture = if start_offset & 1 == 1 {
+                CodeRangeArchitecture::Arm64
+            } else {
+                CodeRangeArchitecture::X86
+            };
+            (start_offset & !1, architecture)
+        } else {
+            let architecture = match start_offset & 0x3 {
+                0 => CodeRangeArchitecture::Arm64,
+                1 => CodeRangeArchitecture::Arm64Ec,
+                2 => CodeRangeArchitecture::Amd64,
+                value => {
+                    return Err(Error::InvalidConstant {
+                        value_given: value as u64,
+                        constant_type: "CodeRangeArchitecture".into(),
+                    })
+                }
+            };
+            (start_offset & !0x3, architecture)
+        };
+        let end = start
+            .checked_add(length)
+            .ok_or(Error::InvalidRva { rva: start })?;
+        Ok(Self {
+            range: start..end,
+            architecture,
+        })
+    }
+}
+
+/// Maps a range of ARM64EC code to the entry point thunk that's used when x64 code calls into
+/// it.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct CodeRangeEntryPoint {
+    pub start_rva: Rva,
+    pub end_rva: Rva,
+    pub entry_point: Rva,
+}
+
+/// Maps an x64 entry point to the ARM64EC function that it redirects to.
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct RedirectionEntry {
+    pub source: Rva,
+    pub destination: Rva,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse_range(is_x86: bool, start_offset: u32, length: u32) -> Result<CodeRange> {
+        let mut data = start_offset.to_le_bytes().to_vec();
+        data.extend_from_slice(&length.to_le_bytes());
+        let source = BytesSource::from_bytes(Bytes::from(data), Endidness::Little)?;
+        CodeRange::parse(is_x86, &source.all()?)
+    }
+
+    #[test]
+    fn arm64ec_code_range_architectures() {
+        let range = parse_range(false, 0x1000, 0x200).unwrap();
+        assert_eq!(range.range, 0x1000..0x1200);
+        assert_eq!(range.architecture, CodeRangeArchitecture::Arm64);
+        let range = parse_range(false, 0x2001, 0x100).unwrap();
+        assert_eq!(range.range, 0x2000..0x2100);
+        assert_eq!(range.architecture, CodeRangeArchitecture::Arm64Ec);
+        let range = parse_range(false, 0x3002, 0x100).unwrap();
+        assert_eq!(range.range, 0x3000..0x3100);
+        assert_eq!(range.architecture, CodeRangeArchitecture::Amd64);
+        assert!(matches!(
+            parse_range(false, 0x4003, 0x100),
+            Err(Error::InvalidConstant { value_given: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn chpe_x86_code_range_architectures() {
+        let range = parse_range(true, 0x1001, 0x200).unwrap();
+        assert_eq!(range.range, 0x1000..0x1200);
+        assert_eq!(range.architecture, CodeRangeArchitecture::Arm64);
+        // Only the low bit is used, so the second one is part of the address.
+        let range = parse_range(true, 0x2002, 0x100).unwrap();
+        assert_eq!(range.range, 0x2002..0x2102);
+        assert_eq!(range.architecture, CodeRangeArchitecture::X86);
+    }
+
+    #[test]
+    fn code_range_past_the_end_of_the_address_space_is_an_error() {
+        assert!(matches!(
+            parse_range(false, 0xffff_f000, 0x2000),
+            Err(Error::InvalidRva { rva: 0xffff_f000 })
+        ));
+    }
+
+    #[test]
+    fn metadata_size_depends_on_the_version() {
+        assert_eq!(HybridMetadata::size_for_version(true, 1), 40);
+        assert_eq!(HybridMetadata::size_for_version(true, 2), 44);
+        assert_eq!(HybridMetadata::size_for_version(true, 4), 48);
+        assert_eq!(HybridMetadata::size_for_version(false, 1), 80);
+        assert_eq!(HybridMetadata::size_for_version(false, 2), 92);
+    }
+}
@@ -1,13 +1,21 @@
-use crate::{error::Error, util::next_different_sizes, Va};
-use segsource::TryFromSegment;
+use crate::{
+    error::{Error, Result},
+    Rva, Va,
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
 
 pub const IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT: u32 = 28;
 
-#[derive(TryFromSegment, Debug, Clone)]
-#[from_seg(error(crate::Error), also_needs(is_32_plus: bool))]
+/// The load configuration structure (IMAGE_LOAD_CONFIG_DIRECTORY).
+///
+/// The structure has grown over time, and the leading `size` field says how much of it is
+/// actually present. Any field that lies past the end of the structure is set to 0, which is how
+/// the loader treats them as well.
+#[derive(Debug, Clone, Default)]
 pub struct LoadConfig {
-    /// Flags that indicate attributes of the file, currently unused.
-    pub characteristics: u32,
+    /// The size of the structure, in bytes.
+    pub size: u32,
 
     /// Date and time stamp value. The value is represented in the number of seconds that have
     /// elapsed since midnight (00:00:00), January 1, 1970, Universal Coordinated Time, according to
@@ -30,29 +38,23 @@ pub struct LoadConfig {
     pub critical_section_default_timeout: u32,
 
     /// Memory that must be freed before it is returned to the system, in bytes.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub de_commit_free_block_threshold: u64,
 
     /// Total amount of free memory, in bytes.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub de_commit_total_free_threshold: u64,
 
     /// [x86 only] The VA of a list of addresses where the LOCK prefix is used so that they can be
     /// replaced with NOP on single processor machines.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub lock_prefix_table: Va,
 
     /// Maximum allocation size, in bytes.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub maximum_allocation_size: u64,
 
     /// Maximum virtual memory size, in bytes.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub virtual_memory_threshold: u64,
 
     /// Setting this field to a non-zero value is equivalent to calling SetProcessAffinityMask with
     /// this value during process startup (.exe only)
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub process_affinity_mask: u64,
 
     /// Process heap flags that correspond to the first argument of the HeapCreate function. These
@@ -62,61 +64,275 @@ pub struct LoadConfig {
     /// The service pack version identifier.
     pub csd_version: u16,
 
-    /// Must be zero.
-    pub reserved: u16,
+    /// The default load flags used when the operating system resolves the statically linked
+    /// imports of the module.
+    pub dependent_load_flags: u16,
 
     /// Reserved for use by the system.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub edit_list: u64,
 
     /// A pointer to a cookie that is used by Visual C++ or GS implementation.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub security_cookie: u64,
 
     /// [x86 only] The VA of the sorted table of RVAs of each valid, unique SE handler in the image.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub se_handler_table: Va,
 
     /// [x86 only] The count of unique handlers in the table.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub se_handler_count: u64,
 
     /// The VA where Control Flow Guard check-function pointer is stored.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_cf_check_function_pointer: Va,
 
     /// The VA where Control Flow Guard dispatch-function pointer is stored.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_cf_dispatch_function_pointer: Va,
 
     /// The VA of the sorted table of RVAs of each Control Flow Guard function in the image.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_cf_function_table: Va,
 
     /// The count of unique RVAs in the above table.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_cf_function_count: u64,
 
     /// Control Flow Guard related flags.
     pub guard_flags: GuardFlags,
 
-    //TODO has a width of 12
-    //pub code_integrity: u
+    /// Code integrity information.
+    pub code_integrity: CodeIntegrity,
+
     /// The VA where Control Flow Guard address taken IAT table is stored.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_address_taken_iat_entry_table: Va,
 
     /// The count of unique RVAs in the above table.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_address_taken_iat_entry_count: u64,
 
     /// The VA where Control Flow Guard long jump target table is stored.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_long_jump_target_table: Va,
 
     /// The count of unique RVAs in the above table.
-    #[from_seg(parser(next_different_sizes::<u32, u64>(!is_32_plus, &segment)))]
     pub guard_long_jump_target_count: u64,
+
+    /// The VA of the dynamic value relocation table. Only used by older images; newer ones use
+    /// `dynamic_value_reloc_table_offset` and `dynamic_value_reloc_table_section` instead.
+    pub dynamic_value_reloc_table: Va,
+
+    /// The VA of the hybrid (CHPE or ARM64EC) metadata.
+    pub chpe_metadata_pointer: Va,
+
+    /// The VA of the Return Flow Guard failure routine.
+    pub guard_rf_failure_routine: Va,
+
+    /// The VA where the Return Flow Guard failure routine's function pointer is stored.
+    pub guard_rf_failure_routine_function_pointer: Va,
+
+    /// The offset of the dynamic value relocation table, relative to the start of the section
+    /// it's in.
+    pub dynamic_value_reloc_table_offset: u32,
+
+    /// The 1-based index of the section that contains the dynamic value relocation table.
+    pub dynamic_value_reloc_table_section: u16,
+
+    /// Must be zero.
+    pub reserved2: u16,
+
+    /// The VA where the Return Flow Guard stack pointer verification function's pointer is stored.
+    pub guard_rf_verify_stack_pointer_function_pointer: Va,
+
+    /// The offset of the hot patch table.
+    pub hot_patch_table_offset: u32,
+
+    /// Must be zero.
+    pub reserved3: u32,
+
+    /// The VA of the enclave configuration.
+    pub enclave_configuration_pointer: Va,
+
+    /// The VA of the volatile metadata.
+    pub volatile_metadata_pointer: Va,
+
+    /// The VA of the sorted table of RVAs of each valid exception handling continuation target.
+    pub guard_eh_continuation_table: Va,
+
+    /// The count of unique RVAs in the above table.
+    pub guard_eh_continuation_count: u64,
+
+    /// The VA where the eXtended Flow Guard check-function pointer is stored.
+    pub guard_xfg_check_function_pointer: Va,
+
+    /// The VA where the eXtended Flow Guard dispatch-function pointer is stored.
+    pub guard_xfg_dispatch_function_pointer: Va,
+
+    /// The VA where the eXtended Flow Guard table dispatch-function pointer is stored.
+    pub guard_xfg_table_dispatch_function_pointer: Va,
+
+    /// The VA of the failure mode CastGuard uses when the OS determines it.
+    pub cast_guard_os_determined_failure_mode: Va,
+
+    /// The VA where the guarded memcpy function pointer is stored.
+    pub guard_memcpy_function_pointer: Va,
+
+    /// The VA of the UMA function pointers.
+    pub uma_function_pointers: Va,
+}
+
+impl LoadConfig {
+    /// The size of the newest version of the structure that this crate knows about.
+    pub fn known_size(is_32_plus: bool) -> usize {
+        64 + 33 * if is_32_plus { 8 } else { 4 }
+    }
+
+    /// If the structure is large enough to include a field ending `end` bytes into it.
+    #[inline]
+    pub fn includes(&self, end: usize) -> bool {
+        self.size as usize >= end
+    }
+}
+
+/// Reads the fields of a structure that might be shorter than expected, returning 0 for anything
+/// past its end.
+struct SizedReader<'a, 's> {
+    segment: &'a DataSegment<'s>,
+    is_32_plus: bool,
+}
+
+impl<'a, 's> SizedReader<'a, 's> {
+    fn has(&self, size: usize) -> Result<bool> {
+        Ok(self.segment.remaining()? >= size)
+    }
+
+    fn u16(&self) -> Result<u16> {
+        if self.has(2)? {
+            Ok(self.segment.next_u16()?)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn u32(&self) -> Result<u32> {
+        if self.has(4)? {
+            Ok(self.segment.next_u32()?)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn pointer(&self) -> Result<u64> {
+        if !self.is_32_plus {
+            Ok(self.u32()? as u64)
+        } else if self.has(8)? {
+            Ok(self.segment.next_u64()?)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+impl<'s> TryFrom<(bool, &DataSegment<'s>)> for LoadConfig {
+    type Error = Error;
+
+    fn try_from((is_32_plus, segment): (bool, &DataSegment<'s>)) -> Result<Self> {
+        let reader = SizedReader {
+            segment,
+            is_32_plus,
+        };
+        let mut config = Self {
+            size: reader.u32()?,
+            time_date_stamp: reader.u32()?,
+            major_version: reader.u16()?,
+            minor_version: reader.u16()?,
+            global_flags_clear: reader.u32()?,
+            global_flags_set: reader.u32()?,
+            critical_section_default_timeout: reader.u32()?,
+            de_commit_free_block_threshold: reader.pointer()?,
+            de_commit_total_free_threshold: reader.pointer()?,
+            lock_prefix_table: reader.pointer()?,
+            maximum_allocation_size: reader.pointer()?,
+            virtual_memory_threshold: reader.pointer()?,
+            ..Self::default()
+        };
+        // The heap flags and affinity mask are in a different order in the 32-bit structure.
+        if is_32_plus {
+            config.process_affinity_mask = reader.pointer()?;
+            config.process_heap_flags = reader.u32()?;
+        } else {
+            config.process_heap_flags = reader.u32()?;
+            config.process_affinity_mask = reader.pointer()?;
+        }
+        Ok(Self {
+            csd_version: reader.u16()?,
+            dependent_load_flags: reader.u16()?,
+            edit_list: reader.pointer()?,
+            security_cookie: reader.pointer()?,
+            se_handler_table: reader.pointer()?,
+            se_handler_count: reader.pointer()?,
+            guard_cf_check_function_pointer: reader.pointer()?,
+            guard_cf_dispatch_function_pointer: reader.pointer()?,
+            guard_cf_function_table: reader.pointer()?,
+            guard_cf_function_count: reader.pointer()?,
+            guard_flags: GuardFlags::from(reader.u32()?),
+            code_integrity: CodeIntegrity {
+                flags: reader.u16()?,
+                catalog: reader.u16()?,
+                catalog_offset: reader.u32()?,
+                reserved: reader.u32()?,
+            },
+            guard_address_taken_iat_entry_table: reader.pointer()?,
+            guard_address_taken_iat_entry_count: reader.pointer()?,
+            guard_long_jump_target_table: reader.pointer()?,
+            guard_long_jump_target_count: reader.pointer()?,
+            dynamic_value_reloc_table: reader.pointer()?,
+            chpe_metadata_pointer: reader.pointer()?,
+            guard_rf_failure_routine: reader.pointer()?,
+            guard_rf_failure_routine_function_pointer: reader.pointer()?,
+            dynamic_value_reloc_table_offset: reader.u32()?,
+            dynamic_value_reloc_table_section: reader.u16()?,
+            reserved2: reader.u16()?,
+            guard_rf_verify_stack_pointer_function_pointer: reader.pointer()?,
+            hot_patch_table_offset: reader.u32()?,
+            reserved3: reader.u32()?,
+            enclave_configuration_pointer: reader.pointer()?,
+            volatile_metadata_pointer: reader.pointer()?,
+            guard_eh_continuation_table: reader.pointer()?,
+            guard_eh_continuation_count: reader.pointer()?,
+            guard_xfg_check_function_pointer: reader.pointer()?,
+            guard_xfg_dispatch_function_pointer: reader.pointer()?,
+            guard_xfg_table_dispatch_function_pointer: reader.pointer()?,
+            cast_guard_os_determined_failure_mode: reader.pointer()?,
+            guard_memcpy_function_pointer: reader.pointer()?,
+            uma_function_pointers: reader.pointer()?,
+            ..config
+        })
+    }
+}
+
+impl Default for GuardFlags {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// A single entry from the SafeSEH handler table of an x86 image.
+#[derive(Debug, Clone)]
+pub struct SafeSehHandler {
+    /// The RVA of the handler.
+    pub rva: Rva,
+
+    /// The name of the section that contains the handler, if any.
+    pub section: Option<String>,
+}
+
+/// The IMAGE_LOAD_CONFIG_CODE_INTEGRITY structure.
+#[derive(Debug, Clone, Default)]
+pub struct CodeIntegrity {
+    /// Flags to indicate if CI information is available, etc.
+    pub flags: u16,
+
+    /// 0xFFFF means not available.
+    pub catalog: u16,
+
+    pub catalog_offset: u32,
+
+    /// Additional bitmask to be defined later.
+    pub reserved: u32,
 }
 
 flags! {
@@ -133,6 +349,172 @@ flags! {
         (CfExportSuppressionInfoPresent, 0x00004000, "Module contains suppressed export information. This also infers that the address taken IAT table is also present in the load config."),
         (CfEnableExportSuppression, 0x00008000, "Module enables suppression of exports."),
         (CfLongjumpTablePresent, 0x00010000, "Module contains longjmp target information."),
+        (RfInstrumented, 0x00020000, "Module contains return flow instrumentation and metadata."),
+        (RfEnable, 0x00040000, "Module requests that the OS enable return flow protection."),
+        (RfStrict, 0x00080000, "Module requests that the OS enable return flow protection in strict mode."),
+        (RetpolinePresent, 0x00100000, "Module was built with retpoline support."),
+        (EhContinuationTablePresentV1, 0x00200000, "Deprecated flag that was used for the exception handling continuation table."),
+        (EhContinuationTablePresent, 0x00400000, "Module contains EH continuation target information."),
+        (XfgEnabled, 0x00800000, "Module was built with eXtended Flow Guard support."),
+        (CastGuardPresent, 0x01000000, "Module has CastGuard instrumentation present."),
+        (MemcpyPresent, 0x02000000, "Module has guarded memcpy instrumentation present."),
         (CfFunctionTableSizeMask, 0xf0000000, "Mask for the subfield that contains the stride of Control Flow Guard function table entries (that is, the additional count of bytes per table entry)."),
     ]
 }
+
+impl GuardFlags {
+    /// The number of extra bytes of metadata that follow each RVA in the Control Flow Guard
+    /// tables.
+    #[inline]
+    pub fn function_table_stride(&self) -> usize {
+        ((self.bits() & Self::CfFunctionTableSizeMask.bits())
+            >> IMAGE_GUARD_CF_FUNCTION_TABLE_SIZE_SHIFT) as usize
+    }
+}
+
+flags! {
+    name: GuardFunctionFlags,
+    doc: "The metadata flags stored in the first extra byte of a Control Flow Guard table entry.",
+    value_type: u8,
+    items: [
+        (FidSuppressed, 0x01, "The call target is explicitly suppressed (do not treat it as valid for purposes of CFG)."),
+        (ExportSuppressed, 0x02, "The call target is export suppressed."),
+        (FidLangExcptHandler, 0x04, "The call target is a language exception handler."),
+        (FidXfg, 0x08, "The call target supports eXtended Flow Guard. Its type hash is stored in the 8 bytes immediately before it."),
+    ]
+}
+
+/// A single entry in one of the Control Flow Guard tables (the function table, the address taken
+/// IAT table, the long jump target table or the EH continuation table).
+#[derive(Debug, Clone)]
+pub struct GuardTableEntry {
+    /// The RVA of the target.
+    pub rva: Rva,
+
+    /// The entry's metadata flags, if the table has any.
+    pub flags: GuardFunctionFlags,
+}
+
+impl<'s> TryFrom<(usize, &DataSegment<'s>)> for GuardTableEntry {
+    type Error = Error;
+
+    fn try_from((stride, segment): (usize, &DataSegment<'s>)) -> Result<Self> {
+        let rva = segment.next_u32()?;
+        let flags = if stride > 0 {
+            let metadata = segment.next_n(stride)?;
+            GuardFunctionFlags::from(metadata.as_ref()[0])
+        } else {
+            GuardFunctionFlags::empty()
+        };
+        Ok(Self { rva, flags })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse(is_32_plus: bool, data: &[u8]) -> LoadConfig {
+        let source =
+            BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little).unwrap();
+        LoadConfig::try_from((is_32_plus, &source.all().unwrap())).unwrap()
+    }
+
+    fn put(data: &mut [u8], offset: usize, value: &[u8]) {
+        data[offset..offset + value.len()].copy_from_slice(value);
+    }
+
+    #[test]
+    fn known_sizes() {
+        assert_eq!(LoadConfig::known_size(true), 0x148);
+        assert_eq!(LoadConfig::known_size(false), 0xc4);
+    }
+
+    #[test]
+    fn fields_past_the_end_of_an_old_structure_are_zero() {
+        // The structure as of Windows 8.1, which ends with the guard flags.
+        let mut data = vec![0; 148];
+        put(&mut data, 0, &148u32.to_le_bytes());
+        put(&mut data, 64, &0x1234u64.to_le_bytes());
+        put(&mut data, 72, &0x5678u32.to_le_bytes());
+        put(&mut data, 88, &0x1_4000_3000u64.to_le_bytes());
+        put(&mut data, 128, &0x1_4000_4000u64.to_le_bytes());
+        put(&mut data, 136, &3u64.to_le_bytes());
+        put(&mut data, 144, &0x0000_0500u32.to_le_bytes());
+        let config = parse(true, &data);
+        assert_eq!(config.size, 148);
+        assert!(config.includes(148));
+        assert!(!config.includes(149));
+        assert_eq!(config.process_affinity_mask, 0x1234);
+        assert_eq!(config.process_heap_flags, 0x5678);
+        assert_eq!(config.security_cookie, 0x1_4000_3000);
+        assert_eq!(config.guard_cf_function_table, 0x1_4000_4000);
+        assert_eq!(config.guard_cf_function_count, 3);
+        assert_eq!(
+            config.guard_flags,
+            GuardFlags::CfInstrumented | GuardFlags::CfFunctionTablePresent
+        );
+        assert_eq!(config.code_integrity.catalog, 0);
+        assert_eq!(config.dynamic_value_reloc_table, 0);
+        assert_eq!(config.uma_function_pointers, 0);
+    }
+
+    #[test]
+    fn truncated_field_reads_as_zero() {
+        // Two bytes of the time stamp, which isn't enough for it.
+        let config = parse(false, &[0x06, 0x00, 0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(config.size, 6);
+        assert_eq!(config.time_date_stamp, 0);
+    }
+
+    #[test]
+    fn function_table_stride_comes_from_the_top_bits() {
+        assert_eq!(GuardFlags::from(0x0000_0500).function_table_stride(), 0);
+        assert_eq!(GuardFlags::from(0x1000_0500).function_table_stride(), 1);
+        assert_eq!(GuardFlags::from(0xf000_0000).function_table_stride(), 15);
+    }
+
+    #[test]
+    fn guard_table_entries_skip_the_stride() {
+        let source = BytesSource::from_bytes(
+            Bytes::from_static(&[
+                0x00, 0x10, 0x00, 0x00, 0x05, 0xaa, 0xaa, //
+                0x00, 0x20, 0x00, 0x00, 0x08, 0xbb, 0xbb,
+            ]),
+            Endidness::Little,
+        )
+        .unwrap();
+        let segment = source.all().unwrap();
+        let first = GuardTableEntry::try_from((3, &segment)).unwrap();
+        let second = GuardTableEntry::try_from((3, &segment)).unwrap();
+        assert_eq!(first.rva, 0x1000);
+        assert_eq!(
+            first.flags,
+            GuardFunctionFlags::FidSuppressed | GuardFunctionFlags::FidLangExcptHandler
+        );
+        assert_eq!(second.rva, 0x2000);
+        assert_eq!(second.flags, GuardFunctionFlags::FidXfg);
+        assert!(GuardTableEntry::try_from((3, &segment)).is_err());
+    }
+
+    #[test]
+    fn x86_structure_has_the_safe_seh_table() {
+        // The structure as of Windows XP, which ends with the SafeSEH handler count.
+        let mut data = vec![0; 72];
+        put(&mut data, 0, &72u32.to_le_bytes());
+        put(&mut data, 44, &0x5678u32.to_le_bytes());
+        put(&mut data, 48, &0x1234u32.to_le_bytes());
+        put(&mut data, 60, &0x0040_3000u32.to_le_bytes());
+        put(&mut data, 64, &0x0040_4000u32.to_le_bytes());
+        put(&mut data, 68, &5u32.to_le_bytes());
+        let config = parse(false, &data);
+        assert_eq!(config.process_heap_flags, 0x5678);
+        assert_eq!(config.process_affinity_mask, 0x1234);
+        assert_eq!(config.security_cookie, 0x40_3000);
+        assert_eq!(config.se_handler_table, 0x40_4000);
+        assert_eq!(config.se_handler_count, 5);
+        assert_eq!(config.guard_cf_check_function_pointer, 0);
+    }
+}
@@ -77,8 +77,11 @@ macro_rules! impl_section_specifics {
 }
 
 pub mod debug;
+pub mod dynamic_relocation;
 pub mod export;
+pub mod hybrid;
 pub mod import;
+pub mod load_config;
 pub mod pdata;
 pub mod relocation;
 pub mod resource;
@@ -215,31 +218,89 @@ pub struct SectionHeader {
     pub characteristics: SectionFlags,
 }
 
+/// If the file alignment is at least this big, the loader rounds each section's raw data pointer
+/// down to a multiple of it.
+const LOADER_FILE_ALIGNMENT: u32 = 0x200;
+
 impl SectionHeader {
-    fn get_read_size(&self, pe_header: &PeHeader) -> u32 {
+    /// The file offset that the loader actually starts reading the section's data from.
+    pub fn loader_pointer_to_raw_data(&self, pe_header: &PeHeader) -> u32 {
+        let file_alignment = pe_header.optional_header.windows_specific.file_alignment;
+        if file_alignment < LOADER_FILE_ALIGNMENT {
+            self.pointer_to_raw_data
+        } else {
+            self.pointer_to_raw_data & !(LOADER_FILE_ALIGNMENT - 1)
+        }
+    }
+
+    /// The size of the section once it's loaded into memory, rounded up to the section alignment.
+    pub fn loader_virtual_size(&self, pe_header: &PeHeader) -> Result<u32> {
+        let section_alignment = pe_header.optional_header.windows_specific.section_alignment;
+        if self.virtual_size == 0 {
+            self.align(self.size_of_raw_data, section_alignment)
+        } else {
+            self.align(self.virtual_size, section_alignment)
+        }
+    }
+
+    /// The number of bytes that the loader reads from the file, starting at
+    /// [`loader_pointer_to_raw_data`](Self::loader_pointer_to_raw_data). The rest of the section
+    /// is zero-filled.
+    pub fn loader_read_size(&self, pe_header: &PeHeader) -> Result<u32> {
+        if self.size_of_raw_data == 0 {
+            return Ok(0);
+        }
+        let file_alignment = pe_header.optional_header.windows_specific.file_alignment;
+        Ok(min(
+            self.raw_data_end(file_alignment)?
+                .saturating_sub(self.loader_pointer_to_raw_data(pe_header)),
+            self.loader_virtual_size(pe_header)?,
+        ))
+    }
+
+    /// The end of the section's raw data in the file, rounded up to the file alignment.
+    #[inline]
+    fn raw_data_end(&self, file_alignment: u32) -> Result<u32> {
+        let end = self
+            .pointer_to_raw_data
+            .checked_add(self.size_of_raw_data)
+            .ok_or_else(|| self.invalid())?;
+        self.align(end, file_alignment)
+    }
+
+    /// The number of bytes of the file that belong to the section when looking for the overlay.
+    /// This isn't the same as [`loader_read_size`](Self::loader_read_size): the sizes are rounded
+    /// to 0x100 bytes rather than the section alignment, which is what other tools (pefile in
+    /// particular) use to find where the overlay starts, so the overlay matches theirs.
+    fn overlay_read_size(&self, pe_header: &PeHeader) -> Result<u32> {
         let file_alignment = pe_header.optional_header.windows_specific.file_alignment;
         let read_size = min(
-            align(
-                self.pointer_to_raw_data + self.size_of_raw_data,
-                file_alignment,
-            ) - self.get_aligned_pointer_to_raw(),
-            align(self.size_of_raw_data, 0x100),
+            self.raw_data_end(file_alignment)?
+                .saturating_sub(self.pointer_to_raw_data & !0x1ff),
+            self.align(self.size_of_raw_data, 0x100)?,
         );
         if self.virtual_size != 0 {
-            min(read_size, align(self.virtual_size, 0x100))
+            Ok(min(read_size, self.align(self.virtual_size, 0x100)?))
         } else {
-            read_size
+            Ok(read_size)
         }
     }
 
     #[inline]
-    fn get_aligned_pointer_to_raw(&self) -> u32 {
-        self.pointer_to_raw_data & !0x1ff
+    pub(crate) fn calc_overlay_offset(&self, pe_header: &PeHeader) -> Result<u64> {
+        Ok(self.overlay_read_size(pe_header)? as u64 + (self.pointer_to_raw_data & !0x1ff) as u64)
     }
 
     #[inline]
-    pub(crate) fn calc_overlay_offset(&self, pe_header: &PeHeader) -> u64 {
-        self.get_read_size(pe_header) as u64 + self.get_aligned_pointer_to_raw() as u64
+    fn align(&self, value: u32, align_to: u32) -> Result<u32> {
+        align(value, align_to).ok_or_else(|| self.invalid())
+    }
+
+    #[inline]
+    fn invalid(&self) -> Error {
+        Error::InvalidSectionHeader {
+            name: self.name.clone(),
+        }
     }
 }
 
@@ -278,12 +339,10 @@ impl SectionInfo for UnknownSection {
 
 #[derive(Debug)]
 pub enum AnySection {
-    Debug(debug::DebugSection),
     Export(export::ExportSection),
     Import(import::ImportSection),
     Tls(tls::TlsSection),
     Resource(resource::ResourceSection),
-    Relocation(relocation::RelocationSection),
     Unknown(UnknownSection),
 }
 
@@ -296,12 +355,10 @@ impl SectionInfo for AnySection {
     #[inline]
     fn name(&self) -> &str {
         match self {
-            Self::Debug(value) => value.name(),
             Self::Export(value) => value.name(),
             Self::Import(value) => value.name(),
             Self::Tls(value) => value.name(),
             Self::Resource(value) => value.name(),
-            Self::Relocation(value) => value.name(),
             Self::Unknown(value) => value.name(),
         }
     }
@@ -309,12 +366,10 @@ impl SectionInfo for AnySection {
     #[inline]
     fn raw_data(&self) -> Result<DataSegment<'_>> {
         match self {
-            Self::Debug(value) => value.raw_data(),
             Self::Export(value) => value.raw_data(),
             Self::Import(value) => value.raw_data(),
             Self::Tls(value) => value.raw_data(),
             Self::Resource(value) => value.raw_data(),
-            Self::Relocation(value) => value.raw_data(),
             Self::Unknown(value) => value.raw_data(),
         }
     }
@@ -326,23 +381,19 @@ impl<'s> TryFrom<(&SectionHeader, DataSegment<'s>, bool)> for AnySection {
     fn try_from(
         (header, segment, is_32_plus): (&SectionHeader, DataSegment<'s>, bool),
     ) -> Result<Self> {
-        Ok(if debug::DebugSection::section_type_name() == header.name {
-            Self::Debug(debug::DebugSection::try_from((is_32_plus, segment))?)
-        } else if export::ExportSection::section_type_name() == header.name {
-            Self::Export(export::ExportSection::try_from((is_32_plus, segment))?)
-        } else if import::ImportSection::section_type_name() == header.name {
-            Self::Import(import::ImportSection::try_from((is_32_plus, segment))?)
-        } else if tls::TlsSection::section_type_name() == header.name {
-            Self::Tls(tls::TlsSection::try_from((is_32_plus, segment))?)
-        } else if resource::ResourceSection::section_type_name() == header.name {
-            Self::Resource(resource::ResourceSection::try_from((is_32_plus, segment))?)
-        } else if relocation::RelocationSection::section_type_name() == header.name {
-            Self::Relocation(relocation::RelocationSection::try_from((
-                is_32_plus, segment,
-            ))?)
-        } else {
-            Self::Unknown(UnknownSection::new(header.name.clone(), segment)?)
-        })
+        Ok(
+            if export::ExportSection::section_type_name() == header.name {
+                Self::Export(export::ExportSection::try_from((is_32_plus, segment))?)
+            } else if import::ImportSection::section_type_name() == header.name {
+                Self::Import(import::ImportSection::try_from((is_32_plus, segment))?)
+            } else if tls::TlsSection::section_type_name() == header.name {
+                Self::Tls(tls::TlsSection::try_from((is_32_plus, segment))?)
+            } else if resource::ResourceSection::section_type_name() == header.name {
+                Self::Resource(resource::ResourceSection::try_from((is_32_plus, segment))?)
+            } else {
+                Self::Unknown(UnknownSection::new(header.name.clone(), segment)?)
+            },
+        )
     }
 }
 
@@ -352,6 +403,9 @@ pub struct Section {
     pub info: AnySection,
     section_start: u32,
     section_end: u32,
+    loader_pointer_to_raw_data: u32,
+    loader_virtual_size: u32,
+    loader_read_size: u32,
 }
 
 impl Section {
@@ -368,6 +422,9 @@ impl Section {
         )?;
         let info = AnySection::try_from((&header, segment, pe_header.is_pe32_plus()))?;
         Ok(Self {
+            loader_pointer_to_raw_data: header.loader_pointer_to_raw_data(pe_header),
+            loader_virtual_size: header.loader_virtual_size(pe_header)?,
+            loader_read_size: header.loader_read_size(pe_header)?,
             header,
             info,
             section_start,
@@ -375,15 +432,35 @@ impl Section {
         })
     }
 
+    /// The file offset that the loader starts reading the section's data from.
+    #[inline]
+    pub fn loader_pointer_to_raw_data(&self) -> u32 {
+        self.loader_pointer_to_raw_data
+    }
+
+    /// The size of the section once it's loaded into memory.
+    #[inline]
+    pub fn loader_virtual_size(&self) -> u32 {
+        self.loader_virtual_size
+    }
+
+    /// The number of bytes that the loader reads from the file. The rest of the section is
+    /// zero-filled.
+    #[inline]
+    pub fn loader_read_size(&self) -> u32 {
+        self.loader_read_size
+    }
+
+    /// Converts an RVA to a file offset the same way the loader maps the section, so the result
+    /// matches [`PeFile::map_image`](crate::PeFile::map_image). Returns `None` if the RVA isn't
+    /// backed by the section's data in the file, which includes its zero-filled part.
     #[inline]
     pub fn rva_to_offset(&self, rva: Rva) -> Option<u64> {
-        if !self.contains_rva(rva) {
-            None
+        let offset = rva.checked_sub(self.header.virtual_address)?;
+        if offset < self.loader_read_size {
+            Some(self.loader_pointer_to_raw_data as u64 + offset as u64)
         } else {
-            Some(
-                (rva as u64 - self.header.virtual_address as u64)
-                    + self.header.pointer_to_raw_data as u64,
-            )
+            None
         }
     }
 
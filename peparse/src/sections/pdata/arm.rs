@@ -0,0 +1,392 @@
+use super::xdata::{truncated_code, ArmUnwindCode, EpilogScope, UnwindRecordHeader};
+use crate::{
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
+
+/// An ARM (Thumb-2) .pdata entry.
+#[derive(Debug, Clone)]
+pub struct ArmNtFunction {
+    /// The RVA of the start of the function. The low bit is always set, since the function is
+    /// made up of Thumb code.
+    pub begin_address: Rva,
+
+    pub unwind_data: ArmUnwindData,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArmUnwindData {
+    /// The unwind data is packed into the .pdata entry itself.
+    Packed(ArmPackedUnwind),
+
+    /// The RVA of the .xdata record that holds the unwind data.
+    Unpacked(Rva),
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for ArmNtFunction {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let begin_address = segment.next_u32()?;
+        let value = segment.next_u32()?;
+        let unwind_data = if value & 0x3 == 0 {
+            ArmUnwindData::Unpacked(value)
+        } else {
+            ArmUnwindData::Packed(ArmPackedUnwind::from(value))
+        };
+        Ok(Self {
+            begin_address,
+            unwind_data,
+        })
+    }
+}
+
+/// Packed ARM unwind data, which describes a function with a canonical prolog and epilog.
+#[derive(Debug, Clone)]
+pub struct ArmPackedUnwind {
+    /// 1 for packed unwind data, 2 for packed unwind data describing a function fragment without a
+    /// prolog, and 3 is reserved.
+    pub flag: u8,
+
+    /// The length of the entire function in bytes.
+    pub function_length: u32,
+
+    /// How the function returns: 0 for `pop {pc}`, 1 for a 16-bit branch, 2 for a 32-bit branch
+    /// and 3 for no epilog at all.
+    pub ret: u8,
+
+    /// If the function homes the integer parameter registers (r0-r3).
+    pub h: bool,
+
+    /// The index of the last saved non-volatile register. If `r` is false, integer registers r4
+    /// through r(4 + reg) are saved, otherwise floating-point registers d8 through d(8 + reg).
+    pub reg: u8,
+
+    /// If floating-point registers are saved instead of integer registers.
+    pub r: bool,
+
+    /// If lr is saved or restored.
+    pub l: bool,
+
+    /// If the function includes extra instructions to set up a frame chain.
+    pub c: bool,
+
+    /// The number of bytes of stack that are allocated for the function.
+    pub stack_adjust: u32,
+}
+
+impl From<u32> for ArmPackedUnwind {
+    fn from(value: u32) -> Self {
+        Self {
+            flag: (value & 0x3) as u8,
+            function_length: ((value >> 2) & 0x7ff) * 2,
+            ret: ((value >> 13) & 0x3) as u8,
+            h: (value >> 15) & 1 == 1,
+            reg: ((value >> 16) & 0x7) as u8,
+            r: (value >> 19) & 1 == 1,
+            l: (value >> 20) & 1 == 1,
+            c: (value >> 21) & 1 == 1,
+            stack_adjust: ((value >> 22) & 0x3ff) * 4,
+        }
+    }
+}
+
+impl ArmPackedUnwind {
+    /// If this describes a function fragment without a prolog.
+    #[inline]
+    pub fn is_fragment(&self) -> bool {
+        self.flag == 2
+    }
+
+    /// Expands the packed data into the unwind codes of the canonical prolog it stands for. Like in
+    /// an .xdata record, the codes are in the order they need to be undone in (i.e. the last
+    /// instruction of the prolog comes first).
+    pub fn unwind_codes(&self) -> Vec<ArmNtUnwindCode> {
+        // Adjustments of 0x3f4 words and up are small ones that can be folded into the register
+        // push instead of being done separately: the low two bits are the number of words minus
+        // one, and bit 2 is set if the prolog folds it.
+        let adjust_words = self.stack_adjust / 4;
+        let (folded_words, stack_adjust) = if adjust_words < 0x3f4 {
+            (0, self.stack_adjust)
+        } else if adjust_words & 0x4 != 0 {
+            ((adjust_words & 0x3) + 1, 0)
+        } else {
+            (0, ((adjust_words & 0x3) + 1) * 4)
+        };
+
+        let mut codes = Vec::new();
+        if self.h {
+            // `push {r0-r3}` only needs the stack to be adjusted back.
+            codes.push(ArmNtUnwindCode::AllocStack {
+                size: 16,
+                wide: false,
+            });
+        }
+        let mut mask = 0u16;
+        if !self.r {
+            mask |= ((1 << (self.reg + 5)) - 1) & !0xf;
+        }
+        if self.c {
+            mask |= 1 << 11;
+        }
+        if self.l {
+            mask |= LR_BIT;
+        }
+        // Folded adjustments are done by pushing the registers just below r4.
+        mask |= 0xf & !((1 << (4 - folded_words)) - 1);
+        if mask != 0 {
+            codes.push(ArmNtUnwindCode::PopRegisters {
+                mask,
+                // The 16-bit push can only hold r0-r7 and lr.
+                wide: mask & 0x1f00 != 0,
+            });
+        }
+        if self.c {
+            // `add r11, sp, #offset` sets up the frame chain, which doesn't need to be undone.
+            codes.push(ArmNtUnwindCode::Nop { wide: true });
+        }
+        if self.r && self.reg != 7 {
+            codes.push(ArmNtUnwindCode::PopFloatingPoint {
+                first: 8,
+                last: 8 + self.reg,
+            });
+        }
+        if stack_adjust != 0 {
+            codes.push(ArmNtUnwindCode::AllocStack {
+                size: stack_adjust,
+                // The 16-bit `sub sp` can only subtract up to 508 bytes.
+                wide: stack_adjust > 508,
+            });
+        }
+        codes.reverse();
+        codes
+    }
+}
+
+/// An unwind code from an ARM (Thumb-2) .xdata record.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ArmNtUnwindCode {
+    /// `add sp, sp, #size` (or `addw`). `wide` is set if the instruction is 32 bits.
+    AllocStack { size: u32, wide: bool },
+
+    /// `pop` of the registers in the mask, where bit n is rn and bit 14 is lr.
+    PopRegisters { mask: u16, wide: bool },
+
+    /// `mov sp, rX`.
+    MoveStackPointer(u8),
+
+    /// `vpop {dFirst-dLast}`.
+    PopFloatingPoint { first: u8, last: u8 },
+
+    /// `ldr.w lr, [sp], #offset`.
+    LoadLr { offset: u32 },
+
+    /// A Microsoft-specific operation.
+    MicrosoftSpecific(u8),
+
+    /// A nop, which is either 16 or 32 bits.
+    Nop { wide: bool },
+
+    /// The end of the unwind codes. If `nop` is set, the epilog ends with a 16-bit (`Some(false)`)
+    /// or 32-bit (`Some(true)`) nop.
+    End { nop: Option<bool> },
+
+    /// A reserved opcode.
+    Reserved(u8),
+}
+
+/// The bit used for lr in [`ArmNtUnwindCode::PopRegisters`] masks.
+const LR_BIT: u16 = 1 << 14;
+
+impl ArmUnwindCode for ArmNtUnwindCode {
+    fn parse_header(word: u32) -> UnwindRecordHeader {
+        UnwindRecordHeader {
+            function_length: (word & 0x3ffff) * 2,
+            version: ((word >> 18) & 0x3) as u8,
+            has_exception_data: (word >> 20) & 1 == 1,
+            single_epilog: (word >> 21) & 1 == 1,
+            is_fragment: (word >> 22) & 1 == 1,
+            epilog_count: ((word >> 23) & 0x1f) as u16,
+            code_words: ((word >> 28) & 0xf) as u16,
+            is_extended: false,
+        }
+    }
+
+    fn parse_epilog_scope(word: u32) -> EpilogScope {
+        EpilogScope {
+            start_offset: (word & 0x3ffff) * 2,
+            start_index: ((word >> 24) & 0xff) as u16,
+            condition: Some(((word >> 20) & 0xf) as u8),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<(Self, usize)> {
+        let opcode = bytes[0];
+        let size = match opcode {
+            0x80..=0xbf | 0xe8..=0xef | 0xf5 | 0xf6 => 2,
+            0xf7 | 0xf9 => 3,
+            0xf8 | 0xfa => 4,
+            _ => 1,
+        };
+        if bytes.len() < size {
+            return Err(truncated_code(opcode));
+        }
+        let operand = bytes[1..size]
+            .iter()
+            .fold(0u32, |value, byte| value << 8 | *byte as u32);
+        let code = match opcode {
+            0x00..=0x7f => Self::AllocStack {
+                size: (opcode as u32 & 0x7f) * 4,
+                wide: false,
+            },
+            0x80..=0xbf => {
+                let word = (opcode as u16) << 8 | bytes[1] as u16;
+                let lr = if word & 0x2000 != 0 { LR_BIT } else { 0 };
+                Self::PopRegisters {
+                    mask: (word & 0x1fff) | lr,
+                    wide: true,
+                }
+            }
+            0xc0..=0xcf => Self::MoveStackPointer(opcode & 0xf),
+            0xd0..=0xdf => {
+                // Pops r4 through r(4 + x) (16-bit) or r(8 + x) (32-bit), and optionally lr.
+                let wide = opcode & 0x08 != 0;
+                let last = (opcode & 0x3) + if wide { 8 } else { 4 };
+                let lr = if opcode & 0x4 != 0 { LR_BIT } else { 0 };
+                Self::PopRegisters {
+                    mask: ((1u16 << (last + 1)) - 1) & !0xf | lr,
+                    wide,
+                }
+            }
+            0xe0..=0xe7 => Self::PopFloatingPoint {
+                first: 8,
+                last: 8 + (opcode & 0x7),
+            },
+            0xe8..=0xeb => Self::AllocStack {
+                size: ((opcode as u32 & 0x3) << 8 | operand) * 4,
+                wide: true,
+            },
+            0xec..=0xed => Self::PopRegisters {
+                mask: operand as u16 | if opcode & 1 != 0 { LR_BIT } else { 0 },
+                wide: false,
+            },
+            0xee if operand < 0x10 => Self::MicrosoftSpecific(operand as u8),
+            0xef if operand < 0x10 => Self::LoadLr {
+                offset: operand * 4,
+            },
+            0xf5 => Self::PopFloatingPoint {
+                first: (operand >> 4) as u8,
+                last: (operand & 0xf) as u8,
+            },
+            0xf6 => Self::PopFloatingPoint {
+                first: 16 + (operand >> 4) as u8,
+                last: 16 + (operand & 0xf) as u8,
+            },
+            0xf7 | 0xf8 => Self::AllocStack {
+                size: operand * 4,
+                wide: false,
+            },
+            0xf9 | 0xfa => Self::AllocStack {
+                size: operand * 4,
+                wide: true,
+            },
+            0xfb => Self::Nop { wide: false },
+            0xfc => Self::Nop { wide: true },
+            0xfd => Self::End { nop: Some(false) },
+            0xfe => Self::End { nop: Some(true) },
+            0xff => Self::End { nop: None },
+            _ => Self::Reserved(opcode),
+        };
+        Ok((code, size))
+    }
+
+    #[inline]
+    fn is_end(&self) -> bool {
+        matches!(self, Self::End { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_codes() {
+        assert_eq!(
+            ArmNtUnwindCode::decode(&[0xd5]).unwrap(),
+            (
+                ArmNtUnwindCode::PopRegisters {
+                    mask: 0x0030 | LR_BIT,
+                    wide: false,
+                },
+                1
+            )
+        );
+        assert_eq!(
+            ArmNtUnwindCode::decode(&[0xe8, 0x01]).unwrap(),
+            (
+                ArmNtUnwindCode::AllocStack {
+                    size: 4,
+                    wide: true
+                },
+                2
+            )
+        );
+        assert_eq!(
+            ArmNtUnwindCode::decode(&[0xfd]).unwrap(),
+            (ArmNtUnwindCode::End { nop: Some(false) }, 1)
+        );
+        assert!(ArmNtUnwindCode::decode(&[0xf8, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn expands_packed_unwind() {
+        // push {r4-r7, lr}; sub sp, sp, #8
+        let packed = ArmPackedUnwind::from(0x0093_0041);
+        assert_eq!(packed.function_length, 0x20);
+        assert_eq!(
+            packed.unwind_codes(),
+            vec![
+                ArmNtUnwindCode::AllocStack {
+                    size: 8,
+                    wide: false,
+                },
+                ArmNtUnwindCode::PopRegisters {
+                    mask: 0x00f0 | LR_BIT,
+                    wide: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_packed_unwind_with_frame_chain() {
+        // push {r4-r7, r11, lr}; add r11, sp, #16
+        let packed = ArmPackedUnwind::from(0x0033_0041);
+        assert_eq!(
+            packed.unwind_codes(),
+            vec![
+                ArmNtUnwindCode::Nop { wide: true },
+                ArmNtUnwindCode::PopRegisters {
+                    mask: 0x08f0 | LR_BIT,
+                    wide: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn folds_small_stack_adjustments_into_the_push() {
+        // push {r2-r4}, where r2 and r3 only adjust the stack.
+        let packed = ArmPackedUnwind::from(0xfd40_0041);
+        assert_eq!(
+            packed.unwind_codes(),
+            vec![ArmNtUnwindCode::PopRegisters {
+                mask: 0x001c,
+                wide: false,
+            }]
+        );
+    }
+}
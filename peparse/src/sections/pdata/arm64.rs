@@ -0,0 +1,594 @@
+use super::xdata::{truncated_code, ArmUnwindCode, EpilogScope, UnwindRecordHeader};
+use crate::{
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
+
+/// The number of the frame pointer register (x29).
+const FP: u8 = 29;
+
+/// The number of the link register (x30).
+const LR: u8 = 30;
+
+/// The largest allocation that the canonical packed prolog makes in a single instruction.
+const MAX_PACKED_ALLOC: u32 = 4080;
+
+/// An ARM64 .pdata entry.
+#[derive(Debug, Clone)]
+pub struct Arm64Function {
+    /// The RVA of the start of the function.
+    pub begin_address: Rva,
+
+    pub unwind_data: Arm64UnwindData,
+}
+
+#[derive(Debug, Clone)]
+pub enum Arm64UnwindData {
+    /// The unwind data is packed into the .pdata entry itself.
+    Packed(Arm64PackedUnwind),
+
+    /// The RVA of the .xdata record that holds the unwind data.
+    Unpacked(Rva),
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for Arm64Function {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let begin_address = segment.next_u32()?;
+        let value = segment.next_u32()?;
+        let unwind_data = if value & 0x3 == 0 {
+            Arm64UnwindData::Unpacked(value)
+        } else {
+            Arm64UnwindData::Packed(Arm64PackedUnwind::from(value))
+        };
+        Ok(Self {
+            begin_address,
+            unwind_data,
+        })
+    }
+}
+
+/// Packed ARM64 unwind data, which describes a function with a canonical prolog and epilog.
+#[derive(Debug, Clone)]
+pub struct Arm64PackedUnwind {
+    /// 1 for packed unwind data, 2 for packed unwind data describing a function fragment without a
+    /// prolog, and 3 is reserved.
+    pub flag: u8,
+
+    /// The length of the entire function in bytes.
+    pub function_length: u32,
+
+    /// The number of non-volatile floating-point registers (d8-d15) that are saved. If this is
+    /// non-zero, `reg_f + 1` registers are saved.
+    pub reg_f: u8,
+
+    /// The number of non-volatile integer registers (x19-x28) that are saved.
+    pub reg_i: u8,
+
+    /// If the function homes the integer parameter registers (x0-x7).
+    pub h: bool,
+
+    /// If the function includes extra instructions to set up a frame chain and return link: 0 for
+    /// an unchained function, 1 for an unchained function where only lr is saved, 2 for a chained
+    /// function with a signed return address and 3 for a chained function.
+    pub cr: u8,
+
+    /// The number of bytes of stack that are allocated for the function.
+    pub frame_size: u32,
+}
+
+impl From<u32> for Arm64PackedUnwind {
+    fn from(value: u32) -> Self {
+        Self {
+            flag: (value & 0x3) as u8,
+            function_length: ((value >> 2) & 0x7ff) * 4,
+            reg_f: ((value >> 13) & 0x7) as u8,
+            reg_i: ((value >> 16) & 0xf) as u8,
+            h: (value >> 20) & 1 == 1,
+            cr: ((value >> 21) & 0x3) as u8,
+            frame_size: ((value >> 23) & 0x1ff) * 16,
+        }
+    }
+}
+
+impl Arm64PackedUnwind {
+    /// If this describes a function fragment without a prolog or epilog.
+    #[inline]
+    pub fn is_fragment(&self) -> bool {
+        self.flag == 2
+    }
+
+    /// Expands the packed data into the unwind codes of the canonical prolog it stands for. Like in
+    /// an .xdata record, the codes are in the order they need to be undone in (i.e. the last
+    /// instruction of the prolog comes first).
+    pub fn unwind_codes(&self) -> Vec<Arm64UnwindCode> {
+        self.codes(true)
+    }
+
+    /// The unwind codes of the canonical epilog, in the order they're undone in. The epilog is the
+    /// prolog run backwards, except that the parameters that were homed aren't reloaded. Function
+    /// fragments don't have an epilog, so this is empty for them.
+    pub fn epilog_codes(&self) -> Vec<Arm64UnwindCode> {
+        if self.is_fragment() {
+            Vec::new()
+        } else {
+            self.codes(false)
+        }
+    }
+
+    fn codes(&self, is_prolog: bool) -> Vec<Arm64UnwindCode> {
+        let saves_lr = self.cr == 1;
+        let is_chained = self.cr == 2 || self.cr == 3;
+        let int_size = self.reg_i as u32 * 8 + if saves_lr { 8 } else { 0 };
+        let fp_count = if self.reg_f > 0 { self.reg_f + 1 } else { 0 };
+        let home_size = if self.h { 64 } else { 0 };
+        let save_size = (int_size + fp_count as u32 * 8 + home_size + 0xf) & !0xf;
+        let local_size = self.frame_size.saturating_sub(save_size);
+
+        let mut codes = Vec::new();
+        if self.cr == 2 {
+            codes.push(Arm64UnwindCode::PacSignLr);
+        }
+        // The first register store also allocates the whole save area.
+        let mut offset = 0;
+        let mut pre_indexed = true;
+        let mut save = |codes: &mut Vec<Arm64UnwindCode>, kind, first, second: Option<u8>| {
+            let code_offset = if pre_indexed { save_size } else { offset };
+            codes.push(match second {
+                Some(second) => Arm64UnwindCode::SavePair {
+                    kind,
+                    first,
+                    second,
+                    offset: code_offset,
+                    pre_indexed,
+                },
+                None => Arm64UnwindCode::SaveRegister {
+                    kind,
+                    register: first,
+                    offset: code_offset,
+                    pre_indexed,
+                },
+            });
+            offset += if second.is_some() { 16 } else { 8 };
+            pre_indexed = false;
+        };
+
+        let last_int = 19 + self.reg_i;
+        let mut register = 19;
+        while register < last_int {
+            if register + 1 < last_int {
+                save(
+                    &mut codes,
+                    Arm64RegisterKind::X,
+                    register,
+                    Some(register + 1),
+                );
+                register += 2;
+            } else if saves_lr {
+                save(&mut codes, Arm64RegisterKind::X, register, Some(LR));
+                register += 1;
+            } else {
+                save(&mut codes, Arm64RegisterKind::X, register, None);
+                register += 1;
+            }
+        }
+        if saves_lr && self.reg_i % 2 == 0 {
+            save(&mut codes, Arm64RegisterKind::X, LR, None);
+        }
+        let last_fp = 8 + fp_count;
+        let mut register = 8;
+        while register < last_fp {
+            if register + 1 < last_fp {
+                save(
+                    &mut codes,
+                    Arm64RegisterKind::D,
+                    register,
+                    Some(register + 1),
+                );
+                register += 2;
+            } else {
+                save(&mut codes, Arm64RegisterKind::D, register, None);
+                register += 1;
+            }
+        }
+        if self.h && is_prolog {
+            // Homing the parameters takes four stores, which don't need to be undone.
+            codes.extend((0..4).map(|_| Arm64UnwindCode::Nop));
+        }
+
+        if is_chained && local_size <= 512 {
+            codes.push(Arm64UnwindCode::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: FP,
+                second: LR,
+                offset: local_size,
+                pre_indexed: true,
+            });
+        } else {
+            if local_size > MAX_PACKED_ALLOC {
+                codes.push(Arm64UnwindCode::AllocStack(MAX_PACKED_ALLOC));
+                codes.push(Arm64UnwindCode::AllocStack(local_size - MAX_PACKED_ALLOC));
+            } else if local_size > 0 {
+                codes.push(Arm64UnwindCode::AllocStack(local_size));
+            }
+            if is_chained {
+                codes.push(Arm64UnwindCode::SavePair {
+                    kind: Arm64RegisterKind::X,
+                    first: FP,
+                    second: LR,
+                    offset: 0,
+                    pre_indexed: false,
+                });
+            }
+        }
+        if is_chained {
+            codes.push(Arm64UnwindCode::SetFp);
+        }
+        codes.reverse();
+        codes
+    }
+}
+
+/// The kind of register that an unwind code saves.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Arm64RegisterKind {
+    /// A 64-bit integer register.
+    X,
+    /// A 64-bit floating-point register.
+    D,
+    /// A 128-bit vector register.
+    Q,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Arm64UnwindCode {
+    /// alloc_s, alloc_m and alloc_l: allocate the provided number of bytes on the stack.
+    AllocStack(u32),
+
+    /// alloc_z: allocate the provided number of SVE vector lengths on the stack.
+    AllocScalableVector(u8),
+
+    /// save_reg, save_reg_x, save_freg, save_freg_x and single-register save_any_reg: save a
+    /// register at `[sp + offset]`, or at `[sp - offset]!` if it's pre-indexed.
+    SaveRegister {
+        kind: Arm64RegisterKind,
+        register: u8,
+        offset: u32,
+        pre_indexed: bool,
+    },
+
+    /// save_r19r20_x, save_fplr, save_fplr_x, save_regp, save_regp_x, save_lrpair, save_fregp,
+    /// save_fregp_x and paired save_any_reg: save a pair of registers at `[sp + offset]`, or at
+    /// `[sp - offset]!` if it's pre-indexed.
+    SavePair {
+        kind: Arm64RegisterKind,
+        first: u8,
+        second: u8,
+        offset: u32,
+        pre_indexed: bool,
+    },
+
+    /// set_fp: set up x29 with `mov x29, sp`.
+    SetFp,
+
+    /// add_fp: set up x29 with `add x29, sp, #offset`.
+    AddFp(u32),
+
+    /// nop: no unwind operation is required.
+    Nop,
+
+    /// end: the end of the unwind codes.
+    End,
+
+    /// end_c: the end of the unwind codes in the current chained scope.
+    EndChained,
+
+    /// save_next: save the next non-volatile integer or floating-point register pair.
+    SaveNext,
+
+    /// MSFT_OP_TRAP_FRAME.
+    TrapFrame,
+
+    /// MSFT_OP_MACHINE_FRAME.
+    MachineFrame,
+
+    /// MSFT_OP_CONTEXT.
+    Context,
+
+    /// MSFT_OP_EC_CONTEXT.
+    EcContext,
+
+    /// MSFT_OP_CLEAR_UNWOUND_TO_CALL.
+    ClearUnwoundToCall,
+
+    /// pac_sign_lr: sign the return address in lr with `pacibsp`.
+    PacSignLr,
+
+    /// A reserved opcode.
+    Reserved(u8),
+}
+
+impl ArmUnwindCode for Arm64UnwindCode {
+    fn parse_header(word: u32) -> UnwindRecordHeader {
+        UnwindRecordHeader {
+            function_length: (word & 0x3ffff) * 4,
+            version: ((word >> 18) & 0x3) as u8,
+            has_exception_data: (word >> 20) & 1 == 1,
+            single_epilog: (word >> 21) & 1 == 1,
+            is_fragment: false,
+            epilog_count: ((word >> 22) & 0x1f) as u16,
+            code_words: ((word >> 27) & 0x1f) as u16,
+            is_extended: false,
+        }
+    }
+
+    fn parse_epilog_scope(word: u32) -> EpilogScope {
+        EpilogScope {
+            start_offset: (word & 0x3ffff) * 4,
+            start_index: ((word >> 22) & 0x3ff) as u16,
+            condition: None,
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<(Self, usize)> {
+        let opcode = bytes[0];
+        let size = match opcode {
+            0xc0..=0xdf | 0xe2 | 0xf8 => 2,
+            0xe7 | 0xf9 => 3,
+            0xe0 | 0xfa => 4,
+            0xfb => 5,
+            _ => 1,
+        };
+        if bytes.len() < size {
+            return Err(truncated_code(opcode));
+        }
+        let second = if size > 1 { bytes[1] as u32 } else { 0 };
+        // Most of the two-byte codes are a register number (X) followed by a scaled offset (Z).
+        let x = |bits: u32| ((((opcode as u32) << 8 | second) >> 6) & ((1 << bits) - 1)) as u8;
+        let z6 = second & 0x3f;
+        let z5 = second & 0x1f;
+        let code = match opcode {
+            0x00..=0x1f => Self::AllocStack((opcode as u32 & 0x1f) * 16),
+            0x20..=0x3f => Self::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: 19,
+                second: 20,
+                offset: (opcode as u32 & 0x1f) * 8,
+                pre_indexed: true,
+            },
+            0x40..=0x7f => Self::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: FP,
+                second: LR,
+                offset: (opcode as u32 & 0x3f) * 8,
+                pre_indexed: false,
+            },
+            0x80..=0xbf => Self::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: FP,
+                second: LR,
+                offset: ((opcode as u32 & 0x3f) + 1) * 8,
+                pre_indexed: true,
+            },
+            0xc0..=0xc7 => Self::AllocStack(((opcode as u32 & 0x7) << 8 | second) * 16),
+            0xc8..=0xcf => Self::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: 19 + x(4),
+                second: 20 + x(4),
+                offset: if opcode < 0xcc { z6 * 8 } else { (z6 + 1) * 8 },
+                pre_indexed: opcode >= 0xcc,
+            },
+            0xd0..=0xd3 => Self::SaveRegister {
+                kind: Arm64RegisterKind::X,
+                register: 19 + x(4),
+                offset: z6 * 8,
+                pre_indexed: false,
+            },
+            0xd4..=0xd5 => Self::SaveRegister {
+                kind: Arm64RegisterKind::X,
+                register: 19 + (((opcode as u32 & 1) << 3 | second >> 5) as u8),
+                offset: (z5 + 1) * 8,
+                pre_indexed: true,
+            },
+            0xd6..=0xd7 => Self::SavePair {
+                kind: Arm64RegisterKind::X,
+                first: 19 + 2 * x(3),
+                second: LR,
+                offset: z6 * 8,
+                pre_indexed: false,
+            },
+            0xd8..=0xdb => Self::SavePair {
+                kind: Arm64RegisterKind::D,
+                first: 8 + x(3),
+                second: 9 + x(3),
+                offset: if opcode < 0xda { z6 * 8 } else { (z6 + 1) * 8 },
+                pre_indexed: opcode >= 0xda,
+            },
+            0xdc..=0xdd => Self::SaveRegister {
+                kind: Arm64RegisterKind::D,
+                register: 8 + x(3),
+                offset: z6 * 8,
+                pre_indexed: false,
+            },
+            0xde => Self::SaveRegister {
+                kind: Arm64RegisterKind::D,
+                register: 8 + (second >> 5) as u8,
+                offset: (z5 + 1) * 8,
+                pre_indexed: true,
+            },
+            0xdf => Self::AllocScalableVector(second as u8),
+            0xe0 => {
+                Self::AllocStack((second << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32) * 16)
+            }
+            0xe1 => Self::SetFp,
+            0xe2 => Self::AddFp(second * 8),
+            0xe3 => Self::Nop,
+            0xe4 => Self::End,
+            0xe5 => Self::EndChained,
+            0xe6 => Self::SaveNext,
+            0xe7 => {
+                // 0pxrrrrr ffoooooo: pair, pre-indexed, register, register kind and offset.
+                let is_pair = second & 0x40 != 0;
+                let pre_indexed = second & 0x20 != 0;
+                let register = (second & 0x1f) as u8;
+                let kind = match bytes[2] >> 6 {
+                    0 => Arm64RegisterKind::X,
+                    1 => Arm64RegisterKind::D,
+                    2 => Arm64RegisterKind::Q,
+                    _ => return Ok((Self::Reserved(opcode), size)),
+                };
+                let scale = if kind == Arm64RegisterKind::Q || is_pair || pre_indexed {
+                    16
+                } else {
+                    8
+                };
+                let offset = (bytes[2] as u32 & 0x3f) * scale;
+                if is_pair {
+                    Self::SavePair {
+                        kind,
+                        first: register,
+                        second: register + 1,
+                        offset,
+                        pre_indexed,
+                    }
+                } else {
+                    Self::SaveRegister {
+                        kind,
+                        register,
+                        offset,
+                        pre_indexed,
+                    }
+                }
+            }
+            0xe8 => Self::TrapFrame,
+            0xe9 => Self::MachineFrame,
+            0xea => Self::Context,
+            0xeb => Self::EcContext,
+            0xec => Self::ClearUnwoundToCall,
+            0xfc => Self::PacSignLr,
+            _ => Self::Reserved(opcode),
+        };
+        Ok((code, size))
+    }
+
+    #[inline]
+    fn is_end(&self) -> bool {
+        matches!(self, Self::End | Self::EndChained)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Arm64UnwindCode {
+        let (code, size) = Arm64UnwindCode::decode(bytes).unwrap();
+        assert_eq!(size, bytes.len());
+        code
+    }
+
+    fn pair(first: u8, second: u8, offset: u32, pre_indexed: bool) -> Arm64UnwindCode {
+        Arm64UnwindCode::SavePair {
+            kind: Arm64RegisterKind::X,
+            first,
+            second,
+            offset,
+            pre_indexed,
+        }
+    }
+
+    #[test]
+    fn decodes_common_codes() {
+        assert_eq!(decode(&[0x02]), Arm64UnwindCode::AllocStack(32));
+        assert_eq!(decode(&[0x21]), pair(19, 20, 8, true));
+        assert_eq!(decode(&[0x41]), pair(FP, LR, 8, false));
+        assert_eq!(decode(&[0x81]), pair(FP, LR, 16, true));
+        assert_eq!(decode(&[0xc8, 0x41]), pair(20, 21, 8, false));
+        assert_eq!(decode(&[0xc1, 0x00]), Arm64UnwindCode::AllocStack(0x1000));
+        assert_eq!(
+            decode(&[0xe0, 0x00, 0x10, 0x00]),
+            Arm64UnwindCode::AllocStack(0x1_0000)
+        );
+        assert_eq!(decode(&[0xe2, 0x02]), Arm64UnwindCode::AddFp(16));
+        assert_eq!(decode(&[0xe4]), Arm64UnwindCode::End);
+        assert_eq!(decode(&[0xfc]), Arm64UnwindCode::PacSignLr);
+        assert_eq!(decode(&[0xed]), Arm64UnwindCode::Reserved(0xed));
+    }
+
+    #[test]
+    fn save_any_reg_scales_offsets() {
+        // A single X register that isn't pre-indexed is the only form scaled by 8.
+        assert_eq!(
+            decode(&[0xe7, 0x13, 0x03]),
+            Arm64UnwindCode::SaveRegister {
+                kind: Arm64RegisterKind::X,
+                register: 19,
+                offset: 24,
+                pre_indexed: false,
+            }
+        );
+        assert_eq!(decode(&[0xe7, 0x53, 0x01]), pair(19, 20, 16, false));
+        assert_eq!(
+            decode(&[0xe7, 0x25, 0x01]),
+            Arm64UnwindCode::SaveRegister {
+                kind: Arm64RegisterKind::X,
+                register: 5,
+                offset: 16,
+                pre_indexed: true,
+            }
+        );
+        assert_eq!(
+            decode(&[0xe7, 0x05, 0x82]),
+            Arm64UnwindCode::SaveRegister {
+                kind: Arm64RegisterKind::Q,
+                register: 5,
+                offset: 32,
+                pre_indexed: false,
+            }
+        );
+        assert_eq!(decode(&[0xe7, 0x05, 0xc2]), Arm64UnwindCode::Reserved(0xe7));
+    }
+
+    #[test]
+    fn truncated_codes_are_errors() {
+        assert!(Arm64UnwindCode::decode(&[0xc8]).is_err());
+        assert!(Arm64UnwindCode::decode(&[0xe7, 0x13]).is_err());
+        assert!(Arm64UnwindCode::decode(&[0xe0, 0x00, 0x10]).is_err());
+    }
+
+    #[test]
+    fn expands_chained_packed_unwind() {
+        // Two integer registers, a chained frame and 32 bytes of stack.
+        let packed = Arm64PackedUnwind::from(0x0162_0041);
+        assert_eq!(packed.function_length, 0x40);
+        assert_eq!(packed.reg_i, 2);
+        assert_eq!(packed.cr, 3);
+        assert_eq!(packed.frame_size, 32);
+        let expected = vec![
+            Arm64UnwindCode::SetFp,
+            pair(FP, LR, 16, true),
+            pair(19, 20, 16, true),
+        ];
+        assert_eq!(packed.unwind_codes(), expected);
+        assert_eq!(packed.epilog_codes(), expected);
+    }
+
+    #[test]
+    fn epilog_does_not_reload_homed_parameters() {
+        let packed = Arm64PackedUnwind::from(0x0210_0001);
+        assert!(packed.h);
+        assert_eq!(packed.unwind_codes(), vec![Arm64UnwindCode::Nop; 4]);
+        assert!(packed.epilog_codes().is_empty());
+    }
+
+    #[test]
+    fn fragments_have_no_epilog() {
+        let packed = Arm64PackedUnwind::from(0x0162_0042);
+        assert!(packed.is_fragment());
+        assert!(!packed.unwind_codes().is_empty());
+        assert!(packed.epilog_codes().is_empty());
+    }
+}
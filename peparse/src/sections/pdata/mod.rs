@@ -0,0 +1,562 @@
+use crate::{
+    coff::constants::Machine,
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::{DataSegment, Endidness, TryFromSegment};
+
+pub mod arm;
+pub mod arm64;
+pub mod xdata;
+
+use arm::{ArmNtFunction, ArmNtUnwindCode, ArmPackedUnwind};
+use arm64::{Arm64Function, Arm64PackedUnwind, Arm64UnwindCode};
+use xdata::UnwindRecord;
+
+/// The exception table (the .pdata section). The format of each entry depends on the machine
+/// type.
+#[derive(Debug, Clone, Default)]
+pub struct ExceptionTable {
+    pub functions: Vec<RuntimeFunction>,
+}
+
+impl<'s> TryFrom<(Machine, &DataSegment<'s>)> for ExceptionTable {
+    type Error = Error;
+
+    fn try_from((machine, segment): (Machine, &DataSegment<'s>)) -> Result<Self> {
+        let entry_size = RuntimeFunction::entry_size(machine)?;
+        let mut functions = Vec::new();
+        while segment.remaining()? >= entry_size {
+            functions.push(RuntimeFunction::try_from((machine, segment))?);
+        }
+        Ok(Self { functions })
+    }
+}
+
+impl ExceptionTable {
+    /// Finds the entry with the highest begin address that's less than or equal to the provided
+    /// RVA. Since the table is sorted, this is done with a binary search. Note that this doesn't
+    /// check that the RVA is before the end of the function.
+    pub fn find(&self, rva: Rva) -> Option<&RuntimeFunction> {
+        let index = self
+            .functions
+            .partition_point(|function| function.begin_address() <= rva);
+        if index == 0 {
+            None
+        } else {
+            self.functions.get(index - 1)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RuntimeFunction {
+    X64(X64Function),
+    Arm64(Arm64Function),
+    /// An ARM (Thumb-2) entry, used by Windows on ARM.
+    ArmNt(ArmNtFunction),
+    /// An entry in the older format used by Windows CE.
+    Arm(ArmFunction),
+    Mips32(Mips32Function),
+}
+
+impl RuntimeFunction {
+    /// The start of the function. For ARM (Thumb-2) entries, the Thumb bit is cleared. Note that for
+    /// MIPS and Windows CE ARM entries this is a VA rather than an RVA.
+    pub fn begin_address(&self) -> Rva {
+        match self {
+            Self::X64(function) => function.begin_address,
+            Self::Arm64(function) => function.begin_address,
+            Self::ArmNt(function) => function.begin_address & !1,
+            Self::Arm(function) => function.begin_address,
+            Self::Mips32(function) => function.begin_address,
+        }
+    }
+
+    /// The size of a single exception table entry for the provided machine type.
+    pub fn entry_size(machine: Machine) -> Result<usize> {
+        match machine {
+            Machine::Amd64 => Ok(12),
+            Machine::Arm64 | Machine::ArmNt => Ok(8),
+            m if m.is_arm32() => Ok(8),
+            m if m.is_mips() => Ok(20),
+            _ => Err(Error::UnsupportedMachine {
+                machine,
+                context: "the exception table".into(),
+            }),
+        }
+    }
+}
+
+impl<'s> TryFrom<(Machine, &DataSegment<'s>)> for RuntimeFunction {
+    type Error = Error;
+
+    fn try_from((machine, segment): (Machine, &DataSegment<'s>)) -> Result<Self> {
+        match machine {
+            Machine::Amd64 => Ok(Self::X64(X64Function::try_from(segment)?)),
+            Machine::Arm64 => Ok(Self::Arm64(Arm64Function::try_from(segment)?)),
+            Machine::ArmNt => Ok(Self::ArmNt(ArmNtFunction::try_from(segment)?)),
+            m if m.is_arm32() => Ok(Self::Arm(ArmFunction::try_from(segment)?)),
+            m if m.is_mips() => Ok(Self::Mips32(Mips32Function::try_from(segment)?)),
+            _ => Err(Error::UnsupportedMachine {
+                machine,
+                context: "the exception table".into(),
+            }),
+        }
+    }
+}
+
+/// A function found using the exception table.
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    /// The RVA of the start of the function.
+    pub begin_address: Rva,
+
+    /// The RVA of the end of the function.
+    pub end_address: Rva,
+
+    pub unwind: FunctionUnwind,
+
+    /// If this is a fragment of a larger function, whose unwind information needs to be chained
+    /// to when unwinding.
+    pub is_chained: bool,
+}
+
+/// The unwind information for a function found using the exception table.
+#[derive(Debug, Clone)]
+pub enum FunctionUnwind {
+    X64(UnwindInfo),
+    Arm64Packed(Arm64PackedUnwind),
+    Arm64(UnwindRecord<Arm64UnwindCode>),
+    ArmNtPacked(ArmPackedUnwind),
+    ArmNt(UnwindRecord<ArmNtUnwindCode>),
+}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct Mips32Function {
+    /// The VA of the corresponding function.
+    pub begin_address: u32,
+
+    /// The VA of the end of the function.
+    pub end_address: u32,
+
+    /// The pointer to the exception handler to be executed.
+    pub exception_handler: u32,
+
+    /// The pointer to additional information to be passed to the handler.
+    pub handler_data: u32,
+
+    /// The VA of the end of the function's prolog.
+    pub prolog_end_address: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArmFunction {
+    /// The VA of the corresponding function.
+    pub begin_address: u32,
+
+    /// The number of instructions in the function's prolog.
+    pub prolog_length: u8,
+
+    pub function_length: u32,
+
+    pub is_32_bit: bool,
+
+    pub has_exception_handler: bool,
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for ArmFunction {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        let begin_address = segment.next_u32()?;
+        let mut remaining = segment.next_u32()?;
+        let has_exception_handler = remaining & 1 == 1;
+        remaining >>= 1;
+        let is_32_bit = remaining & 1 == 1;
+        remaining >>= 1;
+        let function_length = remaining & 0x3fffff;
+        remaining >>= 22;
+        Ok(Self {
+            begin_address,
+            prolog_length: remaining as u8,
+            function_length,
+            is_32_bit,
+            has_exception_handler,
+        })
+    }
+}
+
+#[derive(TryFromSegment, Debug, Clone)]
+#[from_seg(error(crate::Error))]
+pub struct X64Function {
+    /// The RVA of the corresponding function.
+    pub begin_address: Rva,
+
+    /// The RVA of the end of the function.
+    pub end_address: Rva,
+
+    /// The RVA of the unwind information.
+    pub unwind_information: Rva,
+}
+
+flags! {
+    name: UnwindFlags,
+    doc: "",
+    value_type: u8,
+    items: [
+        (EHandler, 0x01, "The function has an exception handler that should be called when looking for functions that need to examine exceptions."),
+        (UHandler, 0x02, "The function has a termination handler that should be called when unwinding an exception."),
+        (ChainInfo, 0x04, "This unwind info structure is not the primary one for the procedure. Instead, the chained unwind info entry is the contents of a previous RUNTIME_FUNCTION entry."),
+    ]
+}
+
+constants_enum! {
+    name: X64Register,
+    doc: "The integer registers, as they're numbered in unwind codes.",
+    value_type: u8,
+    items: [
+        (Rax, 0, ""),
+        (Rcx, 1, ""),
+        (Rdx, 2, ""),
+        (Rbx, 3, ""),
+        (Rsp, 4, ""),
+        (Rbp, 5, ""),
+        (Rsi, 6, ""),
+        (Rdi, 7, ""),
+        (R8, 8, ""),
+        (R9, 9, ""),
+        (R10, 10, ""),
+        (R11, 11, ""),
+        (R12, 12, ""),
+        (R13, 13, ""),
+        (R14, 14, ""),
+        (R15, 15, ""),
+    ]
+}
+
+constants_enum! {
+    name: XmmRegister,
+    doc: "The XMM registers, as they're numbered in unwind codes.",
+    value_type: u8,
+    items: [
+        (Xmm0, 0, ""),
+        (Xmm1, 1, ""),
+        (Xmm2, 2, ""),
+        (Xmm3, 3, ""),
+        (Xmm4, 4, ""),
+        (Xmm5, 5, ""),
+        (Xmm6, 6, ""),
+        (Xmm7, 7, ""),
+        (Xmm8, 8, ""),
+        (Xmm9, 9, ""),
+        (Xmm10, 10, ""),
+        (Xmm11, 11, ""),
+        (Xmm12, 12, ""),
+        (Xmm13, 13, ""),
+        (Xmm14, 14, ""),
+        (Xmm15, 15, ""),
+    ]
+}
+
+/// The x64 UNWIND_INFO structure, which records the effects a function has on the stack pointer
+/// and where the nonvolatile registers are saved on the stack.
+#[derive(Debug, Clone)]
+pub struct UnwindInfo {
+    /// Version number of the unwind data, currently 1 or 2.
+    pub version: u8,
+
+    pub flags: UnwindFlags,
+
+    /// Length of the function prolog in bytes.
+    pub size_of_prolog: u8,
+
+    /// If set, the function uses a frame pointer, and this is the nonvolatile register used as
+    /// the frame pointer.
+    pub frame_register: Option<X64Register>,
+
+    /// The scaled offset from RSP that is applied to the frame register when it's established.
+    pub frame_offset: u32,
+
+    /// The operations that the prolog performs, in reverse order (i.e. the order they need to be
+    /// undone in).
+    pub unwind_codes: Vec<UnwindCode>,
+
+    /// The RVA of the exception handler, if the [`UnwindFlags::EHandler`] or
+    /// [`UnwindFlags::UHandler`] flags are set.
+    pub exception_handler: Option<Rva>,
+
+    /// The RVA of the language-specific handler data that immediately follows the exception
+    /// handler. Its format depends on the handler.
+    pub handler_data: Option<Rva>,
+
+    /// If the [`UnwindFlags::ChainInfo`] flag is set, the function whose unwind info this one is
+    /// chained to.
+    pub chained_function: Option<X64Function>,
+}
+
+impl UnwindInfo {
+    /// The size of the fixed part of the structure, before the unwind codes.
+    pub const HEADER_SIZE: usize = 4;
+
+    /// The total size of the structure (not including the handler data), based on the first
+    /// [`HEADER_SIZE`](Self::HEADER_SIZE) bytes of it.
+    pub fn size_from_header(header: &[u8]) -> usize {
+        let flags = UnwindFlags::from(header[0] >> 3);
+        let count_of_codes = header[2] as usize;
+        // The array of unwind codes is always padded to an even number of slots.
+        let mut size = Self::HEADER_SIZE + ((count_of_codes + 1) & !1) * 2;
+        if flags.intersects(UnwindFlags::EHandler | UnwindFlags::UHandler) {
+            size += 4;
+        } else if flags.contains(UnwindFlags::ChainInfo) {
+            size += 12;
+        }
+        size
+    }
+}
+
+impl<'s> TryFrom<(Rva, &DataSegment<'s>)> for UnwindInfo {
+    type Error = Error;
+
+    fn try_from((rva, segment): (Rva, &DataSegment<'s>)) -> Result<Self> {
+        let version_and_flags = segment.next_u8()?;
+        let flags = UnwindFlags::from(version_and_flags >> 3);
+        let size_of_prolog = segment.next_u8()?;
+        let count_of_codes = segment.next_u8()? as usize;
+        let frame = segment.next_u8()?;
+        let frame_register = match frame & 0xf {
+            0 => None,
+            register => Some(X64Register::try_from(register)?),
+        };
+        let mut slots = Vec::with_capacity(count_of_codes);
+        for _ in 0..count_of_codes {
+            slots.push(segment.next_u16()?);
+        }
+        if count_of_codes % 2 == 1 {
+            segment.next_u16()?;
+        }
+        let mut unwind_codes = Vec::new();
+        let mut slots = slots.into_iter();
+        while let Some(slot) = slots.next() {
+            unwind_codes.push(UnwindCode::parse(slot, &mut slots)?);
+        }
+        let mut exception_handler = None;
+        let mut handler_data = None;
+        let mut chained_function = None;
+        if flags.intersects(UnwindFlags::EHandler | UnwindFlags::UHandler) {
+            exception_handler = Some(segment.next_u32()?);
+            // The handler data immediately follows the handler's RVA.
+            let codes_size = ((count_of_codes + 1) & !1) * 2;
+            handler_data = Some(rva + (Self::HEADER_SIZE + codes_size + 4) as Rva);
+        } else if flags.contains(UnwindFlags::ChainInfo) {
+            chained_function = Some(X64Function::try_from(segment)?);
+        }
+        Ok(Self {
+            version: version_and_flags & 0x7,
+            flags,
+            size_of_prolog,
+            frame_register,
+            frame_offset: (frame >> 4) as u32 * 16,
+            unwind_codes,
+            exception_handler,
+            handler_data,
+            chained_function,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnwindCode {
+    /// The offset (from the beginning of the prolog) of the end of the instruction that performs
+    /// this operation, plus 1 (that is, the offset of the start of the next instruction). For
+    /// epilog codes, this is the size or offset of the epilog instead.
+    pub code_offset: u8,
+
+    pub operation: UnwindOperation,
+}
+
+impl UnwindCode {
+    fn parse<I: Iterator<Item = u16>>(slot: u16, remaining: &mut I) -> Result<Self> {
+        let code_offset = (slot & 0xff) as u8;
+        let op_info = (slot >> 12) as u8;
+        let mut next_slot = || {
+            remaining.next().ok_or_else(|| Error::Other {
+                message: "Unwind code is missing one of its operand slots.".into(),
+            })
+        };
+        let operation = match (slot >> 8) & 0xf {
+            0 => UnwindOperation::PushNonvolatile(X64Register::try_from(op_info)?),
+            1 if op_info == 0 => UnwindOperation::AllocLarge(next_slot()? as u32 * 8),
+            1 => UnwindOperation::AllocLarge(next_slot()? as u32 | (next_slot()? as u32) << 16),
+            2 => UnwindOperation::AllocSmall(op_info as u32 * 8 + 8),
+            3 => UnwindOperation::SetFramePointer,
+            4 => UnwindOperation::SaveNonvolatile {
+                register: X64Register::try_from(op_info)?,
+                offset: next_slot()? as u32 * 8,
+            },
+            5 => UnwindOperation::SaveNonvolatile {
+                register: X64Register::try_from(op_info)?,
+                offset: next_slot()? as u32 | (next_slot()? as u32) << 16,
+            },
+            6 => UnwindOperation::Epilog {
+                flags: op_info,
+                data: next_slot()?,
+            },
+            7 => {
+                next_slot()?;
+                next_slot()?;
+                UnwindOperation::Spare
+            }
+            8 => UnwindOperation::SaveXmm128 {
+                register: XmmRegister::try_from(op_info)?,
+                offset: next_slot()? as u32 * 16,
+            },
+            9 => UnwindOperation::SaveXmm128 {
+                register: XmmRegister::try_from(op_info)?,
+                offset: next_slot()? as u32 | (next_slot()? as u32) << 16,
+            },
+            10 => UnwindOperation::PushMachineFrame {
+                has_error_code: op_info == 1,
+            },
+            op => UnwindOperation::Unknown(op as u8),
+        };
+        Ok(Self {
+            code_offset,
+            operation,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum UnwindOperation {
+    /// UWOP_PUSH_NONVOL: push a nonvolatile integer register, decrementing RSP by 8.
+    PushNonvolatile(X64Register),
+
+    /// UWOP_ALLOC_LARGE: allocate a large-sized area on the stack. The value is the size of the
+    /// allocation in bytes.
+    AllocLarge(u32),
+
+    /// UWOP_ALLOC_SMALL: allocate a small-sized area on the stack. The value is the size of the
+    /// allocation in bytes.
+    AllocSmall(u32),
+
+    /// UWOP_SET_FPREG: establish the frame pointer register by setting the register to some
+    /// offset of the current RSP. The register and offset are in the [`UnwindInfo`].
+    SetFramePointer,
+
+    /// UWOP_SAVE_NONVOL and UWOP_SAVE_NONVOL_FAR: save a nonvolatile integer register on the stack
+    /// using a MOV instead of a PUSH. The offset is from the RSP (or the frame pointer, if one is
+    /// used) after the prolog.
+    SaveNonvolatile { register: X64Register, offset: u32 },
+
+    /// UWOP_EPILOG: describes an epilog. Only used in version 2 unwind info.
+    Epilog { flags: u8, data: u16 },
+
+    /// UWOP_SPARE_CODE: unused.
+    Spare,
+
+    /// UWOP_SAVE_XMM128 and UWOP_SAVE_XMM128_FAR: save all 128 bits of a nonvolatile XMM register
+    /// on the stack.
+    SaveXmm128 { register: XmmRegister, offset: u32 },
+
+    /// UWOP_PUSH_MACHFRAME: push a machine frame. This is used to record the effect of a hardware
+    /// interrupt or exception.
+    PushMachineFrame { has_error_code: bool },
+
+    /// An operation code that isn't defined (11 through 15). Since how many slots it uses isn't
+    /// known, it's assumed to only use one.
+    Unknown(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Source as _};
+
+    fn parse_unwind_info(rva: Rva, data: &[u8]) -> Result<UnwindInfo> {
+        let source = BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little)?;
+        UnwindInfo::try_from((rva, &source.all()?))
+    }
+
+    #[test]
+    fn decodes_x64_unwind_codes() {
+        let slots: [u16; 10] = [
+            0x320c, // alloc_small 32
+            0x6808, 0x0002, // save_xmm128 xmm6, 32
+            0x1104, 0x0000, 0x0001, // alloc_large 0x10000
+            0x5001, // push_nonvol rbp
+            0x1a00, // push_machframe with an error code
+            0x0b00, // an undefined operation
+            0x0000, // padding
+        ];
+        let mut data = vec![0x01, 0x10, 9, 0x00];
+        data.extend(slots.iter().flat_map(|slot| slot.to_le_bytes()));
+        assert_eq!(UnwindInfo::size_from_header(&data), data.len());
+
+        let info = parse_unwind_info(0x2000, &data).unwrap();
+        assert_eq!(info.version, 1);
+        assert_eq!(info.size_of_prolog, 0x10);
+        assert!(info.frame_register.is_none());
+        let codes: Vec<_> = info
+            .unwind_codes
+            .iter()
+            .map(|code| (code.code_offset, code.operation.clone()))
+            .collect();
+        assert_eq!(codes.len(), 6);
+        assert!(matches!(codes[0], (0x0c, UnwindOperation::AllocSmall(32))));
+        assert!(matches!(
+            codes[1],
+            (
+                0x08,
+                UnwindOperation::SaveXmm128 {
+                    register: XmmRegister::Xmm6,
+                    offset: 32
+                }
+            )
+        ));
+        assert!(matches!(
+            codes[2],
+            (0x04, UnwindOperation::AllocLarge(0x10000))
+        ));
+        assert!(matches!(
+            codes[3],
+            (0x01, UnwindOperation::PushNonvolatile(X64Register::Rbp))
+        ));
+        assert!(matches!(
+            codes[4],
+            (
+                0x00,
+                UnwindOperation::PushMachineFrame {
+                    has_error_code: true
+                }
+            )
+        ));
+        assert!(matches!(codes[5], (0x00, UnwindOperation::Unknown(11))));
+    }
+
+    #[test]
+    fn x64_handler_data_follows_the_handler() {
+        let data = [
+            0x09, 0x04, 1, 0x00, 0x01, 0x50, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00,
+        ];
+        assert_eq!(UnwindInfo::size_from_header(&data), data.len());
+        let info = parse_unwind_info(0x2000, &data).unwrap();
+        assert!(info.flags.contains(UnwindFlags::EHandler));
+        assert_eq!(info.exception_handler, Some(0x3000));
+        assert_eq!(info.handler_data, Some(0x200c));
+    }
+
+    #[test]
+    fn x64_missing_operand_slot_is_an_error() {
+        // alloc_large with a scaled size needs a second slot, but there's only one code.
+        assert!(parse_unwind_info(0x2000, &[0x01, 0x04, 1, 0x00, 0x04, 0x01, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn x64_truncated_unwind_info_is_an_error() {
+        assert!(parse_unwind_info(0x2000, &[0x01, 0x04, 4, 0x00, 0x04, 0x32]).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    error::{Error, Result},
+    Rva,
+};
+use core::convert::TryFrom;
+use segsource::DataSegment;
+
+/// An unwind code from an ARM or ARM64 .xdata record.
+pub trait ArmUnwindCode: Sized {
+    /// Parses the first word of an .xdata record, which has a slightly different layout on each
+    /// architecture.
+    fn parse_header(word: u32) -> UnwindRecordHeader;
+
+    /// Parses a single epilog scope.
+    fn parse_epilog_scope(word: u32) -> EpilogScope;
+
+    /// Decodes the unwind code at the start of `bytes`, returning it along with how many bytes it
+    /// used.
+    fn decode(bytes: &[u8]) -> Result<(Self, usize)>;
+
+    /// If this code ends the current sequence of unwind codes.
+    fn is_end(&self) -> bool;
+}
+
+/// The fields that are packed into the first (and optionally second) word of an .xdata record.
+#[derive(Debug, Clone)]
+pub struct UnwindRecordHeader {
+    /// The total length of the function in bytes.
+    pub function_length: u32,
+
+    /// The version of the remaining .xdata. Currently, only version 0 is defined.
+    pub version: u8,
+
+    /// If there's exception data after the unwind codes.
+    pub has_exception_data: bool,
+
+    /// If set, the information describing a single epilog is packed into the header, rather than
+    /// requiring additional scope words later.
+    pub single_epilog: bool,
+
+    /// If this record describes a function fragment without a prolog. ARM only.
+    pub is_fragment: bool,
+
+    /// The number of epilog scopes, or the index of the first unwind code of the only epilog if
+    /// `single_epilog` is set.
+    pub epilog_count: u16,
+
+    /// The number of 32-bit words needed to contain all of the unwind codes.
+    pub code_words: u16,
+
+    /// If the epilog count and code words came from the extension word.
+    pub is_extended: bool,
+}
+
+impl UnwindRecordHeader {
+    /// If the header needs a second word, because the counts were too large to fit in the first.
+    #[inline]
+    pub fn needs_extension(&self) -> bool {
+        !self.is_extended && self.epilog_count == 0 && self.code_words == 0
+    }
+
+    /// Applies the extension word.
+    pub fn extend(&mut self, word: u32) {
+        self.epilog_count = (word & 0xffff) as u16;
+        self.code_words = ((word >> 16) & 0xff) as u16;
+        self.is_extended = true;
+    }
+
+    /// The size of the entire record, not including the exception handler data.
+    pub fn record_size(&self) -> usize {
+        let mut size = if self.is_extended { 8 } else { 4 };
+        if !self.single_epilog {
+            size += self.epilog_count as usize * 4;
+        }
+        size += self.code_words as usize * 4;
+        if self.has_exception_data {
+            size += 4;
+        }
+        size
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpilogScope {
+    /// The offset of the epilog, in bytes, relative to the start of the function.
+    pub start_offset: u32,
+
+    /// The byte index of the first unwind code that describes this epilog.
+    pub start_index: u16,
+
+    /// The condition under which the epilog is executed. ARM only; for unconditional epilogs this
+    /// is 0xe (always).
+    pub condition: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Epilog<C> {
+    /// The epilog's scope, or `None` if the record only has a single epilog whose information is
+    /// packed into the header.
+    pub scope: Option<EpilogScope>,
+
+    /// The operations that the epilog performs.
+    pub unwind_codes: Vec<C>,
+}
+
+/// An unpacked ARM or ARM64 unwind record (.xdata).
+#[derive(Debug, Clone)]
+pub struct UnwindRecord<C> {
+    pub header: UnwindRecordHeader,
+
+    /// The raw unwind code bytes, which the prolog and epilogs index into.
+    pub unwind_code_bytes: Vec<u8>,
+
+    /// The operations that the prolog performs, in the order they need to be undone in.
+    pub prolog: Vec<C>,
+
+    pub epilogs: Vec<Epilog<C>>,
+
+    /// The RVA of the exception handler, if the record has exception data.
+    pub exception_handler: Option<Rva>,
+
+    /// The RVA of the language-specific handler data that immediately follows the exception
+    /// handler.
+    pub handler_data: Option<Rva>,
+}
+
+impl<C: ArmUnwindCode> UnwindRecord<C> {
+    /// Decodes the unwind codes starting at the provided byte index, up to and including the first
+    /// end code.
+    pub fn decode_codes(bytes: &[u8], start_index: usize) -> Result<Vec<C>> {
+        let mut codes = Vec::new();
+        let mut index = start_index;
+        while index < bytes.len() {
+            let (code, size) = C::decode(&bytes[index..])?;
+            index += size;
+            let is_end = code.is_end();
+            codes.push(code);
+            if is_end {
+                break;
+            }
+        }
+        Ok(codes)
+    }
+}
+
+impl<'s, C: ArmUnwindCode> TryFrom<(Rva, &DataSegment<'s>)> for UnwindRecord<C> {
+    type Error = Error;
+
+    fn try_from((rva, segment): (Rva, &DataSegment<'s>)) -> Result<Self> {
+        let mut header = C::parse_header(segment.next_u32()?);
+        if header.needs_extension() {
+            header.extend(segment.next_u32()?);
+        }
+        let mut scopes = Vec::new();
+        if !header.single_epilog {
+            for _ in 0..header.epilog_count {
+                scopes.push(C::parse_epilog_scope(segment.next_u32()?));
+            }
+        }
+        let unwind_code_bytes = segment
+            .next_n(header.code_words as usize * 4)?
+            .as_ref()
+            .to_vec();
+        let prolog = Self::decode_codes(&unwind_code_bytes, 0)?;
+        let epilogs = if header.single_epilog {
+            vec![Epilog {
+                scope: None,
+                unwind_codes: Self::decode_codes(&unwind_code_bytes, header.epilog_count as usize)?,
+            }]
+        } else {
+            scopes
+                .into_iter()
+                .map(|scope| {
+                    Ok(Epilog {
+                        unwind_codes: Self::decode_codes(
+                            &unwind_code_bytes,
+                            scope.start_index as usize,
+                        )?,
+                        scope: Some(scope),
+                    })
+                })
+                .collect::<Result<_>>()?
+        };
+        let mut exception_handler = None;
+        let mut handler_data = None;
+        if header.has_exception_data {
+            exception_handler = Some(segment.next_u32()?);
+            handler_data = Some(rva + header.record_size() as Rva);
+        }
+        Ok(Self {
+            header,
+            unwind_code_bytes,
+            prolog,
+            epilogs,
+            exception_handler,
+            handler_data,
+        })
+    }
+}
+
+/// Returns an error for an unwind code that's cut off before all of its bytes.
+pub(crate) fn truncated_code(opcode: u8) -> Error {
+    Error::Other {
+        message: format!("Unwind code {:02x} is missing some of its bytes.", opcode),
+    }
+}
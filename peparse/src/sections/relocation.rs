@@ -1,14 +1,87 @@
 use crate::{
+    coff::constants::Machine,
     error::{Error, Result},
     Rva,
 };
-use core::convert::TryFrom;
-use segsource::{DataSegment, TryFromSegment};
+use core::convert::{TryFrom, TryInto};
+use segsource::DataSegment;
 
-impl_section_specifics! { RelocationSection, ".reloc", RelocationBlock }
+/// The size of the Page RVA and Block Size fields at the start of every block.
+const BLOCK_HEADER_SIZE: u32 = 8;
 
-#[derive(TryFromSegment, Debug, Clone)]
-#[from_seg(error(crate::Error), also_needs(is_32_plus: bool))]
+/// Every base relocation block in the image.
+#[derive(Debug, Clone, Default)]
+pub struct RelocationTable {
+    pub blocks: Vec<RelocationBlock>,
+
+    /// The block that parsing stopped at, if the table has one that's malformed. Every block
+    /// before it is still in `blocks`.
+    pub invalid_block: Option<InvalidRelocationBlock>,
+}
+
+impl RelocationTable {
+    /// Iterates over every relocation in the table, yielding the RVA each one must be applied to
+    /// along with its type. Relocations of type [`BaseRelocationType::Absolute`] are only used as
+    /// padding and are skipped.
+    pub fn relocations(&self) -> impl Iterator<Item = (Rva, BaseRelocationType)> + '_ {
+        self.blocks.iter().flat_map(|block| {
+            block
+                .relocations
+                .iter()
+                .filter(|r| r.base_type != BaseRelocationType::Absolute)
+                .map(move |r| (block.page_rva + r.offset as Rva, r.base_type))
+        })
+    }
+}
+
+/// A base relocation block that's too small, runs past the end of the table, or ends in the
+/// middle of a relocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidRelocationBlock {
+    /// The offset of the block from the start of the table.
+    pub offset: u32,
+    pub page_rva: Rva,
+    pub block_size: u32,
+}
+
+impl<'s> TryFrom<(Machine, &DataSegment<'s>)> for RelocationTable {
+    type Error = Error;
+
+    fn try_from((machine, segment): (Machine, &DataSegment<'s>)) -> Result<Self> {
+        let table_size = segment.remaining()?;
+        let mut blocks = Vec::new();
+        let mut invalid_block = None;
+        while segment.remaining()? >= BLOCK_HEADER_SIZE as usize {
+            let offset = (table_size - segment.remaining()?) as u32;
+            let page_rva = segment.next_u32()?;
+            let block_size = segment.next_u32()?;
+            // Anything after an empty block is just padding at the end of the section.
+            if page_rva == 0 && block_size == 0 {
+                break;
+            }
+            match RelocationBlock::parse(machine, page_rva, block_size, segment) {
+                Ok(block) => blocks.push(block),
+                // The sizes of the following blocks can't be trusted after a bad one, so parsing
+                // stops here.
+                Err(Error::InvalidRelocationBlock { .. }) => {
+                    invalid_block = Some(InvalidRelocationBlock {
+                        offset,
+                        page_rva,
+                        block_size,
+                    });
+                    break;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(Self {
+            blocks,
+            invalid_block,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RelocationBlock {
     /// The image base plus the page RVA is added to each offset to create the VA where the base
     /// relocation must be applied.
@@ -18,8 +91,49 @@ pub struct RelocationBlock {
     /// Size fields and the Type/Offset fields that follow.
     pub block_size: u32,
 
-    #[from_seg(parse_each, size(remaining))]
-    relocations: Vec<Relocation>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl RelocationBlock {
+    fn parse(
+        machine: Machine,
+        page_rva: Rva,
+        block_size: u32,
+        segment: &DataSegment<'_>,
+    ) -> Result<Self> {
+        if block_size < BLOCK_HEADER_SIZE
+            || block_size % 2 != 0
+            || (block_size - BLOCK_HEADER_SIZE) as usize > segment.remaining()?
+        {
+            return Err(Error::InvalidRelocationBlock {
+                page_rva,
+                block_size,
+            });
+        }
+        let num_slots = ((block_size - BLOCK_HEADER_SIZE) / 2) as usize;
+        let mut relocations = Vec::with_capacity(num_slots);
+        let mut slot = 0;
+        while slot < num_slots {
+            let mut relocation = Relocation::try_from((machine, segment))?;
+            slot += 1;
+            if relocation.base_type == BaseRelocationType::Highadj {
+                if slot == num_slots {
+                    return Err(Error::InvalidRelocationBlock {
+                        page_rva,
+                        block_size,
+                    });
+                }
+                relocation.low_bits = Some(segment.next_u16()?);
+                slot += 1;
+            }
+            relocations.push(relocation);
+        }
+        Ok(Self {
+            page_rva,
+            block_size,
+            relocations,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -27,40 +141,461 @@ pub struct Relocation {
     value: u16,
     pub base_type: BaseRelocationType,
     pub offset: u16,
+
+    /// For [`BaseRelocationType::Highadj`] relocations, the low 16 bits of the 32-bit value, which
+    /// are stored in the slot following the relocation.
+    pub low_bits: Option<u16>,
 }
 
-impl<'s> TryFrom<&DataSegment<'s>> for Relocation {
+impl<'s> TryFrom<(Machine, &DataSegment<'s>)> for Relocation {
     type Error = Error;
 
-    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+    fn try_from((machine, segment): (Machine, &DataSegment<'s>)) -> Result<Self> {
         let value = segment.next_u16()?;
-        let base_type = BaseRelocationType::try_from(value & 0xf000)?;
+        let base_type = BaseRelocationType::from_machine(machine, value >> 12);
         let offset = value & 0x0fff;
         Ok(Self {
             value,
             base_type,
             offset,
+            low_bits: None,
         })
     }
 }
 
-constants_enum! {
-    name: BaseRelocationType,
-    doc: "",
-    value_type: u16,
-    items: [
-        (Absolute, 0x0000, "The base relocation is skipped. This type can be used to pad a block."),
-        (High, 0x1000, "The base relocation adds the high 16 bits of the difference to the 16-bit field at offset. The 16-bit field represents the high value of a 32-bit word."),
-        (Low, 0x2000, "The base relocation adds the low 16 bits of the difference to the 16-bit field at offset. The 16-bit field represents the low half of a 32-bit word."),
-        (Highlow, 0x3000, "The base relocation applies all 32 bits of the difference to the 32-bit field at offset."),
-        (Highadj, 0x4000, "The base relocation adds the high 16 bits of the difference to the 16-bit field at offset. The 16-bit field represents the high value of a 32-bit word. The low 16 bits of the 32-bit value are stored in the 16-bit word that follows this base relocation. This means that this base relocation occupies two slots."),
-        (MipsJmpaddr, 0x5000, "The relocation interpretation is dependent on the machine type.\nWhen the machine type is MIPS, the base relocation applies to a MIPS jump instruction."),
-        //(ArmMov32, 0x5000, "This relocation is meaningful only when the machine type is ARM or Thumb. The base relocation applies the 32-bit address of a symbol across a consecutive MOVW/MOVT instruction pair."),
-        //(RiscvHigh20, 0x5000, "This relocation is only meaningful when the machine type is RISC-V. The base relocation applies to the high 20 bits of a 32-bit absolute address."),
-        (ThumbMov32, 0x7000, "This relocation is meaningful only when the machine type is Thumb. The base relocation applies the 32-bit address of a symbol to a consecutive MOVW/MOVT instruction pair."),
-        //(RiscvLow12I, 0x7000, "This relocation is only meaningful when the machine type is RISC-V. The base relocation applies to the low 12 bits of a 32-bit absolute address formed in RISC-V I-type instruction format."),
-        (RiscvLow12S, 0x8000, "This relocation is only meaningful when the machine type is RISC-V. The base relocation applies to the low 12 bits of a 32-bit absolute address formed in RISC-V S-type instruction format."),
-        (MipsJmpaddr16, 0x9000, "The relocation is only meaningful when the machine type is MIPS. The base relocation applies to a MIPS16 jump instruction."),
-        (Dir64, 0xa000, "The base relocation applies the difference to the 64-bit field at offset."),
-    ]
+/// The type of a base relocation. Several of the type values mean different things depending on
+/// the machine type, so this can't be parsed on its own; use [`BaseRelocationType::from_machine`]
+/// instead.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BaseRelocationType {
+    /// The base relocation is skipped. This type can be used to pad a block.
+    Absolute,
+    /// The base relocation adds the high 16 bits of the difference to the 16-bit field at offset.
+    /// The 16-bit field represents the high value of a 32-bit word.
+    High,
+    /// The base relocation adds the low 16 bits of the difference to the 16-bit field at offset.
+    /// The 16-bit field represents the low half of a 32-bit word.
+    Low,
+    /// The base relocation applies all 32 bits of the difference to the 32-bit field at offset.
+    Highlow,
+    /// The base relocation adds the high 16 bits of the difference to the 16-bit field at offset.
+    /// The 16-bit field represents the high value of a 32-bit word. The low 16 bits of the 32-bit
+    /// value are stored in the 16-bit word that follows this base relocation. This means that this
+    /// base relocation occupies two slots.
+    Highadj,
+    /// When the machine type is MIPS, the base relocation applies to a MIPS jump instruction.
+    MipsJmpaddr,
+    /// This relocation is meaningful only when the machine type is ARM or Thumb. The base
+    /// relocation applies the 32-bit address of a symbol across a consecutive MOVW/MOVT
+    /// instruction pair.
+    ArmMov32,
+    /// This relocation is only meaningful when the machine type is RISC-V. The base relocation
+    /// applies to the high 20 bits of a 32-bit absolute address.
+    RiscvHigh20,
+    /// This relocation is meaningful only when the machine type is Thumb. The base relocation
+    /// applies the 32-bit address of a symbol to a consecutive MOVW/MOVT instruction pair.
+    ThumbMov32,
+    /// This relocation is only meaningful when the machine type is RISC-V. The base relocation
+    /// applies to the low 12 bits of a 32-bit absolute address formed in RISC-V I-type
+    /// instruction format.
+    RiscvLow12I,
+    /// This relocation is only meaningful when the machine type is RISC-V. The base relocation
+    /// applies to the low 12 bits of a 32-bit absolute address formed in RISC-V S-type
+    /// instruction format.
+    RiscvLow12S,
+    /// This relocation is only meaningful when the machine type is LoongArch 32-bit. The base
+    /// relocation applies to a 32-bit absolute address formed in two consecutive instructions.
+    LoongArch32MarkLa,
+    /// This relocation is only meaningful when the machine type is LoongArch 64-bit. The base
+    /// relocation applies to a 64-bit absolute address formed in four consecutive instructions.
+    LoongArch64MarkLa,
+    /// The relocation is only meaningful when the machine type is MIPS. The base relocation
+    /// applies to a MIPS16 jump instruction.
+    MipsJmpaddr16,
+    /// The base relocation applies the difference to the 64-bit field at offset.
+    Dir64,
+    /// A type that's reserved or that has no meaning for the machine type. These can't be
+    /// applied, so they're kept as-is.
+    Reserved(u16),
+}
+
+impl BaseRelocationType {
+    /// Decodes the 4-bit type field of a relocation for the provided machine type.
+    pub fn from_machine(machine: Machine, value: u16) -> Self {
+        match value {
+            0 => Self::Absolute,
+            1 => Self::High,
+            2 => Self::Low,
+            3 => Self::Highlow,
+            4 => Self::Highadj,
+            5 if machine.is_mips() => Self::MipsJmpaddr,
+            5 if machine.is_arm32() => Self::ArmMov32,
+            5 if machine.is_riscv() => Self::RiscvHigh20,
+            7 if machine.is_arm32() => Self::ThumbMov32,
+            7 if machine.is_riscv() => Self::RiscvLow12I,
+            8 if machine.is_riscv() => Self::RiscvLow12S,
+            8 if machine == Machine::LoongArch32 => Self::LoongArch32MarkLa,
+            8 if machine == Machine::LoongArch64 => Self::LoongArch64MarkLa,
+            9 if machine.is_mips() => Self::MipsJmpaddr16,
+            10 => Self::Dir64,
+            other => Self::Reserved(other),
+        }
+    }
+
+    /// The number of bytes at the relocation's offset that are modified when it's applied.
+    pub fn patch_size(&self) -> usize {
+        match self {
+            Self::Absolute | Self::Reserved(_) => 0,
+            Self::High | Self::Low | Self::Highadj => 2,
+            Self::Highlow
+            | Self::MipsJmpaddr
+            | Self::MipsJmpaddr16
+            | Self::RiscvHigh20
+            | Self::RiscvLow12I
+            | Self::RiscvLow12S => 4,
+            Self::ArmMov32 | Self::ThumbMov32 | Self::LoongArch32MarkLa | Self::Dir64 => 8,
+            Self::LoongArch64MarkLa => 16,
+        }
+    }
+
+    /// Applies the relocation to `data`, which must start at the relocation's target and be at
+    /// least [`patch_size`](Self::patch_size) bytes long. `delta` is the difference between the
+    /// new and the preferred image bases, and `low_bits` is the extra slot that's used by
+    /// [`Highadj`](Self::Highadj) relocations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` is smaller than the patch size.
+    pub fn apply(&self, data: &mut [u8], delta: u64, low_bits: Option<u16>) {
+        let delta32 = delta as u32;
+        match self {
+            Self::Absolute | Self::Reserved(_) => {}
+            Self::High => {
+                let value = (read_u16(data, 0) as u32) << 16;
+                write_u16(data, 0, (value.wrapping_add(delta32) >> 16) as u16);
+            }
+            Self::Low => write_u16(data, 0, read_u16(data, 0).wrapping_add(delta32 as u16)),
+            Self::Highlow => write_u32(data, 0, read_u32(data, 0).wrapping_add(delta32)),
+            Self::Highadj => {
+                let value = ((read_u16(data, 0) as u32) << 16)
+                    .wrapping_add(low_bits.unwrap_or(0) as i16 as i32 as u32)
+                    .wrapping_add(delta32)
+                    .wrapping_add(0x8000);
+                write_u16(data, 0, (value >> 16) as u16);
+            }
+            Self::MipsJmpaddr => {
+                let inst = read_u32(data, 0);
+                let target = ((inst & 0x03ff_ffff) << 2).wrapping_add(delta32) >> 2;
+                write_u32(data, 0, (inst & 0xfc00_0000) | (target & 0x03ff_ffff));
+            }
+            Self::MipsJmpaddr16 => {
+                // The extended JAL instruction is two halfwords: the first holds bits 20:16 and
+                // 25:21 of the target and the second holds bits 15:0.
+                let hw1 = read_u16(data, 0) as u32;
+                let hw2 = read_u16(data, 2) as u32;
+                let target = ((hw1 & 0x1f) << 21) | (((hw1 >> 5) & 0x1f) << 16) | hw2;
+                let target = ((target << 2).wrapping_add(delta32) >> 2) & 0x03ff_ffff;
+                let hw1 = (hw1 & !0x3ff) | ((target >> 21) & 0x1f) | (((target >> 16) & 0x1f) << 5);
+                write_u16(data, 0, hw1 as u16);
+                write_u16(data, 2, target as u16);
+            }
+            Self::ArmMov32 => {
+                let movw = read_u32(data, 0);
+                let movt = read_u32(data, 4);
+                let value = (arm_mov_imm16(movt) << 16 | arm_mov_imm16(movw)).wrapping_add(delta32);
+                write_u32(data, 0, arm_mov_with_imm16(movw, value & 0xffff));
+                write_u32(data, 4, arm_mov_with_imm16(movt, value >> 16));
+            }
+            Self::ThumbMov32 => {
+                let movw = (read_u16(data, 0), read_u16(data, 2));
+                let movt = (read_u16(data, 4), read_u16(data, 6));
+                let value =
+                    (thumb_mov_imm16(movt) << 16 | thumb_mov_imm16(movw)).wrapping_add(delta32);
+                let movw = thumb_mov_with_imm16(movw, value & 0xffff);
+                let movt = thumb_mov_with_imm16(movt, value >> 16);
+                write_u16(data, 0, movw.0);
+                write_u16(data, 2, movw.1);
+                write_u16(data, 4, movt.0);
+                write_u16(data, 6, movt.1);
+            }
+            Self::RiscvHigh20 => {
+                // LUI/AUIPC keep the immediate in the top 20 bits of the instruction. The low 12
+                // bits are added by the following instruction as a signed value, so the high part
+                // has to be rounded the same way the linker does.
+                let inst = read_u32(data, 0);
+                let value =
+                    (inst & 0xffff_f000).wrapping_add(delta32.wrapping_add(0x800) & 0xffff_f000);
+                write_u32(data, 0, value | (inst & 0xfff));
+            }
+            Self::RiscvLow12I => {
+                let inst = read_u32(data, 0);
+                let imm = (inst >> 20).wrapping_add(delta32) & 0xfff;
+                write_u32(data, 0, (inst & 0x000f_ffff) | (imm << 20));
+            }
+            Self::RiscvLow12S => {
+                let inst = read_u32(data, 0);
+                let imm = ((inst >> 25) << 5 | ((inst >> 7) & 0x1f)).wrapping_add(delta32) & 0xfff;
+                write_u32(
+                    data,
+                    0,
+                    (inst & 0x01ff_f07f) | ((imm >> 5) << 25) | ((imm & 0x1f) << 7),
+                );
+            }
+            Self::LoongArch32MarkLa => {
+                // lu12i.w holds bits 31:12 and ori holds bits 11:0.
+                let lu12i = read_u32(data, 0);
+                let ori = read_u32(data, 4);
+                let value =
+                    (loongarch_si20(lu12i) << 12 | loongarch_ui12(ori)).wrapping_add(delta32);
+                write_u32(data, 0, loongarch_with_si20(lu12i, value >> 12));
+                write_u32(data, 4, loongarch_with_ui12(ori, value & 0xfff));
+            }
+            Self::LoongArch64MarkLa => {
+                // lu12i.w, ori, lu32i.d and lu52i.d hold bits 31:12, 11:0, 51:32 and 63:52.
+                let lu12i = read_u32(data, 0);
+                let ori = read_u32(data, 4);
+                let lu32i = read_u32(data, 8);
+                let lu52i = read_u32(data, 12);
+                let value = ((loongarch_ui12(lu52i) as u64) << 52
+                    | (loongarch_si20(lu32i) as u64) << 32
+                    | (loongarch_si20(lu12i) as u64) << 12
+                    | loongarch_ui12(ori) as u64)
+                    .wrapping_add(delta);
+                write_u32(data, 0, loongarch_with_si20(lu12i, (value >> 12) as u32));
+                write_u32(data, 4, loongarch_with_ui12(ori, value as u32 & 0xfff));
+                write_u32(data, 8, loongarch_with_si20(lu32i, (value >> 32) as u32));
+                write_u32(data, 12, loongarch_with_ui12(lu52i, (value >> 52) as u32));
+            }
+            Self::Dir64 => {
+                let value = u64::from_le_bytes(data[..8].try_into().unwrap());
+                data[..8].copy_from_slice(&value.wrapping_add(delta).to_le_bytes());
+            }
+        }
+    }
+}
+
+#[inline]
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+#[inline]
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline]
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Gets the 16-bit immediate (imm4:imm12) of an ARM MOVW or MOVT instruction.
+#[inline]
+fn arm_mov_imm16(inst: u32) -> u32 {
+    ((inst >> 4) & 0xf000) | (inst & 0x0fff)
+}
+
+#[inline]
+fn arm_mov_with_imm16(inst: u32, imm: u32) -> u32 {
+    (inst & 0xfff0_f000) | ((imm & 0xf000) << 4) | (imm & 0x0fff)
+}
+
+/// Gets the 16-bit immediate (imm4:i:imm3:imm8) of a Thumb-2 MOVW or MOVT instruction.
+#[inline]
+fn thumb_mov_imm16((hw1, hw2): (u16, u16)) -> u32 {
+    let (hw1, hw2) = (hw1 as u32, hw2 as u32);
+    ((hw1 & 0x000f) << 12) | ((hw1 & 0x0400) << 1) | ((hw2 & 0x7000) >> 4) | (hw2 & 0x00ff)
+}
+
+#[inline]
+fn thumb_mov_with_imm16((hw1, hw2): (u16, u16), imm: u32) -> (u16, u16) {
+    let hw1 = (hw1 as u32 & 0xfbf0) | ((imm >> 12) & 0x000f) | ((imm >> 1) & 0x0400);
+    let hw2 = (hw2 as u32 & 0x8f00) | ((imm << 4) & 0x7000) | (imm & 0x00ff);
+    (hw1 as u16, hw2 as u16)
+}
+
+/// Gets the 20-bit immediate of a LoongArch lu12i.w or lu32i.d instruction.
+#[inline]
+fn loongarch_si20(inst: u32) -> u32 {
+    (inst >> 5) & 0xf_ffff
+}
+
+#[inline]
+fn loongarch_with_si20(inst: u32, imm: u32) -> u32 {
+    (inst & !(0xf_ffff << 5)) | ((imm & 0xf_ffff) << 5)
+}
+
+/// Gets the 12-bit immediate of a LoongArch ori or lu52i.d instruction.
+#[inline]
+fn loongarch_ui12(inst: u32) -> u32 {
+    (inst >> 10) & 0xfff
+}
+
+#[inline]
+fn loongarch_with_ui12(inst: u32, imm: u32) -> u32 {
+    (inst & !(0xfff << 10)) | ((imm & 0xfff) << 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse_table(machine: Machine, data: &[u8]) -> RelocationTable {
+        let source =
+            BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little).unwrap();
+        RelocationTable::try_from((machine, &source.all().unwrap())).unwrap()
+    }
+
+    fn apply(base_type: BaseRelocationType, data: &[u8], delta: u64) -> Vec<u8> {
+        let mut data = data.to_vec();
+        base_type.apply(&mut data, delta, None);
+        data
+    }
+
+    #[test]
+    fn stops_at_first_invalid_block() {
+        let table = parse_table(
+            Machine::I386,
+            &[
+                0x00, 0x10, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x04, 0x30, 0x00, 0x00, //
+                0x00, 0x20, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, //
+                0x00, 0x30, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x08, 0x30,
+            ],
+        );
+        assert_eq!(table.blocks.len(), 1);
+        assert_eq!(
+            table.invalid_block,
+            Some(InvalidRelocationBlock {
+                offset: 12,
+                page_rva: 0x2000,
+                block_size: 4,
+            })
+        );
+        assert_eq!(
+            table.relocations().collect::<Vec<_>>(),
+            vec![(0x1004, BaseRelocationType::Highlow)]
+        );
+    }
+
+    #[test]
+    fn highadj_without_low_bits_is_invalid() {
+        let table = parse_table(
+            Machine::R4000,
+            &[0x00, 0x10, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x40],
+        );
+        assert!(table.blocks.is_empty());
+        assert_eq!(table.invalid_block.map(|block| block.offset), Some(0));
+    }
+
+    #[test]
+    fn block_size_past_end_of_table_is_invalid() {
+        let table = parse_table(
+            Machine::Amd64,
+            &[0x00, 0x10, 0x00, 0x00, 0xf0, 0xff, 0xff, 0xff, 0x00, 0xa0],
+        );
+        assert!(table.blocks.is_empty());
+        assert!(table.invalid_block.is_some());
+    }
+
+    #[test]
+    fn machine_specific_types() {
+        assert_eq!(
+            BaseRelocationType::from_machine(Machine::RiscV64, 5),
+            BaseRelocationType::RiscvHigh20
+        );
+        assert_eq!(
+            BaseRelocationType::from_machine(Machine::ArmNt, 7),
+            BaseRelocationType::ThumbMov32
+        );
+        assert_eq!(
+            BaseRelocationType::from_machine(Machine::Amd64, 5),
+            BaseRelocationType::Reserved(5)
+        );
+        assert_eq!(
+            BaseRelocationType::from_machine(Machine::Amd64, 6),
+            BaseRelocationType::Reserved(6)
+        );
+    }
+
+    #[test]
+    fn reserved_types_are_not_applied() {
+        let base_type = BaseRelocationType::Reserved(11);
+        assert_eq!(base_type.patch_size(), 0);
+        assert_eq!(apply(base_type, &[], 0x1000), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn apply_highlow_and_dir64() {
+        assert_eq!(
+            apply(
+                BaseRelocationType::Highlow,
+                &0x0040_1000u32.to_le_bytes(),
+                0x1_0000
+            ),
+            0x0041_1000u32.to_le_bytes()
+        );
+        assert_eq!(
+            apply(
+                BaseRelocationType::Highlow,
+                &0xffff_f000u32.to_le_bytes(),
+                0x2000
+            ),
+            0x0000_1000u32.to_le_bytes()
+        );
+        assert_eq!(
+            apply(
+                BaseRelocationType::Dir64,
+                &0x1_4000_1000u64.to_le_bytes(),
+                0x7ff0_0000_0000
+            ),
+            0x7ff1_4000_1000u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn apply_highadj_carries_the_signed_low_bits() {
+        let mut data = 0x0040u16.to_le_bytes();
+        BaseRelocationType::Highadj.apply(&mut data, 0x1_0000, Some(0x8000));
+        // 0x003f8000 + 0x10000 is 0x00408000, whose high half becomes 0x0041 once the low half is
+        // sign extended.
+        assert_eq!(data, 0x0041u16.to_le_bytes());
+    }
+
+    #[test]
+    fn apply_arm_mov32() {
+        // movw r0, #0x1234; movt r0, #0x0040
+        let mut data = [0xe301_0234u32.to_le_bytes(), 0xe340_0040u32.to_le_bytes()].concat();
+        BaseRelocationType::ArmMov32.apply(&mut data, 0x1_f000, None);
+        assert_eq!(
+            data,
+            [0xe300_0234u32.to_le_bytes(), 0xe340_0042u32.to_le_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn apply_riscv_high20_rounds_like_the_linker() {
+        // lui t0, 0x10
+        let lui = 0x0001_02b7u32.to_le_bytes();
+        assert_eq!(
+            apply(BaseRelocationType::RiscvHigh20, &lui, 0x7ff),
+            0x0001_02b7u32.to_le_bytes()
+        );
+        assert_eq!(
+            apply(BaseRelocationType::RiscvHigh20, &lui, 0x800),
+            0x0001_12b7u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn apply_panics_on_short_data() {
+        BaseRelocationType::Dir64.apply(&mut [0; 4], 1, None);
+    }
 }
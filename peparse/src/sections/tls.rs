@@ -2,7 +2,7 @@ use super::SectionFlags;
 use crate::{
     error::{Error, Result},
     util::next_different_sizes,
-    Va,
+    Rva, Va,
 };
 use segsource::{DataSegment, TryFromSegment};
 
@@ -52,6 +52,28 @@ pub struct TlsDirectory {
     pub characteristics: SectionFlags,
 }
 
+/// A single entry from the TLS callback array.
+#[derive(Debug, Clone)]
+pub struct TlsCallback {
+    /// The VA of the callback function.
+    pub va: Va,
+
+    /// The RVA of the callback function, or `None` if the callback points outside of the image.
+    pub rva: Option<Rva>,
+
+    /// The name of the section that contains the callback function, if any.
+    pub section: Option<String>,
+}
+
+impl TlsCallback {
+    /// If the callback points somewhere outside of the image, which the loader will happily call
+    /// anyway.
+    #[inline]
+    pub fn is_outside_image(&self) -> bool {
+        self.rva.is_none()
+    }
+}
+
 constants_enum! {
     name: TlsCallbackFunction,
     doc: "Possible reserved parameter values for TLS callback function.",
@@ -64,3 +86,51 @@ constants_enum! {
 
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use core::convert::TryFrom;
+    use segsource::{BytesSource, Endidness, Source as _};
+
+    fn parse(is_32_plus: bool, data: &[u8]) -> TlsDirectory {
+        let source =
+            BytesSource::from_bytes(Bytes::from(data.to_vec()), Endidness::Little).unwrap();
+        TlsDirectory::try_from((is_32_plus, &source.all().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn pe32_directory_has_32_bit_addresses() {
+        let tls = parse(
+            false,
+            &[
+                0x00, 0x30, 0x40, 0x00, 0x10, 0x30, 0x40, 0x00, 0x20, 0x30, 0x40, 0x00, //
+                0x00, 0x20, 0x40, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x00,
+            ],
+        );
+        assert_eq!(tls.raw_data_start_va, 0x40_3000);
+        assert_eq!(tls.raw_data_end_va, 0x40_3010);
+        assert_eq!(tls.address_of_index, 0x40_3020);
+        assert_eq!(tls.address_of_callbacks, 0x40_2000);
+        assert_eq!(tls.size_of_zero_fill, 8);
+        assert_eq!(tls.characteristics, SectionFlags::from(0x0030_0000));
+    }
+
+    #[test]
+    fn pe32_plus_directory_has_64_bit_addresses() {
+        let mut data = Vec::new();
+        for va in &[
+            0x1_4000_3000u64,
+            0x1_4000_3010,
+            0x1_4000_3020,
+            0x1_4000_2000,
+        ] {
+            data.extend_from_slice(&va.to_le_bytes());
+        }
+        data.extend_from_slice(&[0; 8]);
+        let tls = parse(true, &data);
+        assert_eq!(tls.address_of_callbacks, 0x1_4000_2000);
+        assert_eq!(tls.size_of_zero_fill, 0);
+    }
+}
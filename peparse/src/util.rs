@@ -1,6 +1,6 @@
-use crate::{Result, Rva};
+use crate::{Error, Result, Rva};
 use segsource::{marker::Integer, DataSegment, Endidness};
-use std::vec::IntoIter as VecIter;
+use std::{convert::TryFrom, fmt, vec::IntoIter as VecIter};
 
 pub(crate) fn next_different_sizes<I1, I2>(parse_smaller: bool, segment: &DataSegment) -> Result<I2>
 where
@@ -28,6 +28,27 @@ pub(crate) fn parse_utf8_string<const N: usize>(
     }
 }
 
+/// Reads bytes up to a null terminator (or the end of the segment), not including the terminator.
+pub(crate) fn next_null_terminated(segment: &DataSegment<'_>) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while segment.remaining()? > 0 {
+        match segment.next_u8()? {
+            0 => break,
+            byte => bytes.push(byte),
+        }
+    }
+    Ok(bytes)
+}
+
+/// The size in bytes of a table of `count` entries, failing instead of overflowing for counts that
+/// come from a corrupt image.
+pub(crate) fn table_size(count: u64, entry_size: usize) -> Result<usize> {
+    usize::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_mul(entry_size))
+        .ok_or(Error::TableTooLarge { count, entry_size })
+}
+
 pub(crate) fn iter_to_result<V, I>(mut iter: I) -> Result<VecIter<V>>
 where
     I: Iterator<Item = Result<V>>,
@@ -51,10 +72,109 @@ where
     }
 }
 
-pub fn align(rva: Rva, align_to: u32) -> Rva {
-    if rva % align_to != 0 {
-        (rva + align_to - 1) & !(align_to - 1)
-    } else {
-        rva
+/// Rounds `rva` up to a multiple of `align_to`, which has to be a power of two. Returns `None`
+/// if it isn't one, or if the result doesn't fit in 32 bits.
+pub fn align(rva: Rva, align_to: u32) -> Option<Rva> {
+    if !align_to.is_power_of_two() {
+        return None;
+    }
+    Some(rva.checked_add(align_to - 1)? & !(align_to - 1))
+}
+
+/// A GUID, as it's laid out in memory on Windows.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl Guid {
+    /// Formats the GUID as 32 uppercase hex digits without any separators, which is how it's
+    /// used in symbol server keys.
+    pub fn to_simple_string(&self) -> String {
+        format!(
+            "{:08X}{:04X}{:04X}{}",
+            self.data1,
+            self.data2,
+            self.data3,
+            hex::encode_upper(self.data4)
+        )
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{}-{}",
+            self.data1,
+            self.data2,
+            self.data3,
+            hex::encode_upper(&self.data4[..2]),
+            hex::encode_upper(&self.data4[2..])
+        )
+    }
+}
+
+impl<'s> TryFrom<&DataSegment<'s>> for Guid {
+    type Error = Error;
+
+    fn try_from(segment: &DataSegment<'s>) -> Result<Self> {
+        Ok(Self {
+            data1: segment.next_u32()?,
+            data2: segment.next_u16()?,
+            data3: segment.next_u16()?,
+            data4: segment.next_n_as_array::<8>()?,
+        })
+    }
+}
+
+/// Builds the path of a file relative to the root of a symbol store, laid out the way Microsoft's
+/// symbol server expects (`name/KEY/name`).
+pub fn symbol_store_path(name: &str, key: &str) -> String {
+    format!("{}/{}/{}", name, key, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_rounds_up() {
+        assert_eq!(align(0x1001, 0x1000), Some(0x2000));
+        assert_eq!(align(0x2000, 0x1000), Some(0x2000));
+        assert_eq!(align(0, 0x200), Some(0));
+        assert_eq!(align(u32::MAX - 0x1ff, 0x200), Some(u32::MAX - 0x1ff));
+    }
+
+    #[test]
+    fn align_rejects_overflow_and_bad_alignments() {
+        assert_eq!(align(u32::MAX - 1, 0x200), None);
+        assert_eq!(align(0x1234, 0), None);
+        assert_eq!(align(0x1234, 0x300), None);
+    }
+
+    #[test]
+    fn table_size_overflow_is_an_error() {
+        assert_eq!(table_size(4, 8).unwrap(), 32);
+        assert!(matches!(
+            table_size(u64::MAX, 8),
+            Err(Error::TableTooLarge { .. })
+        ));
+        assert!(table_size(usize::MAX as u64 / 2 + 1, 2).is_err());
+    }
+
+    #[test]
+    fn guid_strings() {
+        let guid = Guid {
+            data1: 0x1234_5678,
+            data2: 0x9abc,
+            data3: 0xdef0,
+            data4: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+        };
+        assert_eq!(guid.to_simple_string(), "123456789ABCDEF00123456789ABCDEF");
+        assert_eq!(guid.to_string(), "12345678-9ABC-DEF0-0123-456789ABCDEF");
     }
 }